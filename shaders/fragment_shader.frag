@@ -15,47 +15,12 @@ uniform vec4 iMouse;
 #define MIN_DIST 0.01
 #define MAX_DIST 200.
 in vec2 fragCoord;
-out vec4 fragColor;
+layout(location = 0) out vec4 fragColor;
+// surface under the pixel, orbit camera turns around it
+layout(location = 1) out vec4 hit_point;
 
-mat2 rotate(float angle) {
-    float si = sin(angle);
-    float co = cos(angle);
-    return mat2(co, -si, si, co);
-}
-
-float sd_capsule(vec3 p, vec3 b, float radius) {
-
-    float d = dot(b, p) / dot(b, b);
-    if (d <= 0.0) return length(p) - radius;
-    if (d >= 1.0) return length(p - b) - radius;
-    return length((b * d) - p) - radius;
-}
-
-float sd_box(vec3 p, vec3 size) {
-    return length(max(abs(p) - size, 0.0));
-}
-
-float sd_torus(vec3 p, float radius1, float radius2) {
-    float x = length(p.xz) - radius1;
-    return length(vec2(x, p.y)) - radius2;
-}
-
-float sd_sphere(vec3 p, float radius) {
-    return length(p) - radius;
-}
-
-float sd_inf_cylinder(vec3 p, float radius) {
-    return length(p.xz) - radius;
-}
-
-float sd_cylinder(vec3 ap, vec3 ab, float radius) {
-    float t = dot(ap, ab) / dot(ab, ab);
-    float d = length(ab * t - ap) - radius;
-    float y = (abs(t - 0.5) - 0.5) * length(ab);
-    float e = length(max(vec2(d,y), 0));
-    float i = min(max(d,y), 0);
-    return e + i;
-}
+#include "lib/sdf.glsl"
+#include "lib/camera.glsl"
 
 
 float get_dist(vec3 p) {
@@ -140,16 +105,15 @@ float get_lighting(vec3 point) {
 }
 
 void main() {
-    vec2 uv_coord = (((fragCoord / iResolution.xy) - 0.5) * 2);
-    uv_coord.x *= iResolution.x / iResolution.y; 
-    
-    vec3 ray_direction = normalize(vec3(uv_coord, 1.0));
+    vec3 ray_origin, ray_direction;
+    camera_ray(fragCoord, ray_origin, ray_direction);
 
-    ray_direction = ray_direction * rotation_matrix;
+    float dist = ray_march(ray_origin, ray_direction);
 
-    float dist = ray_march(camera_position, ray_direction);
+    vec3 point = ray_origin + ray_direction * dist;
 
-    float brightness = get_lighting(camera_position + ray_direction * dist);
+    float brightness = get_lighting(point);
 
     fragColor = vec4(ray_direction * brightness, 1.0);
+    hit_point = vec4(point, dist < MAX_DIST ? 1.0 : 0.0);
 }
\ No newline at end of file
@@ -0,0 +1,54 @@
+// rays of the camera set up in the app: perspective with fov, or parallel
+// rays of orthographic views, both with lens shift and depth of field.
+// two-point perspective and isometric views come as these two with
+// adjusted rotation and shift.
+//
+//   #include "lib/camera.glsl"
+//   ...
+//   vec3 ray_origin, ray_direction;
+//   camera_ray(fragCoord, ray_origin, ray_direction);
+//
+// camera_position, rotation_matrix and iResolution are declared
+// by the shader as usual, uniforms below come with the include
+
+// 0 perspective, 1 orthographic
+uniform int projection;
+// vertical field of view in degrees
+uniform float fov;
+// distance of the image plane in parts of half its height, 1 / tan(fov / 2).
+// the app computes it, so fov 90 gives exactly 1
+uniform float image_distance;
+// image shift in parts of its height, like a shift lens
+uniform vec2 lens_shift;
+// height of orthographic view in scene units. parallel rays start
+// on the plane of the camera, so it has to stay outside the scene
+uniform float view_height;
+// depth of field: lens radius in scene units, 0 for a pinhole,
+// and distance of the sharp plane along the view direction
+uniform float aperture_radius;
+uniform float focus_distance;
+// point of this sample on the unit disk of the lens and its offset
+// inside the pixel, frames of many samples average them
+uniform vec2 lens_sample;
+uniform vec2 pixel_jitter;
+
+void camera_ray(vec2 frag_coord, out vec3 ray_origin, out vec3 ray_direction) {
+    vec2 uv = ((frag_coord + pixel_jitter) / iResolution.xy - 0.5) * 2.;
+    uv.x *= iResolution.x / iResolution.y;
+    uv += lens_shift * 2.;
+
+    if (projection == 1) {
+        ray_origin = camera_position + vec3(uv * view_height / 2., 0.) * rotation_matrix;
+        ray_direction = vec3(0., 0., 1.) * rotation_matrix;
+    } else {
+        ray_origin = camera_position;
+        ray_direction = normalize(vec3(uv, image_distance)) * rotation_matrix;
+    }
+
+    if (aperture_radius > 0.) {
+        vec3 forward = vec3(0., 0., 1.) * rotation_matrix;
+        vec3 focus_point = ray_origin + ray_direction * focus_distance / dot(ray_direction, forward);
+        ray_origin += vec3(lens_sample * aperture_radius, 0.) * rotation_matrix;
+        ray_direction = normalize(focus_point - ray_origin);
+    }
+}
@@ -0,0 +1,92 @@
+// sphere tracing of `float map(vec3 p)`, which the shader defines.
+//
+//   #define MAX_DIST 500.
+//   #include "lib/march.glsl"
+//
+// MAX_STEPS, MIN_DIST and MAX_DIST can be defined before the include.
+//
+// orbit camera picks its pivot from the surface under the cursor
+// when the shader tells where the surface is:
+//
+//   layout(location = 0) out vec4 fragColor;
+//   layout(location = 1) out vec4 hit_point;
+//   ...
+//   vec2 hit = ray_march(camera_position, ray_direction);
+//   hit_point = vec4(camera_position + ray_direction * hit.x, hit.x < MAX_DIST ? 1. : 0.);
+
+#ifndef MAX_STEPS
+#define MAX_STEPS 150
+#endif
+
+#ifndef MIN_DIST
+#define MIN_DIST 0.001
+#endif
+
+#ifndef MAX_DIST
+#define MAX_DIST 200.
+#endif
+
+float map(vec3 p);
+
+// tetrahedron of four samples around p
+vec3 get_normal(vec3 p) {
+    vec2 e = vec2(0.001, -0.001);
+
+    return normalize(
+        e.yxx * map(p + e.yxx) +
+        e.xyx * map(p + e.xyx) +
+        e.xxy * map(p + e.xxy) +
+        e.yyy * map(p + e.yyy)
+    );
+}
+
+// x is distance along the ray, y is number of steps.
+// distance is past MAX_DIST when nothing is hit
+vec2 ray_march(vec3 ray_origin, vec3 ray_direction) {
+    float total_distance = 0.;
+
+    int i = 0;
+    for (; i < MAX_STEPS; i++) {
+        float d = map(ray_origin);
+        total_distance += d;
+
+        if (d < MIN_DIST || total_distance > MAX_DIST) {
+            return vec2(total_distance, float(i));
+        }
+
+        ray_origin += ray_direction * d;
+    }
+
+    return vec2(total_distance, float(i));
+}
+
+// soft shadow towards a light, 1 is fully lit. larger k gives harder shadows
+float soft_shadow(vec3 p, vec3 light_direction, float k) {
+    float result = 1.0;
+    float t = MIN_DIST * 10.;
+
+    for (int i = 0; i < MAX_STEPS && t < MAX_DIST; i++) {
+        float d = map(p + light_direction * t);
+        if (d < MIN_DIST) {
+            return 0.0;
+        }
+        result = min(result, k * d / t);
+        t += d;
+    }
+
+    return result;
+}
+
+// ambient occlusion from five samples along the normal, 1 is not occluded
+float ambient_occlusion(vec3 p, vec3 normal) {
+    float occlusion = 0.0;
+    float weight = 1.0;
+
+    for (int i = 1; i <= 5; i++) {
+        float h = 0.02 * float(i * i);
+        occlusion += (h - map(p + normal * h)) * weight;
+        weight *= 0.7;
+    }
+
+    return clamp(1.0 - 2.0 * occlusion, 0.0, 1.0);
+}
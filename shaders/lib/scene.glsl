@@ -0,0 +1,116 @@
+// ray marching template of scenes built from primitives, see scene.rs.
+// the generated materials, lights and map() of the scene replace the
+// marker line below the includes, march.glsl declares map() for them.
+// map_material() of the scene gives distance in x and material in y
+#version 420
+
+uniform vec3 camera_position;
+uniform mat3 rotation_matrix;
+uniform vec3 iResolution;
+uniform float iTime;
+
+#define MAX_DIST 300.
+in vec2 fragCoord;
+layout(location = 0) out vec4 fragColor;
+// surface under the pixel, orbit camera turns around it
+layout(location = 1) out vec4 hit_point;
+
+#include "lib/sdf.glsl"
+#include "lib/march.glsl"
+#include "lib/camera.glsl"
+
+// operators on distance and material, the material of
+// the surface that is left goes with the distance
+
+vec2 op_union(vec2 a, vec2 b) {
+    return a.x < b.x ? a : b;
+}
+
+vec2 op_subtract(vec2 a, vec2 b) {
+    return vec2(op_subtract(a.x, b.x), a.y);
+}
+
+vec2 op_intersect(vec2 a, vec2 b) {
+    return a.x > b.x ? a : b;
+}
+
+vec2 op_smooth_union(vec2 a, vec2 b, float k) {
+    return vec2(op_smooth_union(a.x, b.x, k), a.x < b.x ? a.y : b.y);
+}
+
+vec2 op_smooth_subtract(vec2 a, vec2 b, float k) {
+    return vec2(op_smooth_subtract(a.x, b.x, k), a.y);
+}
+
+vec2 op_smooth_intersect(vec2 a, vec2 b, float k) {
+    return vec2(op_smooth_intersect(a.x, b.x, k), a.x > b.x ? a.y : b.y);
+}
+
+// scene
+
+const vec3 SKY_COLOR = vec3(0.55, 0.7, 0.9);
+
+vec3 sky(vec3 direction) {
+    return mix(SKY_COLOR, vec3(0.9), pow(1.0 - max(direction.y, 0.0), 4.0));
+}
+
+// soft shadow of a point light, surfaces behind the light don't cast it
+float light_shadow(vec3 p, vec3 light_direction, float light_distance, float k) {
+    float result = 1.0;
+    float t = MIN_DIST * 10.;
+
+    for (int i = 0; i < MAX_STEPS && t < light_distance; i++) {
+        float d = map(p + light_direction * t);
+        if (d < MIN_DIST) {
+            return 0.0;
+        }
+        result = min(result, k * d / t);
+        t += d;
+    }
+
+    return result;
+}
+
+vec3 shade(vec3 point, vec3 ray_direction, float dist) {
+    vec3 normal = get_normal(point);
+    vec3 albedo = MATERIAL_COLORS[int(map_material(point).y + 0.5)];
+    vec3 start = point + normal * MIN_DIST * 10.;
+
+    float sun = max(dot(normal, SUN_DIRECTION), 0.0);
+    if (sun > 0.0) {
+        sun *= soft_shadow(start, SUN_DIRECTION, 16.0);
+    }
+    float ambient = (0.5 + 0.5 * normal.y) * ambient_occlusion(point, normal);
+    vec3 light = SUN_COLOR * sun + SKY_COLOR * ambient * 0.4;
+
+    // point lights fade with square of the distance
+    for (int i = 0; i < LIGHT_COUNT; i++) {
+        vec3 to_light = LIGHT_POSITIONS[i] - point;
+        float light_distance = length(to_light);
+        vec3 light_direction = to_light / light_distance;
+
+        float diffuse = max(dot(normal, light_direction), 0.0);
+        if (diffuse > 0.0) {
+            diffuse *= light_shadow(start, light_direction, light_distance, 16.0);
+        }
+        light += LIGHT_COLORS[i] * diffuse / (light_distance * light_distance);
+    }
+
+    vec3 color = albedo * light;
+    // far surfaces fade into the sky
+    return mix(color, sky(ray_direction), 1.0 - exp(-dist * 0.004));
+}
+
+void main() {
+    vec3 ray_origin, ray_direction;
+    camera_ray(fragCoord, ray_origin, ray_direction);
+
+    float dist = ray_march(ray_origin, ray_direction).x;
+    vec3 point = ray_origin + ray_direction * dist;
+    bool hit = dist < MAX_DIST;
+
+    vec3 color = hit ? shade(point, ray_direction, dist) : sky(ray_direction);
+
+    fragColor = vec4(pow(color, vec3(1.0 / 2.2)), 1.0);
+    hit_point = vec4(point, hit ? 1.0 : 0.0);
+}
@@ -0,0 +1,116 @@
+// signed distance functions and operators on them
+//
+//   #include "lib/sdf.glsl"
+//
+// distances are negative inside the shape. shapes are centered at
+// the origin, move the point instead: sd_sphere(p - center, radius)
+
+// rotation in a plane: p.xz *= rotate(angle)
+mat2 rotate(float angle) {
+    float c = cos(angle);
+    float s = sin(angle);
+    return mat2(c, -s, s, c);
+}
+
+// primitives
+
+float sd_sphere(vec3 p, float radius) {
+    return length(p) - radius;
+}
+
+// b is half of the box size
+float sd_box(vec3 p, vec3 b) {
+    vec3 d = abs(p) - b;
+    return min(max(d.x, max(d.y, d.z)), 0.0) + length(max(d, 0.0));
+}
+
+float sd_round_box(vec3 p, vec3 b, float radius) {
+    return sd_box(p, b - radius) - radius;
+}
+
+// ring in xz plane
+float sd_torus(vec3 p, float radius1, float radius2) {
+    float x = length(p.xz) - radius1;
+    return length(vec2(x, p.y)) - radius2;
+}
+
+// segment from the origin to b
+float sd_capsule(vec3 p, vec3 b, float radius) {
+    float d = dot(b, p) / dot(b, b);
+    if (d <= 0.0) return length(p) - radius;
+    if (d >= 1.0) return length(p - b) - radius;
+    return length((b * d) - p) - radius;
+}
+
+// infinite along y
+float sd_inf_cylinder(vec3 p, float radius) {
+    return length(p.xz) - radius;
+}
+
+// flat ends, axis from the origin to ab
+float sd_cylinder(vec3 ap, vec3 ab, float radius) {
+    float t = dot(ap, ab) / dot(ab, ab);
+    float d = length(ab * t - ap) - radius;
+    float y = (abs(t - 0.5) - 0.5) * length(ab);
+    float e = length(max(vec2(d, y), 0.0));
+    float i = min(max(d, y), 0.0);
+    return e + i;
+}
+
+// normal must be normalized, plane is at distance h from the origin
+float sd_plane(vec3 p, vec3 normal, float h) {
+    return dot(p, normal) - h;
+}
+
+float sd_octahedron(vec3 p, float size) {
+    p = abs(p);
+    return (p.x + p.y + p.z - size) * 0.57735027;
+}
+
+// operators
+
+float op_union(float a, float b) {
+    return min(a, b);
+}
+
+// a with b cut out of it
+float op_subtract(float a, float b) {
+    return max(a, -b);
+}
+
+float op_intersect(float a, float b) {
+    return max(a, b);
+}
+
+// k is the size of the blend
+float op_smooth_union(float a, float b, float k) {
+    float h = clamp(0.5 + 0.5 * (b - a) / k, 0.0, 1.0);
+    return mix(b, a, h) - k * h * (1.0 - h);
+}
+
+float op_smooth_subtract(float a, float b, float k) {
+    float h = clamp(0.5 - 0.5 * (a + b) / k, 0.0, 1.0);
+    return mix(a, -b, h) + k * h * (1.0 - h);
+}
+
+float op_smooth_intersect(float a, float b, float k) {
+    float h = clamp(0.5 - 0.5 * (b - a) / k, 0.0, 1.0);
+    return mix(b, a, h) + k * h * (1.0 - h);
+}
+
+// shell of the given thickness around the surface
+float op_onion(float d, float thickness) {
+    return abs(d) - thickness;
+}
+
+// domain operators, applied to the point before the distance function
+
+// infinite copies every period units: sd_sphere(op_repeat(p, vec3(7.0)), 1.0)
+vec3 op_repeat(vec3 p, vec3 period) {
+    return mod(p + 0.5 * period, period) - 0.5 * period;
+}
+
+// mirror copy of negative x side
+vec3 op_mirror_x(vec3 p) {
+    return vec3(abs(p.x), p.yz);
+}
@@ -0,0 +1,21 @@
+// previous frame of this buffer is in iChannel0
+
+void mainImage( out vec4 fragColor, in vec2 fragCoord )
+{
+    vec2 uv = (2.0 * fragCoord - iResolution.xy) / iResolution.y;
+
+    vec3 previous = texture(iChannel0, fragCoord / iResolution.xy).rgb;
+
+    vec3 col = previous * 0.96;
+
+    for (int i = 0; i < 3; i++)
+    {
+        float t = iTime * (1.0 + 0.3 * float(i)) + float(i) * 2.1;
+        vec2 dot_position = vec2(sin(t * 1.3), cos(t * 0.9)) * 0.7;
+        float d = length(uv - dot_position);
+        vec3 dot_color = 0.5 + 0.5 * cos(vec3(0, 2, 4) + float(i) * 2.0);
+        col += dot_color * smoothstep(0.06, 0.0, d);
+    }
+
+    fragColor = vec4(col, 1.0);
+}
@@ -0,0 +1,16 @@
+void mainImage( out vec4 fragColor, in vec2 fragCoord )
+{
+    vec2 uv = fragCoord / iResolution.xy;
+    vec2 texel = 1.0 / iChannelResolution[0].xy;
+
+    vec3 col = texture(iChannel0, uv).rgb;
+
+    vec3 glow = vec3(0.0);
+    for (int x = -2; x <= 2; x++)
+        for (int y = -2; y <= 2; y++)
+            glow += texture(iChannel0, uv + vec2(x, y) * texel * 2.0).rgb;
+
+    col += glow / 25.0 * 0.5;
+
+    fragColor = vec4(1.0 - exp(-col), 1.0);
+}
@@ -0,0 +1,527 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::render::FrameInput;
+use super::sidecar::{self, sidecar_path, SidecarError};
+
+// path edited in the window is kept next to the shader in "<shader file>.path"
+const PATH_EXTENSION: &str = "path";
+// name of the file content in messages
+const WHAT: &str = "camera path";
+
+// keyframe added in the window comes this long after the last one
+const KEYFRAME_STEP: f32 = 2.0;
+
+pub const DEFAULT_FOV: f32 = 90.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Interpolation {
+    // smooth curve through every keyframe
+    #[default]
+    CatmullRom,
+    // keyframes are control points of one curve, camera passes
+    // through the first and the last one and is pulled towards the others
+    Bezier,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Ease {
+    #[default]
+    Linear,
+    In,
+    Out,
+    InOut,
+}
+
+impl Ease {
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            Ease::Linear => t,
+            Ease::In => t * t,
+            Ease::Out => 1.0 - (1.0 - t) * (1.0 - t),
+            Ease::InOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+// camera at a moment of shader time. ease shapes the timing of
+// the way to the next keyframe, for bezier paths only ease of
+// the first keyframe is used, for the whole path
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: f32,
+    pub position: [f32; 3],
+    // degrees, like --yaw and --pitch
+    pub yaw: f32,
+    pub pitch: f32,
+    #[serde(default = "default_fov")]
+    pub fov: f32,
+    #[serde(default)]
+    pub ease: Ease,
+}
+
+fn default_fov() -> f32 {
+    DEFAULT_FOV
+}
+
+// camera at some time of the path
+pub struct CameraState {
+    pub position: [f32; 3],
+    // radians, like FrameInput angles
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fov: f32,
+}
+
+//   (
+//       interpolation: CatmullRom,
+//       keyframes: [
+//           (time: 0.0, position: (0.0, 1.0, -8.0), yaw: 0.0, pitch: 0.0),
+//           (time: 4.0, position: (3.0, 2.0, 0.0), yaw: 45.0, pitch: -10.0, fov: 60.0, ease: InOut),
+//       ],
+//   )
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CameraPath {
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl CameraPath {
+    pub fn sidecar(shader_path: &Path) -> PathBuf {
+        sidecar_path(shader_path, PATH_EXTENSION)
+    }
+
+    pub fn load(path: &Path) -> Result<CameraPath, SidecarError> {
+        let camera_path: CameraPath = sidecar::read(WHAT, path)?;
+
+        let invalid = |message: String| SidecarError::Parse {
+            what: WHAT,
+            path: path.to_path_buf(),
+            message,
+        };
+
+        if camera_path.keyframes.is_empty() {
+            return Err(invalid(String::from("path has no keyframes")));
+        }
+
+        for pair in camera_path.keyframes.windows(2) {
+            if pair[1].time <= pair[0].time {
+                return Err(invalid(format!(
+                    "keyframe times must grow, {} follows {}",
+                    pair[1].time, pair[0].time
+                )));
+            }
+        }
+
+        for keyframe in camera_path.keyframes.iter() {
+            if keyframe.fov <= 0.0 || keyframe.fov >= 180.0 {
+                return Err(invalid(format!(
+                    "fov {} of keyframe at {} is out of range 0..180",
+                    keyframe.fov, keyframe.time
+                )));
+            }
+        }
+
+        Ok(camera_path)
+    }
+
+    // missing file is an empty path
+    fn load_or_empty(path: &Path) -> Result<CameraPath, SidecarError> {
+        if !path.exists() {
+            return Ok(CameraPath::default());
+        }
+        CameraPath::load(path)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), SidecarError> {
+        sidecar::write(WHAT, path, self)
+    }
+
+    // time of the last keyframe
+    pub fn end(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    pub fn duration(&self) -> f32 {
+        match (self.keyframes.first(), self.keyframes.last()) {
+            (Some(first), Some(last)) => last.time - first.time,
+            _ => 0.0,
+        }
+    }
+
+    // camera stays at the first keyframe before the path starts
+    // and at the last one after it ends
+    pub fn sample(&self, time: f32) -> CameraState {
+        let points = self.points();
+        let first = &self.keyframes[0];
+        let last = &self.keyframes[self.keyframes.len() - 1];
+
+        let point = if self.keyframes.len() == 1 || time <= first.time {
+            points[0]
+        } else if time >= last.time {
+            points[points.len() - 1]
+        } else {
+            match self.interpolation {
+                Interpolation::CatmullRom => {
+                    let segment = self
+                        .keyframes
+                        .windows(2)
+                        .position(|pair| time < pair[1].time)
+                        .unwrap_or(self.keyframes.len() - 2);
+                    let from = &self.keyframes[segment];
+                    let to = &self.keyframes[segment + 1];
+                    let t = from.ease.apply((time - from.time) / (to.time - from.time));
+                    catmull_rom(&points, segment, t)
+                }
+                Interpolation::Bezier => {
+                    let t = first
+                        .ease
+                        .apply((time - first.time) / (last.time - first.time));
+                    bezier(&points, t)
+                }
+            }
+        };
+
+        CameraState {
+            position: [point[0], point[1], point[2]],
+            yaw: point[3].to_radians(),
+            pitch: point[4].to_radians(),
+            fov: point[5],
+        }
+    }
+
+    // keyframes as vectors of position, yaw, pitch and fov.
+    // yaw turns the short way between keyframes
+    fn points(&self) -> Vec<[f32; 6]> {
+        let mut points: Vec<[f32; 6]> = Vec::with_capacity(self.keyframes.len());
+
+        for keyframe in self.keyframes.iter() {
+            let yaw = match points.last() {
+                Some(previous) => {
+                    previous[3] + (keyframe.yaw - previous[3] + 180.0).rem_euclid(360.0) - 180.0
+                }
+                None => keyframe.yaw,
+            };
+            points.push([
+                keyframe.position[0],
+                keyframe.position[1],
+                keyframe.position[2],
+                yaw,
+                keyframe.pitch,
+                keyframe.fov,
+            ]);
+        }
+
+        points
+    }
+}
+
+// segment from points[segment] to points[segment + 1],
+// missing neighbours at the ends are mirrored
+fn catmull_rom(points: &[[f32; 6]], segment: usize, t: f32) -> [f32; 6] {
+    let p1 = points[segment];
+    let p2 = points[segment + 1];
+    let p0 = if segment > 0 {
+        points[segment - 1]
+    } else {
+        mirror(p1, p2)
+    };
+    let p3 = if segment + 2 < points.len() {
+        points[segment + 2]
+    } else {
+        mirror(p2, p1)
+    };
+
+    let mut result = [0.0; 6];
+    for i in 0..6 {
+        result[i] = 0.5
+            * (2.0 * p1[i]
+                + (p2[i] - p0[i]) * t
+                + (2.0 * p0[i] - 5.0 * p1[i] + 4.0 * p2[i] - p3[i]) * t * t
+                + (3.0 * p1[i] - p0[i] - 3.0 * p2[i] + p3[i]) * t * t * t);
+    }
+    result
+}
+
+fn mirror(point: [f32; 6], other: [f32; 6]) -> [f32; 6] {
+    let mut result = [0.0; 6];
+    for i in 0..6 {
+        result[i] = 2.0 * point[i] - other[i];
+    }
+    result
+}
+
+// de casteljau
+fn bezier(points: &[[f32; 6]], t: f32) -> [f32; 6] {
+    let mut points = points.to_vec();
+
+    while points.len() > 1 {
+        for index in 0..points.len() - 1 {
+            let next = points[index + 1];
+            for (value, target) in points[index].iter_mut().zip(next.iter()) {
+                *value += (target - *value) * t;
+            }
+        }
+        points.pop();
+    }
+
+    points[0]
+}
+
+// path of the shader opened in the window: keyframes are added at the
+// current view and the path can be played back in real time
+pub struct PathEditor {
+    path: PathBuf,
+    // path of a replayed session, it is used instead of the file
+    replayed: Option<CameraPath>,
+}
+
+impl PathEditor {
+    pub fn new(shader_path: &Path) -> PathEditor {
+        PathEditor {
+            path: CameraPath::sidecar(shader_path),
+            replayed: None,
+        }
+    }
+
+    // path as it is now, kept in a recorded session. no keyframes without a file
+    pub fn snapshot(&self) -> Result<CameraPath, SidecarError> {
+        self.read()
+    }
+
+    // from now on the path is the recorded one, keyframes are added to it
+    pub fn replay(&mut self, camera_path: CameraPath) {
+        self.replayed = Some(camera_path);
+    }
+
+    pub fn add_keyframe(&mut self, frame_input: &FrameInput) -> Result<String, SidecarError> {
+        let mut camera_path = self.read()?;
+
+        let time = camera_path
+            .keyframes
+            .last()
+            .map_or(0.0, |keyframe| keyframe.time + KEYFRAME_STEP);
+
+        camera_path.keyframes.push(Keyframe {
+            time,
+            position: frame_input.camera_position,
+            yaw: frame_input.last_angle_x.to_degrees(),
+            pitch: frame_input.last_angle_y.to_degrees(),
+            fov: frame_input.fov,
+            ease: Ease::default(),
+        });
+        let count = camera_path.keyframes.len();
+        self.write(camera_path)?;
+
+        match self.replayed {
+            Some(_) => Ok(format!("keyframe {} at {:.1}s added", count, time)),
+            None => Ok(format!(
+                "keyframe {} at {:.1}s added to {}",
+                count,
+                time,
+                self.path.display()
+            )),
+        }
+    }
+
+    pub fn remove_last_keyframe(&mut self) -> Result<String, SidecarError> {
+        let mut camera_path = self.read()?;
+
+        let keyframe = match camera_path.keyframes.pop() {
+            Some(keyframe) => keyframe,
+            None => return Ok(String::from("camera path has no keyframes")),
+        };
+
+        let emptied = camera_path.keyframes.is_empty();
+        self.write(camera_path)?;
+
+        if emptied && self.replayed.is_none() {
+            return Ok(format!(
+                "keyframe at {:.1}s removed, {} deleted",
+                keyframe.time,
+                self.path.display()
+            ));
+        }
+        Ok(format!("keyframe at {:.1}s removed", keyframe.time))
+    }
+
+    // starts playback from the first keyframe, or stops it
+    pub fn toggle_preview(&self, frame_input: &mut FrameInput) -> Result<String, SidecarError> {
+        // path of --camera-path goes by shader time through the whole render
+        if frame_input.camera_path.is_some() && frame_input.path_time.is_none() {
+            return Ok(String::from(
+                "camera path of --camera-path can't be stopped",
+            ));
+        }
+
+        if frame_input.camera_path.take().is_some() {
+            return Ok(String::from("camera path stopped"));
+        }
+
+        let camera_path = self.read()?;
+        if camera_path.keyframes.is_empty() {
+            return Ok(String::from("camera path has no keyframes, C adds one"));
+        }
+
+        let message = format!(
+            "playing camera path, {} keyframes, {:.1}s",
+            camera_path.keyframes.len(),
+            camera_path.duration()
+        );
+
+        frame_input.path_time = Some(camera_path.keyframes[0].time);
+        frame_input.camera_path = Some(camera_path);
+        frame_input.transition = None;
+
+        Ok(message)
+    }
+
+    fn read(&self) -> Result<CameraPath, SidecarError> {
+        match &self.replayed {
+            Some(camera_path) => Ok(camera_path.clone()),
+            None => CameraPath::load_or_empty(&self.path),
+        }
+    }
+
+    // a path without keyframes can't be loaded, so
+    // the file goes away with the last one
+    fn write(&mut self, camera_path: CameraPath) -> Result<(), SidecarError> {
+        match &mut self.replayed {
+            Some(replayed) => {
+                *replayed = camera_path;
+                Ok(())
+            }
+            None if camera_path.keyframes.is_empty() => sidecar::remove(WHAT, &self.path),
+            None => camera_path.save(&self.path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINTS: [[f32; 6]; 4] = [
+        [0.0, 1.0, -8.0, 0.0, 0.0, 90.0],
+        [3.0, 2.0, 0.0, 45.0, -10.0, 60.0],
+        [4.0, 0.5, 5.0, 30.0, 20.0, 70.0],
+        [-2.0, 1.0, 9.0, 90.0, 0.0, 90.0],
+    ];
+
+    fn keyframe(time: f32, yaw: f32) -> Keyframe {
+        Keyframe {
+            time,
+            position: [time, 0.0, 0.0],
+            yaw,
+            pitch: 0.0,
+            fov: DEFAULT_FOV,
+            ease: Ease::Linear,
+        }
+    }
+
+    fn assert_close(actual: [f32; 6], expected: [f32; 6], tolerance: f32) {
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() < tolerance, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    // central difference of curve at t
+    fn derivative(curve: impl Fn(f32) -> [f32; 6], t: f32) -> [f32; 6] {
+        let h = 1e-2;
+        let (before, after) = (curve(t - h), curve(t + h));
+        let mut result = [0.0; 6];
+        for i in 0..6 {
+            result[i] = (after[i] - before[i]) / (2.0 * h);
+        }
+        result
+    }
+
+    #[test]
+    fn catmull_rom_passes_keyframes() {
+        for segment in 0..POINTS.len() - 1 {
+            assert_close(catmull_rom(&POINTS, segment, 0.0), POINTS[segment], 1e-5);
+            assert_close(
+                catmull_rom(&POINTS, segment, 1.0),
+                POINTS[segment + 1],
+                1e-4,
+            );
+        }
+    }
+
+    #[test]
+    fn catmull_rom_is_smooth_at_keyframes() {
+        // segments continue past their ends with the same polynomial,
+        // so the derivative at a keyframe can be taken on both sides
+        for segment in 0..POINTS.len() - 2 {
+            let incoming = derivative(|t| catmull_rom(&POINTS, segment, t), 1.0);
+            let outgoing = derivative(|t| catmull_rom(&POINTS, segment + 1, t), 0.0);
+            assert_close(incoming, outgoing, 0.05);
+
+            // tangent is half the difference of the neighbours
+            let mut tangent = [0.0; 6];
+            for (i, value) in tangent.iter_mut().enumerate() {
+                *value = 0.5 * (POINTS[segment + 2][i] - POINTS[segment][i]);
+            }
+            assert_close(outgoing, tangent, 0.05);
+        }
+    }
+
+    #[test]
+    fn bezier_passes_first_and_last_point() {
+        assert_close(bezier(&POINTS, 0.0), POINTS[0], 1e-5);
+        assert_close(bezier(&POINTS, 1.0), POINTS[3], 1e-5);
+
+        // pulled towards the control points in between
+        let mut middle = [0.0; 6];
+        for (i, value) in middle.iter_mut().enumerate() {
+            *value = 0.25 * POINTS[0][i] + 0.5 * POINTS[1][i] + 0.25 * POINTS[2][i];
+        }
+        assert_close(bezier(&POINTS[..3], 0.5), middle, 1e-5);
+
+        // starts and ends towards the neighbouring control point
+        let start = derivative(|t| bezier(&POINTS, t), 0.0);
+        let end = derivative(|t| bezier(&POINTS, t), 1.0);
+        let (mut to_second, mut from_third) = ([0.0; 6], [0.0; 6]);
+        for i in 0..6 {
+            to_second[i] = 3.0 * (POINTS[1][i] - POINTS[0][i]);
+            from_third[i] = 3.0 * (POINTS[3][i] - POINTS[2][i]);
+        }
+        assert_close(start, to_second, 0.5);
+        assert_close(end, from_third, 0.5);
+    }
+
+    #[test]
+    fn yaw_turns_the_short_way() {
+        let path = CameraPath {
+            interpolation: Interpolation::CatmullRom,
+            keyframes: vec![
+                keyframe(0.0, 170.0),
+                keyframe(1.0, -170.0),
+                keyframe(2.0, -150.0),
+            ],
+        };
+        let yaws: Vec<f32> = path.points().iter().map(|point| point[3]).collect();
+        assert_eq!(yaws, vec![170.0, 190.0, 210.0]);
+        assert!((path.sample(0.5).yaw - 180f32.to_radians()).abs() < 1e-4);
+
+        let path = CameraPath {
+            interpolation: Interpolation::Bezier,
+            keyframes: vec![keyframe(0.0, -170.0), keyframe(1.0, 170.0)],
+        };
+        let yaws: Vec<f32> = path.points().iter().map(|point| point[3]).collect();
+        assert_eq!(yaws, vec![-170.0, -190.0]);
+        assert!((path.sample(0.5).yaw - (-180f32).to_radians()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn camera_stays_at_ends() {
+        let path = CameraPath {
+            interpolation: Interpolation::CatmullRom,
+            keyframes: vec![keyframe(1.0, 0.0), keyframe(3.0, 90.0)],
+        };
+        assert_eq!(path.sample(0.0).position, [1.0, 0.0, 0.0]);
+        assert_eq!(path.sample(5.0).position, [3.0, 0.0, 0.0]);
+        assert!((path.sample(5.0).yaw - 90f32.to_radians()).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,419 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::DynamicImage;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum FrameFormat {
+    /// 8 bit per channel png
+    Png,
+    /// 16 bit per channel png
+    Png16,
+    /// jpeg, see --quality
+    Jpeg,
+    /// YUV4MPEG2 video stream, readable by ffmpeg and most encoders
+    Y4m,
+    /// raw rgba frames one after another, for piping into an encoder
+    Raw,
+}
+
+impl FrameFormat {
+    pub fn is_stream(self) -> bool {
+        matches!(self, FrameFormat::Y4m | FrameFormat::Raw)
+    }
+
+    pub fn default_output(self) -> PathBuf {
+        match self {
+            FrameFormat::Y4m => PathBuf::from("video.y4m"),
+            FrameFormat::Raw => PathBuf::from(STDOUT_PATH),
+            _ => PathBuf::from("frames"),
+        }
+    }
+}
+
+// streams are written to stdout when output is "-"
+pub const STDOUT_PATH: &str = "-";
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum OverwritePolicy {
+    /// Replace existing files
+    Overwrite,
+    /// Keep existing files and don't write the frame
+    Skip,
+    /// Stop rendering when a file already exists
+    Error,
+}
+
+#[derive(Clone)]
+pub struct CaptureOptions {
+    // directory for image frames, file for streams
+    pub output: PathBuf,
+    pub pattern: String,
+    pub format: FrameFormat,
+    pub quality: u8,
+    pub overwrite: OverwritePolicy,
+}
+
+pub enum CaptureError {
+    CreateDir { path: PathBuf, error: io::Error },
+    Exists { path: PathBuf },
+    Save { path: PathBuf, error: image::ImageError },
+    Write { path: PathBuf, error: io::Error },
+    SizeChanged { expected: (u32, u32), actual: (u32, u32) },
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::CreateDir { path, error } => write!(
+                f,
+                "can not create output directory {}: {}",
+                path.display(),
+                error
+            ),
+            CaptureError::Exists { path } => write!(
+                f,
+                "{} already exists, use --overwrite to replace or skip existing frames",
+                path.display()
+            ),
+            CaptureError::Save { path, error } => {
+                write!(f, "can not save frame {}: {}", path.display(), error)
+            }
+            CaptureError::Write { path, error } => {
+                write!(f, "can not write video {}: {}", path.display(), error)
+            }
+            CaptureError::SizeChanged { expected, actual } => write!(
+                f,
+                "frame size changed from {}x{} to {}x{}, video stream needs all frames of the same size",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+        }
+    }
+}
+
+// pattern must have exactly one run of '#', it is replaced by
+// frame number padded with zeros to the length of the run
+pub fn validate_pattern(pattern: &str) -> Result<(), String> {
+    let runs = pattern
+        .split(|c| c != '#')
+        .filter(|run| !run.is_empty())
+        .count();
+
+    if runs == 1 {
+        Ok(())
+    } else {
+        Err(format!(
+            "pattern \"{}\" must contain one group of '#' for the frame number, like \"frame_#####\"",
+            pattern
+        ))
+    }
+}
+
+fn file_name(pattern: &str, frame_number: u32, extension: &str) -> String {
+    let start = pattern.find('#').unwrap_or(pattern.len());
+    let width = pattern[start..].len() - pattern[start..].trim_start_matches('#').len();
+
+    format!(
+        "{}{:0width$}{}.{}",
+        &pattern[..start],
+        frame_number,
+        &pattern[start + width..],
+        extension,
+        width = width
+    )
+}
+
+// where captured frames go
+pub enum FrameSink {
+    Images(ImageWriter),
+    Stream(StreamWriter),
+}
+
+impl FrameSink {
+    pub fn new(options: CaptureOptions, fps: f32) -> Result<FrameSink, CaptureError> {
+        if options.format.is_stream() {
+            StreamWriter::new(&options, fps).map(FrameSink::Stream)
+        } else {
+            ImageWriter::new(options).map(FrameSink::Images)
+        }
+    }
+
+    pub fn write(&mut self, frame_number: u32, image: DynamicImage) -> Result<(), CaptureError> {
+        match self {
+            FrameSink::Images(writer) => writer.write(frame_number, image),
+            FrameSink::Stream(writer) => writer.write(image),
+        }
+    }
+
+    pub fn finish(&mut self) -> Result<(), CaptureError> {
+        match self {
+            FrameSink::Images(_) => Ok(()),
+            FrameSink::Stream(writer) => writer.flush(),
+        }
+    }
+}
+
+pub struct ImageWriter {
+    options: CaptureOptions,
+}
+
+impl ImageWriter {
+    pub fn new(options: CaptureOptions) -> Result<ImageWriter, CaptureError> {
+        fs::create_dir_all(&options.output).map_err(|error| CaptureError::CreateDir {
+            path: options.output.clone(),
+            error,
+        })?;
+
+        Ok(ImageWriter { options })
+    }
+
+    fn frame_path(&self, frame_number: u32) -> PathBuf {
+        let extension = match self.options.format {
+            FrameFormat::Jpeg => "jpg",
+            _ => "png",
+        };
+
+        self.options
+            .output
+            .join(file_name(&self.options.pattern, frame_number, extension))
+    }
+
+    pub fn write(&self, frame_number: u32, image: DynamicImage) -> Result<(), CaptureError> {
+        let path = self.frame_path(frame_number);
+
+        if path.exists() {
+            match self.options.overwrite {
+                OverwritePolicy::Overwrite => {}
+                OverwritePolicy::Skip => return Ok(()),
+                OverwritePolicy::Error => return Err(CaptureError::Exists { path }),
+            }
+        }
+
+        let save_error = |error| CaptureError::Save {
+            path: path.clone(),
+            error,
+        };
+
+        match self.options.format {
+            FrameFormat::Png => image
+                .into_rgba8()
+                .save_with_format(&path, image::ImageFormat::Png)
+                .map_err(save_error),
+            FrameFormat::Png16 => image
+                .into_rgba16()
+                .save_with_format(&path, image::ImageFormat::Png)
+                .map_err(save_error),
+            FrameFormat::Jpeg => save_jpeg(&path, image, self.options.quality).map_err(save_error),
+            FrameFormat::Y4m | FrameFormat::Raw => unreachable!("streams are written by StreamWriter"),
+        }
+    }
+}
+
+fn save_jpeg(path: &Path, image: DynamicImage, quality: u8) -> Result<(), image::ImageError> {
+    let rgb = image.into_rgb8();
+    let file = io::BufWriter::new(fs::File::create(path)?);
+
+    JpegEncoder::new_with_quality(file, quality).encode(
+        rgb.as_raw(),
+        rgb.width(),
+        rgb.height(),
+        image::ColorType::Rgb8,
+    )
+}
+
+// writes all frames into one file or stdout, so long renders don't produce
+// thousands of files and can be piped straight into an encoder:
+//   constructor render --format raw shader.frag | ffmpeg -f rawvideo -pix_fmt rgba -s WxH -r 60 -i - out.mp4
+pub struct StreamWriter {
+    path: PathBuf,
+    out: Box<dyn Write>,
+    y4m: bool,
+    fps: f32,
+    size: Option<(u32, u32)>,
+}
+
+impl StreamWriter {
+    fn new(options: &CaptureOptions, fps: f32) -> Result<StreamWriter, CaptureError> {
+        let out: Box<dyn Write> = if options.output == Path::new(STDOUT_PATH) {
+            Box::new(io::BufWriter::new(io::stdout()))
+        } else {
+            if options.output.exists() && options.overwrite != OverwritePolicy::Overwrite {
+                return Err(CaptureError::Exists {
+                    path: options.output.clone(),
+                });
+            }
+
+            if let Some(dir) = options.output.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                fs::create_dir_all(dir).map_err(|error| CaptureError::CreateDir {
+                    path: dir.to_path_buf(),
+                    error,
+                })?;
+            }
+
+            let file = fs::File::create(&options.output).map_err(|error| CaptureError::Write {
+                path: options.output.clone(),
+                error,
+            })?;
+
+            Box::new(io::BufWriter::new(file))
+        };
+
+        Ok(StreamWriter {
+            path: options.output.clone(),
+            out,
+            y4m: options.format == FrameFormat::Y4m,
+            fps,
+            size: None,
+        })
+    }
+
+    fn write(&mut self, image: DynamicImage) -> Result<(), CaptureError> {
+        let rgba = image.into_rgba8();
+        let size = rgba.dimensions();
+
+        match self.size {
+            Some(expected) if expected != size => {
+                return Err(CaptureError::SizeChanged {
+                    expected,
+                    actual: size,
+                })
+            }
+            Some(_) => {}
+            None => {
+                self.size = Some(size);
+
+                if self.y4m {
+                    let (numerator, denominator) = frame_rate_ratio(self.fps);
+                    let header = format!(
+                        "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C444 XCOLORRANGE=LIMITED\n",
+                        size.0, size.1, numerator, denominator
+                    );
+                    self.write_bytes(header.as_bytes())?;
+                } else {
+                    eprintln!(
+                        "raw stream: rgba, {}x{}, {} fps, rows from top to bottom",
+                        size.0, size.1, self.fps
+                    );
+                }
+            }
+        }
+
+        if self.y4m {
+            self.write_bytes(b"FRAME\n")?;
+            let planes = yuv444_planes(&rgba);
+            self.write_bytes(&planes)
+        } else {
+            self.write_bytes(rgba.as_raw())
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), CaptureError> {
+        let path = &self.path;
+        self.out
+            .write_all(bytes)
+            .map_err(|error| CaptureError::Write {
+                path: path.clone(),
+                error,
+            })
+    }
+
+    fn flush(&mut self) -> Result<(), CaptureError> {
+        let path = &self.path;
+        self.out.flush().map_err(|error| CaptureError::Write {
+            path: path.clone(),
+            error,
+        })
+    }
+}
+
+// y4m wants frame rate as a ratio of integers
+fn frame_rate_ratio(fps: f32) -> (u32, u32) {
+    if fps.fract() == 0.0 {
+        return (fps as u32, 1);
+    }
+
+    let mut numerator = (fps * 1000.0).round() as u32;
+    let mut denominator = 1000;
+
+    let mut a = numerator;
+    let mut b = denominator;
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    numerator /= a;
+    denominator /= a;
+
+    (numerator, denominator)
+}
+
+// full resolution Y, Cb and Cr planes one after another,
+// bt.709 coefficients with limited (16..235) range
+fn yuv444_planes(rgba: &image::RgbaImage) -> Vec<u8> {
+    let pixel_count = (rgba.width() * rgba.height()) as usize;
+    let mut planes = vec![0u8; pixel_count * 3];
+
+    for (i, pixel) in rgba.pixels().enumerate() {
+        let r = pixel[0] as f32 / 255.0;
+        let g = pixel[1] as f32 / 255.0;
+        let b = pixel[2] as f32 / 255.0;
+
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let cb = (b - y) / 1.8556;
+        let cr = (r - y) / 1.5748;
+
+        planes[i] = (16.0 + 219.0 * y).round() as u8;
+        planes[pixel_count + i] = (128.0 + 224.0 * cb).round() as u8;
+        planes[pixel_count * 2 + i] = (128.0 + 224.0 * cr).round() as u8;
+    }
+
+    planes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_has_one_group() {
+        assert!(validate_pattern("frame_#####").is_ok());
+        assert!(validate_pattern("#").is_ok());
+        assert!(validate_pattern("shot_##_final").is_ok());
+        assert!(validate_pattern("frame").is_err());
+        assert!(validate_pattern("").is_err());
+        assert!(validate_pattern("take_#_frame_###").is_err());
+    }
+
+    #[test]
+    fn frame_number_is_padded() {
+        assert_eq!(file_name("frame_#####", 42, "png"), "frame_00042.png");
+        assert_eq!(file_name("#_left", 7, "jpg"), "7_left.jpg");
+        assert_eq!(file_name("shot_##_final", 3, "png"), "shot_03_final.png");
+        // numbers longer than the group are not cut
+        assert_eq!(file_name("##", 123, "png"), "123.png");
+    }
+
+    #[test]
+    fn frame_rate_is_reduced_ratio() {
+        assert_eq!(frame_rate_ratio(60.0), (60, 1));
+        assert_eq!(frame_rate_ratio(0.5), (1, 2));
+        assert_eq!(frame_rate_ratio(29.97), (2997, 100));
+        assert_eq!(frame_rate_ratio(23.976), (2997, 125));
+    }
+
+    #[test]
+    fn planes_are_limited_range_bt709() {
+        let pixels = [255, 255, 255, 255, 0, 0, 0, 255, 255, 0, 0, 255];
+        let rgba = image::RgbaImage::from_raw(3, 1, pixels.to_vec()).unwrap();
+
+        // white, black and red: Y plane, then Cb and Cr
+        assert_eq!(
+            yuv444_planes(&rgba),
+            vec![235, 16, 63, 128, 128, 102, 128, 128, 240]
+        );
+    }
+}
@@ -0,0 +1,559 @@
+use clap::{Args, Parser, Subcommand};
+use std::convert::TryInto;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use super::camera_path::{CameraPath, DEFAULT_FOV};
+use super::capture::{validate_pattern, CaptureOptions, FrameFormat, OverwritePolicy};
+use super::collision::{DEFAULT_EYE_HEIGHT, DEFAULT_STEP_HEIGHT};
+use super::lens::{focal_length_fov, Lens, Projection, DEFAULT_VIEW_HEIGHT};
+use super::photo::{Photo, DEFAULT_FOCUS_DISTANCE};
+use super::scene::{is_scene_file, Scene, SceneCamera};
+use super::session::Session;
+
+const DEFAULT_SHADER: &str = "fragment_shader.frag";
+const DEFAULT_CAMERA: [f32; 3] = [0.0, 1.0, 0.0];
+const SHADERS_DIR: &str = "shaders";
+
+#[derive(Parser)]
+#[command(
+    name = "constructor",
+    version,
+    about = "tool for architectural conceptual modeling",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    // `constructor shader.frag` is the same as `constructor view shader.frag`
+    #[command(flatten)]
+    view: ViewArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Open shader in a window with free camera
+    View(ViewArgs),
+    /// Render shader to a sequence of frames with fixed time step
+    Render(RenderArgs),
+    /// Print information about shader file
+    Info(InfoArgs),
+}
+
+#[derive(Args)]
+struct ViewArgs {
+    /// Fragment shader, .ron project file or .scene.ron/.scene.json scene file,
+    /// looked up in current directory and then in "shaders/"
+    shader: Option<PathBuf>,
+
+    /// Window width in logical pixels
+    #[arg(long, default_value_t = 1792, value_parser = clap::value_parser!(u32).range(1..))]
+    width: u32,
+
+    /// Window height in logical pixels
+    #[arg(long, default_value_t = 768, value_parser = clap::value_parser!(u32).range(1..))]
+    height: u32,
+
+    /// Start camera position [default: 0,1,0 or camera of the scene file]
+    #[arg(long, value_name = "X,Y,Z", value_parser = parse_vector::<3>)]
+    camera: Option<[f32; 3]>,
+
+    /// Start camera yaw in degrees [default: 0 or camera of the scene file]
+    #[arg(long, allow_negative_numbers = true, value_parser = parse_number)]
+    yaw: Option<f32>,
+
+    /// Start camera pitch in degrees, from -90 to 90 [default: 0 or camera of the scene file]
+    #[arg(long, allow_negative_numbers = true, value_parser = parse_pitch)]
+    pitch: Option<f32>,
+
+    /// Vertical field of view in degrees, passed to shaders as fov uniform
+    /// [default: 90 or camera of the scene file]
+    #[arg(long, value_parser = parse_fov)]
+    fov: Option<f32>,
+
+    /// Focal length in mm of a full frame (36x24 mm) camera, instead of --fov
+    #[arg(long, value_name = "MM", value_parser = parse_positive, conflicts_with = "fov")]
+    focal_length: Option<f32>,
+
+    /// How rays leave the camera, shaders get it through lib/camera.glsl
+    #[arg(long, value_enum, default_value_t = Projection::Perspective)]
+    projection: Projection,
+
+    /// Lens shift in parts of image height, like a tilt-shift lens: 0,0.2 moves the view up
+    #[arg(long, value_name = "X,Y", default_value = "0,0", allow_negative_numbers = true, value_parser = parse_vector::<2>)]
+    shift: [f32; 2],
+
+    /// Height of orthographic and isometric views in scene units
+    #[arg(long, default_value_t = DEFAULT_VIEW_HEIGHT, value_parser = parse_positive)]
+    view_height: f32,
+
+    /// Aperture as f-number, like 2.8, for depth of field. Without it everything is sharp.
+    /// Scene units are taken as meters
+    #[arg(long, value_name = "N", value_parser = parse_positive)]
+    f_stop: Option<f32>,
+
+    /// Distance of the sharp plane. Without it focus follows the surface at the image center
+    /// in shaders writing hit_point
+    #[arg(long, value_name = "DISTANCE", value_parser = parse_positive)]
+    focus: Option<f32>,
+
+    /// Time the shutter is open for motion blur, like 1/50 or 0.02 seconds
+    #[arg(long, value_name = "SECONDS", default_value = "0", value_parser = parse_shutter)]
+    shutter: f32,
+
+    /// Sensor sensitivity, 100 leaves brightness of the shader as it is
+    #[arg(long, default_value_t = 100.0, value_parser = parse_positive)]
+    iso: f32,
+
+    /// Exposure compensation in stops
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true, value_parser = parse_number)]
+    ev: f32,
+
+    /// Samples averaged for every frame, depth of field and motion blur need many of them
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    samples: u32,
+
+    /// Walk on the scene with gravity instead of flying, G switches in the window.
+    /// Scene units are taken as meters
+    #[arg(long)]
+    walk: bool,
+
+    /// Height of the eye above the feet when walking
+    #[arg(long, value_name = "METERS", default_value_t = DEFAULT_EYE_HEIGHT, value_parser = parse_positive)]
+    eye_height: f32,
+
+    /// Highest step climbed by walking, higher ones stop the walker
+    #[arg(long, value_name = "METERS", default_value_t = DEFAULT_STEP_HEIGHT, value_parser = parse_non_negative)]
+    step_height: f32,
+
+    /// Fly through surfaces of the scene, N switches in the window
+    #[arg(long)]
+    no_collision: bool,
+
+    /// Camera speed in units per second
+    #[arg(long, default_value_t = 200.0, value_parser = parse_non_negative)]
+    speed: f32,
+
+    /// Directory searched for #include files after the directory of the including file,
+    /// can be given several times
+    #[arg(long = "include", short = 'I', value_name = "DIR")]
+    include_dirs: Vec<PathBuf>,
+
+    /// Start from a preset saved in "<shader>.presets", overrides camera options
+    #[arg(long, value_name = "NAME")]
+    preset: Option<String>,
+
+    /// Record window input to FILE when the window closes. Time goes by fixed 1/60 s
+    /// steps (1/fps for video), so the session can be replayed exactly
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Replay input recorded with --record instead of taking it from the window.
+    /// Shader defaults to the recorded one, camera and parameters start as recorded.
+    /// Video gets fps of the session and, without --frames, all its frames
+    #[arg(long, value_name = "FILE", conflicts_with = "preset")]
+    replay: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct RenderArgs {
+    #[command(flatten)]
+    view: ViewArgs,
+
+    /// Length of the video in seconds of shader time
+    #[arg(long, default_value_t = 10.0, value_parser = parse_positive)]
+    duration: f32,
+
+    /// Render only frames from START up to END (exclusive), instead of the whole duration.
+    /// Shader time of a frame is frame / fps, so ranges can be rendered separately
+    #[arg(long, value_name = "START..END", value_parser = parse_frames)]
+    frames: Option<Range<u32>>,
+
+    /// Camera follows keyframes of a path file in shader time.
+    /// Without FILE "<shader>.path" is used, the file C key adds keyframes to
+    #[arg(long, value_name = "FILE", num_args = 0..=1)]
+    camera_path: Option<Option<PathBuf>>,
+
+    /// Camera circles a pivot by DEGREES over the duration, negative turns the other way
+    #[arg(long, value_name = "DEGREES", allow_negative_numbers = true, value_parser = parse_number, conflicts_with = "camera_path")]
+    turntable: Option<f32>,
+
+    /// Pivot of --turntable, defaults to where the start view meets the ground
+    #[arg(long, value_name = "X,Y,Z", value_parser = parse_vector::<3>, requires = "turntable")]
+    pivot: Option<[f32; 3]>,
+
+    /// Render without window, using gpu or software renderer through EGL
+    #[arg(long)]
+    headless: bool,
+
+    /// Size of rendered frames, independent of window size
+    #[arg(long, value_name = "WIDTHxHEIGHT", default_value = "1920x1080", value_parser = parse_resolution)]
+    resolution: (u32, u32),
+
+    /// Frames per second, shader time advances by 1/fps every frame
+    #[arg(long, default_value_t = 60.0, value_parser = parse_positive)]
+    fps: f32,
+
+    /// Directory for image frames, or file for y4m and raw streams ("-" for stdout).
+    /// Defaults to "frames" for images, "video.y4m" for y4m and stdout for raw
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    /// File name of a frame, '#' group is replaced by zero padded frame number
+    #[arg(long, default_value = "frame_#####", value_parser = parse_pattern)]
+    pattern: String,
+
+    /// Image format of frames
+    #[arg(long, value_enum, default_value_t = FrameFormat::Png)]
+    format: FrameFormat,
+
+    /// Jpeg quality from 1 to 100
+    #[arg(long, default_value_t = 90, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: u8,
+
+    /// What to do when frame file already exists, skip only works with image frames
+    #[arg(long, value_enum, default_value_t = OverwritePolicy::Overwrite)]
+    overwrite: OverwritePolicy,
+}
+
+#[derive(Args)]
+struct InfoArgs {
+    /// Fragment shader file, looked up in current directory and then in "shaders/"
+    shader: Option<PathBuf>,
+
+    /// Directory searched for #include files, can be given several times
+    #[arg(long = "include", short = 'I', value_name = "DIR")]
+    include_dirs: Vec<PathBuf>,
+}
+
+pub struct VideoOptions {
+    pub resolution: (u32, u32),
+    pub frames: Range<u32>,
+    pub fps: f32,
+    pub capture: CaptureOptions,
+}
+
+pub struct WalkOptions {
+    pub enabled: bool,
+    pub eye_height: f32,
+    pub step_height: f32,
+}
+
+pub struct TurntableOptions {
+    pub degrees: f32,
+    // seconds of shader time
+    pub duration: f32,
+    pub pivot: Option<[f32; 3]>,
+}
+
+pub struct Options {
+    pub shader: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub camera_position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub speed: f32,
+    pub fov: f32,
+    pub lens: Lens,
+    pub photo: Photo,
+    // samples of every frame, see photo
+    pub samples: u32,
+    pub collision: bool,
+    pub walk: WalkOptions,
+    pub video: Option<VideoOptions>,
+    pub headless: bool,
+    pub include_dirs: Vec<PathBuf>,
+    pub preset: Option<String>,
+    // keyframes for video, see camera_path
+    pub camera_path: Option<PathBuf>,
+    pub turntable: Option<TurntableOptions>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<Session>,
+}
+
+pub enum Action {
+    Run(Box<Options>),
+    // shader and include directories
+    Info(PathBuf, Vec<PathBuf>),
+}
+
+impl Cli {
+    // parses command line, prints usage and exits on errors
+    pub fn parse_action() -> Action {
+        let cli = Cli::parse();
+
+        let result = match cli.command {
+            None => cli.view.into_options(None, false).map(|options| Action::Run(Box::new(options))),
+            Some(Command::View(view)) => view.into_options(None, false).map(|options| Action::Run(Box::new(options))),
+            // a stream is one file, there are no frames to keep
+            Some(Command::Render(render))
+                if render.format.is_stream() && render.overwrite == OverwritePolicy::Skip =>
+            {
+                Err(clap::Error::raw(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    "--overwrite skip only works with image frames, not y4m or raw streams\n",
+                )
+                .with_cmd(&<Cli as clap::CommandFactory>::command()))
+            }
+            Some(Command::Render(render)) => {
+                let format = render.format;
+                let whole_session = render.frames.is_none();
+                let frames = render
+                    .frames
+                    .unwrap_or(0..(render.duration * render.fps).round().max(1.0) as u32);
+                let video = VideoOptions {
+                    resolution: render.resolution,
+                    frames,
+                    fps: render.fps,
+                    capture: CaptureOptions {
+                        output: render.output.unwrap_or_else(|| format.default_output()),
+                        pattern: render.pattern,
+                        format: render.format,
+                        quality: render.quality,
+                        overwrite: render.overwrite,
+                    },
+                };
+                let camera_path = render.camera_path;
+                let (duration, pivot) = (render.duration, render.pivot);
+                let turntable = render.turntable.map(|degrees| TurntableOptions {
+                    degrees,
+                    duration,
+                    pivot,
+                });
+                render
+                    .view
+                    .into_options(Some(video), render.headless)
+                    .map(|mut options| {
+                        options.camera_path = camera_path.map(|path| {
+                            path.unwrap_or_else(|| CameraPath::sidecar(&options.shader))
+                        });
+                        options.turntable = turntable;
+                        if let (Some(session), Some(video)) = (&options.replay, &mut options.video) {
+                            video.fps = 1.0 / session.time_step;
+                            if whole_session {
+                                video.frames = 0..session.frames.max(1);
+                            }
+                        }
+                        Action::Run(Box::new(options))
+                    })
+            }
+            Some(Command::Info(info)) => {
+                let include_dirs = info.include_dirs;
+                resolve_shader_path(info.shader).map(|shader| Action::Info(shader, include_dirs))
+            }
+        };
+
+        result.unwrap_or_else(|error| error.exit())
+    }
+}
+
+impl ViewArgs {
+    fn into_options(self, video: Option<VideoOptions>, headless: bool) -> Result<Options, clap::Error> {
+        let replay = match &self.replay {
+            Some(path) => Some(Session::load(path).map_err(|error| {
+                clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!("{}\n", error))
+                    .with_cmd(&<Cli as clap::CommandFactory>::command())
+            })?),
+            None => None,
+        };
+        let shader = self
+            .shader
+            .or_else(|| replay.as_ref().map(|session| session.shader.clone()));
+        let shader = resolve_shader_path(shader)?;
+
+        // scene files set the view they open with, options go first.
+        // broken scenes are reported when they are drawn
+        let scene_camera = if is_scene_file(&shader) {
+            Scene::load(&shader)
+                .map(|scene| scene.camera)
+                .unwrap_or_default()
+        } else {
+            SceneCamera::default()
+        };
+        let fov = self.fov.or(scene_camera.fov).unwrap_or(DEFAULT_FOV);
+
+        Ok(Options {
+            shader,
+            width: self.width,
+            height: self.height,
+            camera_position: self
+                .camera
+                .or(scene_camera.position)
+                .unwrap_or(DEFAULT_CAMERA),
+            yaw: self.yaw.or(scene_camera.yaw).unwrap_or(0.0).to_radians(),
+            pitch: self
+                .pitch
+                .or(scene_camera.pitch)
+                .map_or(0.0, |pitch| pitch.clamp(-90.0, 90.0))
+                .to_radians(),
+            speed: self.speed,
+            fov: self.focal_length.map_or(fov, focal_length_fov),
+            lens: Lens {
+                projection: self.projection,
+                shift: self.shift,
+                view_height: self.view_height,
+            },
+            photo: Photo {
+                f_stop: self.f_stop,
+                focus_distance: self.focus.unwrap_or(DEFAULT_FOCUS_DISTANCE),
+                autofocus: self.focus.is_none(),
+                shutter: self.shutter,
+                iso: self.iso,
+                ev: self.ev,
+            },
+            samples: self.samples,
+            collision: !self.no_collision,
+            walk: WalkOptions {
+                enabled: self.walk,
+                eye_height: self.eye_height,
+                step_height: self.step_height,
+            },
+            video,
+            headless,
+            include_dirs: self.include_dirs,
+            preset: self.preset,
+            camera_path: None,
+            turntable: None,
+            record: self.record,
+            replay,
+        })
+    }
+}
+
+fn resolve_shader_path(shader: Option<PathBuf>) -> Result<PathBuf, clap::Error> {
+    let path = shader.unwrap_or_else(|| PathBuf::from(DEFAULT_SHADER));
+
+    if path.is_file() {
+        return Ok(path);
+    }
+
+    let in_shaders_dir = Path::new(SHADERS_DIR).join(&path);
+
+    if in_shaders_dir.is_file() {
+        return Ok(in_shaders_dir);
+    }
+
+    Err(clap::Error::raw(
+        clap::error::ErrorKind::ValueValidation,
+        format!(
+            "shader file not found: {} (also looked in {})\n",
+            path.display(),
+            in_shaders_dir.display()
+        ),
+    )
+    .with_cmd(&<Cli as clap::CommandFactory>::command()))
+}
+
+fn parse_pattern(s: &str) -> Result<String, String> {
+    validate_pattern(s)?;
+    Ok(String::from(s))
+}
+
+fn parse_resolution(s: &str) -> Result<(u32, u32), String> {
+    let error = || format!("expected resolution like 1920x1080, got \"{}\"", s);
+
+    let (width, height) = s.split_once('x').ok_or_else(error)?;
+    let width = width.trim().parse::<u32>().map_err(|_| error())?;
+    let height = height.trim().parse::<u32>().map_err(|_| error())?;
+
+    if width == 0 || height == 0 {
+        return Err(error());
+    }
+
+    Ok((width, height))
+}
+
+fn parse_frames(s: &str) -> Result<Range<u32>, String> {
+    let error = || format!("expected frame range like 0..600, got \"{}\"", s);
+
+    let (start, end) = s.split_once("..").ok_or_else(error)?;
+    let start = start.trim().parse::<u32>().map_err(|_| error())?;
+    let end = end.trim().parse::<u32>().map_err(|_| error())?;
+
+    if start >= end {
+        return Err(format!("frame range {} is empty", s));
+    }
+
+    Ok(start..end)
+}
+
+// N comma separated numbers, like "1,2.5,-3"
+fn parse_vector<const N: usize>(s: &str) -> Result<[f32; N], String> {
+    let values = s
+        .split(',')
+        .map(|value| value.trim().parse::<f32>())
+        .collect::<Result<Vec<f32>, _>>()
+        .map_err(|error| format!("{} in \"{}\"", error, s))?;
+
+    values
+        .try_into()
+        .map_err(|_| format!("expected {} comma separated numbers, got \"{}\"", N, s))
+}
+
+fn parse_number(s: &str) -> Result<f32, String> {
+    let value = s.parse::<f32>().map_err(|error| error.to_string())?;
+
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(format!("{} is not a finite number", s))
+    }
+}
+
+fn parse_positive(s: &str) -> Result<f32, String> {
+    let value = parse_number(s)?;
+
+    if value > 0.0 {
+        Ok(value)
+    } else {
+        Err(format!("{} must be greater than 0", s))
+    }
+}
+
+fn parse_non_negative(s: &str) -> Result<f32, String> {
+    let value = parse_number(s)?;
+
+    if value >= 0.0 {
+        Ok(value)
+    } else {
+        Err(format!("{} must not be negative", s))
+    }
+}
+
+// seconds as a number or a fraction like 1/50
+fn parse_shutter(s: &str) -> Result<f32, String> {
+    let value = match s.split_once('/') {
+        Some((numerator, denominator)) => {
+            parse_number(numerator.trim())? / parse_positive(denominator.trim())?
+        }
+        None => parse_number(s)?,
+    };
+
+    if value >= 0.0 {
+        Ok(value)
+    } else {
+        Err(format!("{} must not be negative", s))
+    }
+}
+
+fn parse_fov(s: &str) -> Result<f32, String> {
+    let value = parse_number(s)?;
+
+    if value > 0.0 && value < 180.0 {
+        Ok(value)
+    } else {
+        Err(format!("{} is out of range 0..180", s))
+    }
+}
+
+fn parse_pitch(s: &str) -> Result<f32, String> {
+    let value = parse_number(s)?;
+
+    if (-90.0..=90.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!("{} is out of range -90..90", s))
+    }
+}
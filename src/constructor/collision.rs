@@ -0,0 +1,514 @@
+// camera stays out of the surfaces of the scene: it slides along them when
+// flying and walks on them with gravity, steps and jumps in walk mode.
+// the scene is known by its distance field, shaders share theirs with
+// the app by defining
+//   float map(vec3 p);
+// (or get_dist) which the image pass is compiled into a probe of, see probe_source.
+// any shader can define one, so it is evaluated where the shader runs, on the gpu,
+// and not on the cpu. scene files are the exception, their field is known
+// in rust and measured without a probe, see Scene::distance
+
+// flying camera is a ball of this radius, like the old floor clamp at 0.1
+pub const CAMERA_RADIUS: f32 = 0.1;
+// moves are cut into at most this many steps along surfaces
+const SLIDE_STEPS: usize = 6;
+// closer than this the camera touches the surface and slides
+const CONTACT: f32 = 0.001;
+
+pub const DEFAULT_EYE_HEIGHT: f32 = 1.6;
+pub const DEFAULT_STEP_HEIGHT: f32 = 0.3;
+// meters per second
+pub const WALK_SPEED: f32 = 1.4;
+const GRAVITY: f32 = 9.81;
+// about half a meter high
+const JUMP_SPEED: f32 = 3.2;
+// body collides with walls from step height up, head with ceilings
+const BODY_RADIUS: f32 = 0.25;
+const HEAD_RADIUS: f32 = 0.1;
+// ground steeper than this slides the walker instead of holding it
+const MIN_GROUND_NORMAL: f32 = 0.5;
+// feet this close to the ground stand on it
+const GROUND_TOLERANCE: f32 = 0.01;
+
+// name of the point uniform the probe evaluates the distance at
+pub const PROBE_POINT: &str = "probe_point";
+// distance functions shaders may define, in order of preference
+const DISTANCE_FUNCTIONS: [&str; 2] = ["map", "get_dist"];
+// central differences of the gradient in the probe
+const PROBE_STEP: f32 = 0.001;
+
+#[derive(Clone, Copy, Debug)]
+pub struct FieldSample {
+    // to the nearest surface, negative inside
+    pub distance: f32,
+    // points away from the surface
+    pub gradient: [f32; 3],
+}
+
+impl FieldSample {
+    fn normal(&self) -> [f32; 3] {
+        let length = dot(self.gradient, self.gradient).sqrt();
+        if length > 0.0 {
+            scale(self.gradient, 1.0 / length)
+        } else {
+            [0.0, 1.0, 0.0]
+        }
+    }
+}
+
+pub trait DistanceField {
+    // distance and gradient at every point, in one go
+    // since fields on the gpu are slow to ask
+    fn sample(&mut self, points: &[[f32; 3]]) -> Vec<FieldSample>;
+}
+
+// flat ground at y = 0 that every scene stands on
+pub struct Ground;
+
+impl DistanceField for Ground {
+    fn sample(&mut self, points: &[[f32; 3]]) -> Vec<FieldSample> {
+        points
+            .iter()
+            .map(|point| FieldSample {
+                distance: point[1],
+                gradient: [0.0, 1.0, 0.0],
+            })
+            .collect()
+    }
+}
+
+// nearer of the ground and the scene
+pub fn with_ground(samples: Vec<FieldSample>, points: &[[f32; 3]]) -> Vec<FieldSample> {
+    samples
+        .into_iter()
+        .zip(Ground.sample(points))
+        .map(|(scene, ground)| {
+            if ground.distance < scene.distance {
+                ground
+            } else {
+                scene
+            }
+        })
+        .collect()
+}
+
+fn sample_one(field: &mut dyn DistanceField, point: [f32; 3]) -> FieldSample {
+    field.sample(&[point])[0]
+}
+
+// moves a ball by movement, it stops at surfaces and slides along them
+pub fn slide(
+    field: &mut dyn DistanceField,
+    position: [f32; 3],
+    movement: [f32; 3],
+    radius: f32,
+) -> [f32; 3] {
+    let mut position = position;
+    let mut movement = movement;
+
+    for _ in 0..SLIDE_STEPS {
+        let length = dot(movement, movement).sqrt();
+        if length == 0.0 {
+            break;
+        }
+
+        let sample = sample_one(field, position);
+        let free = sample.distance - radius;
+
+        if free >= length {
+            position = add(position, movement);
+            break;
+        }
+
+        // nothing is nearer than free, so the ball can go that far
+        if free > CONTACT {
+            position = add(position, scale(movement, free / length));
+            movement = scale(movement, 1.0 - free / length);
+            continue;
+        }
+
+        // touching: part of the move going into the surface is dropped
+        let normal = sample.normal();
+        let into = dot(movement, normal);
+        if into < 0.0 {
+            movement = sub(movement, scale(normal, into));
+        }
+        position = add(position, movement);
+        break;
+    }
+
+    push_out(field, position, radius)
+}
+
+// thin walls and moving surfaces can end up inside the ball
+fn push_out(field: &mut dyn DistanceField, position: [f32; 3], radius: f32) -> [f32; 3] {
+    let sample = sample_one(field, position);
+
+    if sample.distance < radius {
+        add(position, scale(sample.normal(), radius - sample.distance))
+    } else {
+        position
+    }
+}
+
+// camera of a person: eye at eye height above the feet, gravity pulls the
+// feet to the ground, steps up to step height are climbed by walking
+pub struct Walk {
+    pub eye_height: f32,
+    pub step_height: f32,
+    vertical_speed: f32,
+    on_ground: bool,
+}
+
+impl Walk {
+    pub fn new(eye_height: f32, step_height: f32) -> Walk {
+        Walk {
+            eye_height,
+            step_height,
+            vertical_speed: 0.0,
+            on_ground: false,
+        }
+    }
+
+    // walking starts again in the air, it falls to the ground first
+    pub fn reset(&mut self) {
+        self.vertical_speed = 0.0;
+        self.on_ground = false;
+    }
+
+    // new eye position after walking by horizontal movement for time step.
+    // jump only starts from the ground
+    pub fn step(
+        &mut self,
+        field: &mut dyn DistanceField,
+        eye: [f32; 3],
+        movement: [f32; 3],
+        time_step: f32,
+        jump: bool,
+    ) -> [f32; 3] {
+        // body ball is above the steps, so they don't stop it
+        let body_drop = self.eye_height - self.step_height - BODY_RADIUS;
+        let body = [eye[0], eye[1] - body_drop, eye[2]];
+        let moved = slide(field, body, [movement[0], 0.0, movement[2]], BODY_RADIUS);
+        let mut eye = [moved[0], eye[1], moved[2]];
+
+        if jump && self.on_ground {
+            self.vertical_speed = JUMP_SPEED;
+            self.on_ground = false;
+        }
+
+        let feet_point = [eye[0], eye[1] - self.eye_height, eye[2]];
+        let samples = field.sample(&[feet_point, eye]);
+        let (feet, head) = (samples[0], samples[1]);
+
+        // height of the feet above the ground under them, negative on a step
+        let gap = feet.distance / feet.normal()[1].max(MIN_GROUND_NORMAL);
+        // walking down stairs keeps the feet on them
+        let reach = if self.on_ground {
+            self.step_height
+        } else {
+            GROUND_TOLERANCE
+        };
+
+        if self.vertical_speed <= 0.0 && gap <= reach {
+            eye[1] -= gap;
+            self.vertical_speed = 0.0;
+            self.on_ground = true;
+            return eye;
+        }
+
+        self.on_ground = false;
+        self.vertical_speed -= GRAVITY * time_step;
+        let mut rise = self.vertical_speed * time_step;
+
+        if rise < 0.0 {
+            // lands on the ground instead of going into it
+            rise = rise.max(-gap.max(0.0));
+        } else {
+            let free = (head.distance - HEAD_RADIUS).max(0.0);
+            if rise > free {
+                rise = free;
+                self.vertical_speed = 0.0;
+            }
+        }
+
+        eye[1] += rise;
+        eye
+    }
+}
+
+// image pass source turned into a probe of its distance function: main()
+// writes distance and gradient at the probe point instead of the image.
+// None when the shader has no distance function
+pub fn probe_source(source: &str) -> Option<String> {
+    let code = strip_comments(source);
+    let function = DISTANCE_FUNCTIONS
+        .iter()
+        .find(|name| defines_function(&code, "float", name))?;
+    let output = first_output(&code)?;
+    let main = find_main(&code)?;
+
+    let mut probe = String::with_capacity(source.len() + 512);
+    probe.push_str(&source[..main]);
+    probe.push_str("void image_main");
+    probe.push_str(&source[main + "void main".len()..]);
+    probe.push_str(&format!(
+        "
+
+uniform vec3 {point};
+
+void main() {{
+    vec3 p = {point};
+    vec2 e = vec2({step:?}, 0.);
+    vec3 gradient = vec3(
+        {function}(p + e.xyy) - {function}(p - e.xyy),
+        {function}(p + e.yxy) - {function}(p - e.yxy),
+        {function}(p + e.yyx) - {function}(p - e.yyx)) / (2. * e.x);
+    {output} = vec4({function}(p), gradient);
+}}
+",
+        point = PROBE_POINT,
+        step = PROBE_STEP,
+        function = function,
+        output = output,
+    ));
+
+    Some(probe)
+}
+
+// comments blanked out, so code in them isn't found. positions
+// of the rest are the same as in source
+fn strip_comments(source: &str) -> String {
+    let mut code = String::with_capacity(source.len());
+    let mut rest = source;
+
+    while let Some(c) = rest.chars().next() {
+        let comment = if rest.starts_with("//") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            comment.find("*/").map_or(rest.len(), |end| end + 4)
+        } else {
+            code.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        };
+
+        for c in rest[..comment].chars() {
+            if c == '\n' {
+                code.push(c);
+            } else {
+                code.extend(std::iter::repeat_n(' ', c.len_utf8()));
+            }
+        }
+        rest = &rest[comment..];
+    }
+
+    code
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// "float map(" with any spaces, not a part of a longer name
+fn defines_function(source: &str, return_type: &str, name: &str) -> bool {
+    source.match_indices(name).any(|(index, _)| {
+        let before = source[..index].trim_end();
+        let after = source[index + name.len()..].trim_start();
+
+        before.ends_with(return_type)
+            && before.len() < index
+            && !before[..before.len() - return_type.len()].ends_with(is_identifier)
+            && after.starts_with('(')
+    })
+}
+
+// first color output, it goes to the probe target
+fn first_output(source: &str) -> Option<&str> {
+    source.lines().find_map(|line| {
+        let mut line = line.trim();
+        if line.starts_with("layout") {
+            line = line[line.find(')')? + 1..].trim_start();
+        }
+        let name = line
+            .strip_prefix("out")?
+            .trim_start()
+            .strip_prefix("vec4")?;
+        if !name.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let name = name.trim_start();
+        let end = name.find(|c: char| !is_identifier(c))?;
+        Some(&name[..end])
+    })
+}
+
+// start of "void main" followed by "("
+fn find_main(source: &str) -> Option<usize> {
+    source
+        .match_indices("void main")
+        .find_map(|(index, pattern)| {
+            let after = &source[index + pattern.len()..];
+            let at_start = source[..index]
+                .chars()
+                .next_back()
+                .is_none_or(|c| !is_identifier(c));
+            if at_start && after.trim_start().starts_with('(') {
+                Some(index)
+            } else {
+                None
+            }
+        })
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f32; 3], factor: f32) -> [f32; 3] {
+    [a[0] * factor, a[1] * factor, a[2] * factor]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::sdf::{sd_box, sd_plane};
+    use super::*;
+    use glam::Vec3;
+
+    // field of a function, gradient by central differences
+    struct Analytic<F: Fn(Vec3) -> f32>(F);
+
+    impl<F: Fn(Vec3) -> f32> DistanceField for Analytic<F> {
+        fn sample(&mut self, points: &[[f32; 3]]) -> Vec<FieldSample> {
+            points
+                .iter()
+                .map(|&point| {
+                    let p = Vec3::from(point);
+                    let gradient = [Vec3::X, Vec3::Y, Vec3::Z].map(|axis| {
+                        let e = axis * PROBE_STEP;
+                        ((self.0)(p + e) - (self.0)(p - e)) / (2.0 * PROBE_STEP)
+                    });
+                    FieldSample {
+                        distance: (self.0)(p),
+                        gradient,
+                    }
+                })
+                .collect()
+        }
+    }
+
+    // ground with a block of the height from x = 1 on
+    fn block(height: f32) -> Analytic<impl Fn(Vec3) -> f32> {
+        Analytic(move |p: Vec3| {
+            let block = sd_box(p - Vec3::new(3.0, 0.0, 0.0), Vec3::new(2.0, height, 10.0));
+            p.y.min(block)
+        })
+    }
+
+    fn walk(field: &mut dyn DistanceField, walk: &mut Walk, eye: [f32; 3], x: f32) -> [f32; 3] {
+        (0..100).fold(eye, |eye, _| {
+            walk.step(field, eye, [x, 0.0, 0.0], 1.0 / 60.0, false)
+        })
+    }
+
+    #[test]
+    fn slide_stops_at_wall() {
+        let mut wall = Analytic(|p: Vec3| sd_plane(p, Vec3::new(-1.0, 0.0, 0.0), -1.0));
+
+        let free = slide(&mut wall, [0.0, 1.0, 0.0], [0.5, 0.0, 0.0], 0.1);
+        assert_eq!(free, [0.5, 1.0, 0.0]);
+
+        let stopped = slide(&mut wall, [0.0, 1.0, 0.0], [2.0, 0.0, 0.0], 0.1);
+        assert!((stopped[0] - 0.9).abs() < 0.01, "{:?}", stopped);
+
+        // part of the moves along the wall is kept
+        let slid = (0..40).fold([0.0, 1.0, 0.0], |position, _| {
+            slide(&mut wall, position, [0.05, 0.0, 0.05], 0.1)
+        });
+        assert!((slid[0] - 0.9).abs() < 0.01, "{:?}", slid);
+        assert!(slid[2] > 1.5, "{:?}", slid);
+    }
+
+    #[test]
+    fn slide_pushes_out_of_surface() {
+        let pushed = slide(&mut Ground, [0.0, 0.05, 0.0], [0.0, 0.0, 0.0], 0.1);
+        assert!((pushed[1] - 0.1).abs() < 1e-6, "{:?}", pushed);
+    }
+
+    #[test]
+    fn walker_falls_to_ground() {
+        let mut walker = Walk::new(DEFAULT_EYE_HEIGHT, DEFAULT_STEP_HEIGHT);
+        let eye = walk(&mut Ground, &mut walker, [0.0, 3.0, 0.0], 0.0);
+        assert!((eye[1] - DEFAULT_EYE_HEIGHT).abs() < 1e-4, "{:?}", eye);
+    }
+
+    #[test]
+    fn walker_climbs_steps_and_stops_at_walls() {
+        let mut walker = Walk::new(DEFAULT_EYE_HEIGHT, DEFAULT_STEP_HEIGHT);
+        let eye = walk(&mut block(0.2), &mut walker, [0.0, 1.6, 0.0], 0.03);
+        assert!(eye[0] > 2.0, "{:?}", eye);
+        assert!((eye[1] - 1.8).abs() < 0.01, "{:?}", eye);
+
+        let mut walker = Walk::new(DEFAULT_EYE_HEIGHT, DEFAULT_STEP_HEIGHT);
+        let eye = walk(&mut block(1.0), &mut walker, [0.0, 1.6, 0.0], 0.03);
+        assert!((eye[0] - (1.0 - BODY_RADIUS)).abs() < 0.01, "{:?}", eye);
+        assert!((eye[1] - 1.6).abs() < 0.01, "{:?}", eye);
+    }
+
+    #[test]
+    fn walker_jumps_from_ground() {
+        let mut walker = Walk::new(DEFAULT_EYE_HEIGHT, DEFAULT_STEP_HEIGHT);
+        let eye = walk(&mut Ground, &mut walker, [0.0, 1.6, 0.0], 0.0);
+
+        let time_step = 1.0 / 60.0;
+        let mut highest = eye[1];
+        let mut eye = walker.step(&mut Ground, eye, [0.0; 3], time_step, true);
+        for _ in 0..120 {
+            highest = highest.max(eye[1]);
+            eye = walker.step(&mut Ground, eye, [0.0; 3], time_step, false);
+        }
+
+        let height = JUMP_SPEED * JUMP_SPEED / (2.0 * GRAVITY);
+        assert!((highest - 1.6 - height).abs() < 0.05, "{}", highest);
+        assert!((eye[1] - 1.6).abs() < 1e-4, "{:?}", eye);
+    }
+
+    #[test]
+    fn probe_evaluates_map() {
+        let source = "\
+out vec4 color;
+float map(vec3 p) { return length(p) - 1.; }
+void main() { color = vec4(map(vec3(0.))); }
+";
+        let probe = probe_source(source).unwrap();
+        assert!(probe.contains("void image_main() { color"));
+        assert!(probe.contains("color = vec4(map(p), gradient);"));
+    }
+
+    #[test]
+    fn probe_ignores_comments() {
+        let source = "\
+// float map(vec3 p) is not here, void main() neither
+/* out vec4 debug; */
+out vec4 color;
+float get_dist(vec3 p) { return p.y; }
+void main() { color = vec4(get_dist(vec3(0.))); }
+";
+        let probe = probe_source(source).unwrap();
+        assert!(probe.starts_with("// float map(vec3 p) is not here, void main() neither\n"));
+        assert!(probe.contains("void image_main() { color"));
+        assert!(probe.contains("color = vec4(get_dist(p), gradient);"));
+
+        assert!(
+            probe_source("out vec4 color;\n// float map(vec3 p) {}\nvoid main() {}\n").is_none()
+        );
+    }
+}
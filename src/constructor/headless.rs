@@ -0,0 +1,116 @@
+use glium::backend::{Backend, Context};
+use glium::debug::DebugCallbackBehavior;
+use glium::SwapBuffersError;
+use glutin_egl::api::egl::context::PossiblyCurrentContext;
+use glutin_egl::api::egl::device::Device;
+use glutin_egl::api::egl::display::Display;
+use glutin_egl::config::{Api, ConfigSurfaceTypes, ConfigTemplateBuilder};
+use glutin_egl::context::{ContextApi, ContextAttributesBuilder, Version};
+use glutin_egl::prelude::*;
+use std::ffi::CString;
+use std::fmt;
+use std::os::raw::c_void;
+use std::rc::Rc;
+
+pub struct HeadlessError(String);
+
+impl fmt::Display for HeadlessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "can not create headless gl context: {}", self.0)
+    }
+}
+
+// gl context without window and without default framebuffer.
+// egl device platform doesn't need x11 or wayland, and with mesa it
+// falls back to llvmpipe software renderer when there is no gpu
+struct SurfacelessBackend {
+    display: Display,
+    context: PossiblyCurrentContext,
+    dimensions: (u32, u32),
+}
+
+unsafe impl Backend for SurfacelessBackend {
+    fn swap_buffers(&self) -> Result<(), SwapBuffersError> {
+        Ok(())
+    }
+
+    unsafe fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        let symbol = CString::new(symbol).unwrap();
+        self.display.get_proc_address(&symbol) as *const c_void
+    }
+
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    fn is_current(&self) -> bool {
+        self.context.is_current()
+    }
+
+    unsafe fn make_current(&self) {
+        self.context.make_current_surfaceless().unwrap();
+    }
+}
+
+fn create_backend(device: &Device, dimensions: (u32, u32)) -> Result<SurfacelessBackend, String> {
+    let display = unsafe { Display::with_device(device, None) }.map_err(|error| error.to_string())?;
+
+    let template = ConfigTemplateBuilder::new()
+        .with_surface_type(ConfigSurfaceTypes::empty())
+        .with_api(Api::OPENGL)
+        .build();
+
+    let config = unsafe { display.find_configs(template) }
+        .map_err(|error| error.to_string())?
+        .next()
+        .ok_or_else(|| String::from("no suitable config"))?;
+
+    // drivers return newest core profile compatible with requested version
+    let attributes = ContextAttributesBuilder::new()
+        .with_context_api(ContextApi::OpenGl(Some(Version::new(3, 3))))
+        .build(None);
+
+    let context = unsafe { display.create_context(&config, &attributes) }
+        .map_err(|error| error.to_string())?
+        .make_current_surfaceless()
+        .map_err(|error| error.to_string())?;
+
+    Ok(SurfacelessBackend {
+        display,
+        context,
+        dimensions,
+    })
+}
+
+pub fn create_headless_context(dimensions: (u32, u32)) -> Result<Rc<Context>, HeadlessError> {
+    let mut devices: Vec<Device> = Device::query_devices()
+        .map_err(|error| HeadlessError(error.to_string()))?
+        .collect();
+
+    // real gpus first, software renderer is the last resort
+    devices.sort_by_key(|device| device.extensions().contains("EGL_MESA_device_software"));
+
+    let mut errors = Vec::new();
+
+    for device in devices.iter() {
+        match create_backend(device, dimensions) {
+            Ok(backend) => {
+                return unsafe {
+                    Context::new(backend, true, DebugCallbackBehavior::default())
+                }
+                .map_err(|error| HeadlessError(error.to_string()));
+            }
+            Err(error) => errors.push(format!(
+                "{}: {}",
+                device.name().unwrap_or("unnamed device"),
+                error
+            )),
+        }
+    }
+
+    if errors.is_empty() {
+        Err(HeadlessError(String::from("no egl devices found")))
+    } else {
+        Err(HeadlessError(errors.join("; ")))
+    }
+}
@@ -0,0 +1,61 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::preprocessor::{preprocess, preprocess_source};
+use super::scene::{is_scene_file, Scene, SCENE_SHADER_NAME};
+use super::shader_error::ShaderError;
+use super::shadertoy::{has_function, is_shadertoy_shader};
+
+// prints what can be learned about the shader without creating gl context
+pub fn print_info(path: &Path, include_dirs: &[PathBuf]) -> Result<(), ShaderError> {
+    // scene files are shown as the shader generated from them
+    let (source, preprocessed) = if is_scene_file(path) {
+        let source = Scene::load(path)?.shader_source();
+        let preprocessed = preprocess_source(SCENE_SHADER_NAME, &source, include_dirs)?;
+        (source, preprocessed)
+    } else {
+        let source = fs::read_to_string(path).map_err(|error| ShaderError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        // uniforms of included files are listed too
+        let preprocessed = preprocess(path, include_dirs)?;
+        (source, preprocessed)
+    };
+
+    let version = source
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("#version"))
+        .map(str::trim)
+        .unwrap_or("none");
+
+    let entry = if has_function(&source, "main") {
+        "main"
+    } else if is_shadertoy_shader(&source) {
+        "mainImage (wrapped for shadertoy compatibility)"
+    } else {
+        "none"
+    };
+
+    println!("file:     {}", path.display());
+    println!("size:     {} bytes, {} lines", source.len(), source.lines().count());
+    println!("version:  {}", version);
+    println!("entry:    {}", entry);
+    println!("includes:");
+
+    for include in preprocessed.source_map.includes() {
+        println!("  {}", include);
+    }
+
+    println!("uniforms:");
+
+    for line in preprocessed.source.lines() {
+        if let Some(declaration) = line.trim().strip_prefix("uniform ") {
+            let declaration = declaration.split(';').next().unwrap_or("").trim();
+            println!("  {}", declaration);
+        }
+    }
+
+    Ok(())
+}
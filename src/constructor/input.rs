@@ -0,0 +1,334 @@
+use glium::glutin::event::{ElementState, MouseButton, VirtualKeyCode, WindowEvent};
+use serde::{Deserialize, Serialize};
+
+use super::orbit::Orbit;
+use super::render::{show_message, FrameInput, RenderData};
+use super::sidecar::SidecarError;
+
+// keys the app reacts to. sessions store these instead of
+// winit key codes, so the file format doesn't follow winit
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Key {
+    W,
+    A,
+    S,
+    D,
+    U,
+    I,
+    O,
+    J,
+    K,
+    L,
+    P,
+    C,
+    V,
+    F,
+    G,
+    N,
+    Space,
+    Tab,
+    Delete,
+    F2,
+    NumpadAdd,
+    NumpadSubtract,
+    F5,
+    F6,
+    F7,
+    F9,
+    // number keys 1 to 9
+    Number(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Button {
+    Left,
+    Middle,
+    Right,
+}
+
+// everything from the window that changes what is drawn
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    Key { key: Key, pressed: bool },
+    Ctrl(bool),
+    Mouse { button: Button, pressed: bool },
+    CursorMoved { x: f32, y: f32 },
+    // lines scrolled, positive up
+    Wheel(f32),
+    Resized { width: u32, height: u32 },
+}
+
+impl InputEvent {
+    // None for events the app doesn't use
+    pub fn from_window_event(event: &WindowEvent) -> Option<InputEvent> {
+        match event {
+            WindowEvent::KeyboardInput { input, .. } => Some(InputEvent::Key {
+                key: key(input.virtual_keycode?)?,
+                pressed: input.state == ElementState::Pressed,
+            }),
+            WindowEvent::ModifiersChanged(state) => Some(InputEvent::Ctrl(state.ctrl())),
+            WindowEvent::MouseInput { button, state, .. } => Some(InputEvent::Mouse {
+                button: match button {
+                    MouseButton::Left => Button::Left,
+                    MouseButton::Middle => Button::Middle,
+                    MouseButton::Right => Button::Right,
+                    MouseButton::Other(_) => return None,
+                },
+                pressed: *state == ElementState::Pressed,
+            }),
+            WindowEvent::CursorMoved { position, .. } => Some(InputEvent::CursorMoved {
+                x: position.x as f32,
+                y: position.y as f32,
+            }),
+            WindowEvent::Resized(size) => Some(InputEvent::Resized {
+                width: size.width,
+                height: size.height,
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn key(key: VirtualKeyCode) -> Option<Key> {
+    Some(match key {
+        VirtualKeyCode::W => Key::W,
+        VirtualKeyCode::A => Key::A,
+        VirtualKeyCode::S => Key::S,
+        VirtualKeyCode::D => Key::D,
+        VirtualKeyCode::U => Key::U,
+        VirtualKeyCode::I => Key::I,
+        VirtualKeyCode::O => Key::O,
+        VirtualKeyCode::J => Key::J,
+        VirtualKeyCode::K => Key::K,
+        VirtualKeyCode::L => Key::L,
+        VirtualKeyCode::P => Key::P,
+        VirtualKeyCode::C => Key::C,
+        VirtualKeyCode::V => Key::V,
+        VirtualKeyCode::F => Key::F,
+        VirtualKeyCode::G => Key::G,
+        VirtualKeyCode::N => Key::N,
+        VirtualKeyCode::Space => Key::Space,
+        VirtualKeyCode::Tab => Key::Tab,
+        VirtualKeyCode::Delete => Key::Delete,
+        VirtualKeyCode::F2 => Key::F2,
+        VirtualKeyCode::NumpadAdd => Key::NumpadAdd,
+        VirtualKeyCode::NumpadSubtract => Key::NumpadSubtract,
+        VirtualKeyCode::F5 => Key::F5,
+        VirtualKeyCode::F6 => Key::F6,
+        VirtualKeyCode::F7 => Key::F7,
+        VirtualKeyCode::F9 => Key::F9,
+        VirtualKeyCode::Key1
+        | VirtualKeyCode::Key2
+        | VirtualKeyCode::Key3
+        | VirtualKeyCode::Key4
+        | VirtualKeyCode::Key5
+        | VirtualKeyCode::Key6
+        | VirtualKeyCode::Key7
+        | VirtualKeyCode::Key8
+        | VirtualKeyCode::Key9 => Key::Number(key as u32 - VirtualKeyCode::Key1 as u32 + 1),
+        _ => return None,
+    })
+}
+
+// live and replayed events go through here. replayed sessions save
+// presets, views and keyframes to the ones recorded, not to files
+pub fn apply_input(render_data: &mut RenderData, event: &InputEvent) {
+    let frame_input = &mut render_data.frame_input;
+
+    match *event {
+        InputEvent::Key { key, pressed } => match key {
+            Key::W => frame_input.w_pressed = pressed,
+            Key::S => frame_input.s_pressed = pressed,
+            Key::A => frame_input.a_pressed = pressed,
+            Key::D => frame_input.d_pressed = pressed,
+            Key::U => frame_input.u_pressed = pressed,
+            Key::I => frame_input.i_pressed = pressed,
+            Key::O => frame_input.o_pressed = pressed,
+            Key::J => frame_input.j_pressed = pressed,
+            Key::K => frame_input.k_pressed = pressed,
+            Key::L => frame_input.l_pressed = pressed,
+            Key::Space => frame_input.space_pressed = pressed,
+            Key::NumpadSubtract => frame_input.camera_speed -= 5.,
+            Key::NumpadAdd => frame_input.camera_speed += 5.,
+            Key::P if pressed => render_data.panel.toggle(),
+            // Tab switches between fly and orbit camera, camera stays where it is
+            Key::Tab if pressed => {
+                let message = if frame_input.orbit.take().is_some() {
+                    "fly camera"
+                } else {
+                    let orbit = Orbit::new(frame_input);
+                    frame_input.orbit = Some(orbit);
+                    "orbit camera: drag turns around the pivot, right button pans, wheel dollies"
+                };
+                show_message(render_data, String::from(message));
+            }
+            // G switches between flying and walking on the scene, Space jumps
+            Key::G if pressed => {
+                frame_input.walking = !frame_input.walking;
+                let message = if frame_input.walking {
+                    frame_input.walk.reset();
+                    frame_input.orbit = None;
+                    "walking: WASD walks, Space jumps"
+                } else {
+                    "flying"
+                };
+                show_message(render_data, String::from(message));
+            }
+            // N lets the flying camera go through surfaces
+            Key::N if pressed => {
+                frame_input.collision = !frame_input.collision;
+                let message = if frame_input.collision {
+                    "camera collides with the scene"
+                } else {
+                    "camera goes through the scene"
+                };
+                show_message(render_data, String::from(message));
+            }
+            // F2 goes through projections, see lens
+            Key::F2 if pressed => {
+                frame_input.lens.projection = frame_input.lens.projection.next();
+                let message = frame_input.lens.projection.name().to_string();
+                show_message(render_data, message);
+            }
+            // F switches autofocus, off it keeps the last focus distance
+            Key::F if pressed => {
+                let photo = &mut frame_input.photo;
+                photo.autofocus = !photo.autofocus;
+                let message = match (photo.f_stop, photo.autofocus) {
+                    (None, _) => String::from("everything is sharp without --f-stop"),
+                    (Some(_), true) => String::from("autofocus on the image center"),
+                    (Some(_), false) => format!("focus fixed at {:.2}", photo.focus_distance),
+                };
+                show_message(render_data, message);
+            }
+            // F5 saves a preset, F6 and F7 go through presets, F9 loads current one again
+            Key::F5 if pressed => {
+                let result = render_data
+                    .presets
+                    .save(&render_data.frame_input, &render_data.parameters);
+                report(render_data, result);
+            }
+            Key::F6 | Key::F7 | Key::F9 if pressed => {
+                let step = match key {
+                    Key::F6 => -1,
+                    Key::F7 => 1,
+                    _ => 0,
+                };
+                let result = render_data.presets.cycle(
+                    step,
+                    &mut render_data.frame_input,
+                    &mut render_data.parameters,
+                );
+                report(render_data, result);
+            }
+            // C adds keyframe to camera path at current view, Delete removes last one,
+            // V plays the path
+            Key::C if pressed => {
+                let result = render_data
+                    .path_editor
+                    .add_keyframe(&render_data.frame_input);
+                report(render_data, result);
+            }
+            Key::Delete if pressed => {
+                let result = render_data.path_editor.remove_last_keyframe();
+                report(render_data, result);
+            }
+            Key::V if pressed => {
+                let result = render_data
+                    .path_editor
+                    .toggle_preview(&mut render_data.frame_input);
+                report(render_data, result);
+            }
+            // number keys fly to saved views, with Ctrl they save current camera
+            Key::Number(number) if pressed => {
+                let result = if frame_input.ctrl_pressed {
+                    render_data.views.save(number, &render_data.frame_input)
+                } else {
+                    render_data
+                        .views
+                        .recall(number, &mut render_data.frame_input)
+                };
+                report(render_data, result);
+            }
+            _ => {}
+        },
+        InputEvent::Ctrl(pressed) => frame_input.ctrl_pressed = pressed,
+        InputEvent::Mouse { button, pressed } => match button {
+            Button::Middle => {
+                frame_input.mouse_button3_pressed = pressed;
+                if pressed {
+                    pick_under_cursor(frame_input);
+                } else {
+                    frame_input.mouse_button3_first_click = true;
+                }
+            }
+            // clicks on the parameter panel don't reach the camera and the shader
+            Button::Left if pressed => {
+                let taken = render_data.panel.press(&mut render_data.parameters);
+                if !taken_by_panel(render_data, taken) {
+                    let frame_input = &mut render_data.frame_input;
+                    frame_input.mouse_button3_pressed = true;
+                    frame_input.shader_toy_mouse.press();
+                    pick_under_cursor(frame_input);
+                }
+            }
+            Button::Left => {
+                let taken = render_data.panel.release(&render_data.parameters);
+                if !taken_by_panel(render_data, taken) {
+                    let frame_input = &mut render_data.frame_input;
+                    frame_input.mouse_button3_pressed = false;
+                    frame_input.mouse_button3_first_click = true;
+                    frame_input.shader_toy_mouse.release();
+                }
+            }
+            Button::Right if pressed => {
+                let taken = render_data.panel.reset(&mut render_data.parameters);
+                if !taken_by_panel(render_data, taken) {
+                    let frame_input = &mut render_data.frame_input;
+                    frame_input.right_button_pressed = true;
+                }
+            }
+            Button::Right => frame_input.right_button_pressed = false,
+        },
+        InputEvent::CursorMoved { x, y } => {
+            frame_input.mouse_input_x = x;
+            frame_input.mouse_input_y = y;
+            frame_input.shader_toy_mouse.move_to(x, y);
+            render_data.panel.move_to(&mut render_data.parameters, x, y);
+        }
+        // wheel changes speed of the fly camera and distance of the orbit one,
+        // moving closer doesn't change orthographic views, so there it zooms
+        InputEvent::Wheel(lines) => match &frame_input.orbit {
+            _ if frame_input.lens.projection.is_orthographic() => frame_input.lens.zoom(lines),
+            Some(orbit) => {
+                frame_input.camera_position = orbit.dolly(frame_input.camera_position, lines)
+            }
+            None => frame_input.camera_speed -= lines / 2.,
+        },
+        InputEvent::Resized { width, height } => {
+            frame_input.display_width = width;
+            frame_input.display_height = height;
+            render_data.panel.resize(width);
+        }
+    }
+}
+
+// orbit camera turns around what was clicked
+fn pick_under_cursor(frame_input: &mut FrameInput) {
+    if let Some(orbit) = &mut frame_input.orbit {
+        orbit.pick = Some([frame_input.mouse_input_x, frame_input.mouse_input_y]);
+    }
+}
+
+// values the panel couldn't save are shown, the click was still on it
+fn taken_by_panel(render_data: &mut RenderData, taken: Result<bool, SidecarError>) -> bool {
+    taken.unwrap_or_else(|error| {
+        show_message(render_data, error.to_string());
+        true
+    })
+}
+
+fn report<E: ToString>(render_data: &mut RenderData, result: Result<String, E>) {
+    let message = result.unwrap_or_else(|error| error.to_string());
+    show_message(render_data, message);
+}
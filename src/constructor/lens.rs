@@ -0,0 +1,321 @@
+use serde::{Deserialize, Serialize};
+
+// isometric view looks down at atan(1 / sqrt 2),
+// so all three axes come out equally long
+const ISOMETRIC_PITCH: f32 = 35.264_39;
+// verticals can't stay vertical when looking straight up or down
+const MAX_TWO_POINT_PITCH: f32 = 80.0;
+// one wheel line changes orthographic view height by this part of it
+const ZOOM_STEP: f32 = 0.1;
+// focal lengths are given for full frame, 36x24 mm
+const SENSOR_HEIGHT: f32 = 24.0;
+
+pub const DEFAULT_VIEW_HEIGHT: f32 = 20.0;
+
+// how shaders including lib/camera.glsl turn pixels into rays
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum Projection {
+    /// usual perspective with fov
+    Perspective,
+    /// perspective with vertical lines kept vertical, view is tilted by lens shift
+    TwoPoint,
+    /// parallel rays, for plans, elevations and axonometric views
+    Orthographic,
+    /// orthographic view from a corner, yaw snaps to 45 degrees plus quarter turns
+    Isometric,
+}
+
+impl Projection {
+    // F2 goes through them in this order
+    pub fn next(self) -> Projection {
+        match self {
+            Projection::Perspective => Projection::TwoPoint,
+            Projection::TwoPoint => Projection::Orthographic,
+            Projection::Orthographic => Projection::Isometric,
+            Projection::Isometric => Projection::Perspective,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Projection::Perspective => "perspective",
+            Projection::TwoPoint => "two-point perspective",
+            Projection::Orthographic => "orthographic",
+            Projection::Isometric => "isometric",
+        }
+    }
+
+    pub fn is_orthographic(self) -> bool {
+        matches!(self, Projection::Orthographic | Projection::Isometric)
+    }
+}
+
+// projection of the camera, fov stays in FrameInput since camera
+// paths animate it:
+//   lens: (projection: Orthographic, shift: (0.0, 0.1), view_height: 30.0),
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Lens {
+    pub projection: Projection,
+    // image shift in parts of image height, positive moves the view right and up
+    pub shift: [f32; 2],
+    // height of orthographic views in scene units
+    pub view_height: f32,
+}
+
+impl Default for Lens {
+    fn default() -> Lens {
+        Lens {
+            projection: Projection::Perspective,
+            shift: [0.0, 0.0],
+            view_height: DEFAULT_VIEW_HEIGHT,
+        }
+    }
+}
+
+// what shaders get for the camera angles and lens
+pub struct LensView {
+    // yaw and pitch of rotation_matrix
+    pub angles: [f32; 2],
+    // projection uniform, 0 perspective and 1 orthographic
+    pub projection: i32,
+    pub shift: [f32; 2],
+}
+
+impl Lens {
+    // two-point perspective keeps the camera level and looks up or down
+    // by shifting the image instead, isometric replaces camera angles
+    pub fn view(&self, angles: [f32; 2], fov: f32) -> LensView {
+        match self.projection {
+            Projection::Perspective => LensView {
+                angles,
+                projection: 0,
+                shift: self.shift,
+            },
+            Projection::TwoPoint => {
+                let pitch = angles[1].clamp(
+                    -MAX_TWO_POINT_PITCH.to_radians(),
+                    MAX_TWO_POINT_PITCH.to_radians(),
+                );
+                // positive pitch looks down
+                let tilt = -pitch.tan() * image_distance(fov) / 2.0;
+
+                LensView {
+                    angles: [angles[0], 0.0],
+                    projection: 0,
+                    shift: [self.shift[0], self.shift[1] + tilt],
+                }
+            }
+            Projection::Orthographic => LensView {
+                angles,
+                projection: 1,
+                shift: self.shift,
+            },
+            Projection::Isometric => {
+                let quarter = std::f32::consts::FRAC_PI_2;
+                let corner = quarter / 2.0;
+                let yaw = ((angles[0] - corner) / quarter).round() * quarter + corner;
+
+                LensView {
+                    angles: [yaw, ISOMETRIC_PITCH.to_radians()],
+                    projection: 1,
+                    shift: self.shift,
+                }
+            }
+        }
+    }
+
+    // height of the view in scene units at distance from the camera
+    pub fn view_size(&self, distance: f32, fov: f32) -> f32 {
+        if self.projection.is_orthographic() {
+            self.view_height
+        } else {
+            distance * 2.0 * (fov.to_radians() / 2.0).tan()
+        }
+    }
+
+    // positive lines zoom in
+    pub fn zoom(&mut self, lines: f32) {
+        self.view_height *= (1.0 - ZOOM_STEP).powf(lines);
+    }
+}
+
+// distance of the image plane of perspective views in parts of half its
+// height, image_distance uniform of lib/camera.glsl. in f64 so that
+// the default fov of 90 gives exactly 1
+pub fn image_distance(fov: f32) -> f32 {
+    (1.0 / (f64::from(fov) / 2.0).to_radians().tan()) as f32
+}
+
+// vertical field of view of a lens with focal length in mm
+pub fn focal_length_fov(focal_length: f32) -> f32 {
+    2.0 * (SENSOR_HEIGHT / 2.0 / focal_length).atan().to_degrees()
+}
+
+// focal length in mm of a lens with vertical field of view in degrees
+pub fn focal_length(fov: f32) -> f32 {
+    SENSOR_HEIGHT / 2.0 / (fov.to_radians() / 2.0).tan()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constructor::orbit::camera_axes;
+
+    const ASPECT: f32 = 16.0 / 9.0;
+    const CORNERS: [[f32; 2]; 4] = [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]];
+
+    // camera_ray of lib/camera.glsl without depth of field, uv from -1 to 1
+    // across the image. origin is relative to the camera position
+    fn ray(lens: &Lens, angles: [f32; 2], fov: f32, uv: [f32; 2]) -> ([f32; 3], [f32; 3]) {
+        let view = lens.view(angles, fov);
+        let axes = camera_axes(view.angles[0], view.angles[1]);
+        let to_world = |v: [f32; 3]| {
+            let mut world = [0.0; 3];
+            for (axis, length) in axes.iter().zip(v.iter()) {
+                for i in 0..3 {
+                    world[i] += axis[i] * length;
+                }
+            }
+            world
+        };
+
+        let x = uv[0] * ASPECT + view.shift[0] * 2.0;
+        let y = uv[1] + view.shift[1] * 2.0;
+
+        if view.projection == 1 {
+            let half = lens.view_height / 2.0;
+            (to_world([x * half, y * half, 0.0]), axes[2])
+        } else {
+            let d = image_distance(fov);
+            let length = (x * x + y * y + d * d).sqrt();
+            ([0.0; 3], to_world([x / length, y / length, d / length]))
+        }
+    }
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    // degrees above the horizon and clockwise from +z seen from above
+    fn direction_angles(direction: [f32; 3]) -> [f32; 2] {
+        [
+            direction[1].asin().to_degrees(),
+            direction[0].atan2(direction[2]).to_degrees(),
+        ]
+    }
+
+    fn lens(projection: Projection) -> Lens {
+        Lens {
+            projection,
+            ..Lens::default()
+        }
+    }
+
+    #[test]
+    fn perspective_rays_spread_by_fov() {
+        let lens = lens(Projection::Perspective);
+        let angles = [30f32.to_radians(), 20f32.to_radians()];
+
+        let (origin, centre) = ray(&lens, angles, 90.0, [0.0, 0.0]);
+        assert_eq!(origin, [0.0; 3]);
+        assert_close(centre, camera_axes(angles[0], angles[1])[2]);
+
+        // corners are half of the fov up or down and the image width sideways
+        for uv in CORNERS.iter() {
+            let (_, direction) = ray(&lens, [0.0, 0.0], 60.0, *uv);
+            let tan = 30f32.to_radians().tan();
+            let expected = [uv[0] * ASPECT * tan, uv[1] * tan, 1.0];
+            let length = (1.0 + (ASPECT * tan).powi(2) + tan * tan).sqrt();
+            assert_close(direction, expected.map(|value| value / length));
+        }
+        assert_eq!(image_distance(90.0), 1.0);
+    }
+
+    #[test]
+    fn shift_moves_the_image() {
+        let lens = Lens {
+            shift: [0.25, -0.5],
+            ..lens(Projection::Perspective)
+        };
+
+        // shifted by half and one image height at fov 90
+        let (_, centre) = ray(&lens, [0.0, 0.0], 90.0, [0.0, 0.0]);
+        let expected = [0.5, -1.0, 1.0];
+        let length = 1.5;
+        assert_close(centre, expected.map(|value| value / length));
+
+        // corner ray of the unshifted view, shifted up by the same amount
+        let (_, corner) = ray(&lens, [0.0, 0.0], 90.0, [1.0, 1.0]);
+        let expected = [ASPECT + 0.5, 0.0, 1.0];
+        let length = ((ASPECT + 0.5).powi(2) + 1.0).sqrt();
+        assert_close(corner, expected.map(|value| value / length));
+    }
+
+    #[test]
+    fn two_point_keeps_verticals() {
+        let lens = lens(Projection::TwoPoint);
+        let angles = [45f32.to_radians(), 30f32.to_radians()];
+
+        // centre looks down like perspective
+        let (_, centre) = ray(&lens, angles, 60.0, [0.0, 0.0]);
+        let [up, heading] = direction_angles(centre);
+        assert!((up + 30.0).abs() < 1e-3 && (heading - 45.0).abs() < 1e-3);
+
+        // corners above each other look along the same vertical plane
+        for x in [-1.0, 1.0] {
+            let (_, bottom) = ray(&lens, angles, 60.0, [x, -1.0]);
+            let (_, top) = ray(&lens, angles, 60.0, [x, 1.0]);
+            assert!((direction_angles(bottom)[1] - direction_angles(top)[1]).abs() < 1e-3);
+        }
+
+        // looking straight down is limited
+        let (_, centre) = ray(&lens, [0.0, 90f32.to_radians()], 60.0, [0.0, 0.0]);
+        assert!((direction_angles(centre)[0] + MAX_TWO_POINT_PITCH).abs() < 1e-3);
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let lens = Lens {
+            view_height: 10.0,
+            ..lens(Projection::Orthographic)
+        };
+        let angles = [30f32.to_radians(), 20f32.to_radians()];
+        let axes = camera_axes(angles[0], angles[1]);
+
+        let (origin, direction) = ray(&lens, angles, 90.0, [0.0, 0.0]);
+        assert_eq!(origin, [0.0; 3]);
+        assert_close(direction, axes[2]);
+
+        // corners start on the camera plane, view_height apart vertically
+        for uv in CORNERS.iter() {
+            let (origin, direction) = ray(&lens, angles, 90.0, *uv);
+            assert_close(direction, axes[2]);
+            let mut expected = [0.0; 3];
+            for (i, value) in expected.iter_mut().enumerate() {
+                *value = axes[0][i] * uv[0] * ASPECT * 5.0 + axes[1][i] * uv[1] * 5.0;
+            }
+            assert_close(origin, expected);
+        }
+        assert_eq!(lens.view_size(100.0, 90.0), 10.0);
+    }
+
+    #[test]
+    fn isometric_looks_along_the_diagonal() {
+        let lens = lens(Projection::Isometric);
+
+        // yaw snaps to the nearest corner, view goes down and along both
+        // horizontal axes at the same angle
+        for (yaw, x, z) in [
+            (10.0, 1.0, 1.0),
+            (100.0, 1.0, -1.0),
+            (-80.0, -1.0, 1.0),
+            (200.0, -1.0, -1.0),
+        ] {
+            let (_, direction) = ray(&lens, [f32::to_radians(yaw), 0.0], 90.0, [0.0, 0.0]);
+            let expected = [x, -1.0, z];
+            assert_close(direction, expected.map(|value| value / 3f32.sqrt()));
+        }
+    }
+}
@@ -1 +1,27 @@
-pub mod render;
\ No newline at end of file
+pub mod camera_path;
+pub mod capture;
+pub mod cli;
+pub mod collision;
+pub mod headless;
+pub mod info;
+pub mod input;
+pub mod lens;
+pub mod orbit;
+pub mod overlay;
+pub mod panel;
+pub mod parameters;
+pub mod photo;
+pub mod pipeline;
+pub mod preprocessor;
+pub mod presets;
+pub mod project;
+pub mod render;
+pub mod scene;
+pub mod sdf;
+pub mod session;
+pub mod shader_error;
+pub mod shadertoy;
+pub mod sidecar;
+pub mod textures;
+pub mod views;
+pub mod watcher;
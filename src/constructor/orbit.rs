@@ -0,0 +1,219 @@
+use super::lens::Lens;
+use super::render::FrameInput;
+
+// pivot in front of the camera when there is no ground under the view
+const DEFAULT_DISTANCE: f32 = 10.0;
+// ground far at the horizon is not a useful pivot
+const MAX_GROUND_DISTANCE: f32 = 100.0;
+// closest the camera comes to the pivot
+const MIN_DISTANCE: f32 = 0.05;
+// one wheel line moves the camera by this part of its distance to the pivot
+const DOLLY_STEP: f32 = 0.1;
+
+// right, up and forward axes of the camera in world space,
+// the way rotation_matrix turns view rays in shaders
+pub fn camera_axes(yaw: f32, pitch: f32) -> [[f32; 3]; 3] {
+    [
+        [yaw.cos(), 0.0, -yaw.sin()],
+        [
+            pitch.sin() * yaw.sin(),
+            pitch.cos(),
+            pitch.sin() * yaw.cos(),
+        ],
+        [
+            pitch.cos() * yaw.sin(),
+            -pitch.sin(),
+            pitch.cos() * yaw.cos(),
+        ],
+    ]
+}
+
+// where the view direction meets the ground, or a point
+// straight ahead when the camera doesn't look down
+pub fn ground_pivot(position: [f32; 3], yaw: f32, pitch: f32) -> [f32; 3] {
+    let forward = camera_axes(yaw, pitch)[2];
+
+    let distance = if forward[1] < 0.0 {
+        (-position[1] / forward[1]).min(MAX_GROUND_DISTANCE)
+    } else {
+        DEFAULT_DISTANCE
+    };
+
+    add(position, scale(forward, distance))
+}
+
+// camera position is kept in camera axes as seen from the pivot,
+// so the camera turns around the pivot as one piece with its view
+// and nothing jumps when the pivot is off the view center
+fn turn(pivot: [f32; 3], position: [f32; 3], from: [f32; 2], to: [f32; 2]) -> [f32; 3] {
+    let offset = sub(position, pivot);
+    let from_axes = camera_axes(from[0], from[1]);
+    let to_axes = camera_axes(to[0], to[1]);
+
+    from_axes
+        .iter()
+        .zip(to_axes.iter())
+        .fold(pivot, |result, (from_axis, to_axis)| {
+            add(result, scale(*to_axis, dot(offset, *from_axis)))
+        })
+}
+
+// camera turns around a pivot with the mouse, right button pans
+// and wheel dollies. pivot is the surface clicked on when the shader
+// writes hit_point, see Pipeline::pick, otherwise the ground under the view
+pub struct Orbit {
+    pub pivot: [f32; 3],
+    // cursor to pick new pivot under, after the frame is drawn
+    pub pick: Option<[f32; 2]>,
+    // cursor at the last frame of a pan
+    pan_from: Option<[f32; 2]>,
+}
+
+impl Orbit {
+    // camera stays where it is, pivot is picked at the view center
+    pub fn new(frame_input: &FrameInput) -> Orbit {
+        Orbit {
+            pivot: ground_pivot(
+                frame_input.camera_position,
+                frame_input.last_angle_x,
+                frame_input.last_angle_y,
+            ),
+            pick: Some([
+                frame_input.display_width as f32 / 2.0,
+                frame_input.display_height as f32 / 2.0,
+            ]),
+            pan_from: None,
+        }
+    }
+
+    // orbit with the pivot it had, like at the start of a recorded session
+    pub fn around(pivot: [f32; 3]) -> Orbit {
+        Orbit {
+            pivot,
+            pick: None,
+            pan_from: None,
+        }
+    }
+
+    pub fn rotate(&self, position: [f32; 3], from: [f32; 2], to: [f32; 2]) -> [f32; 3] {
+        turn(self.pivot, position, from, to)
+    }
+
+    // positive lines move towards the pivot
+    pub fn dolly(&self, position: [f32; 3], lines: f32) -> [f32; 3] {
+        let offset = sub(position, self.pivot);
+        let distance = dot(offset, offset).sqrt();
+        if distance == 0.0 {
+            return position;
+        }
+
+        let new_distance = (distance * (1.0 - DOLLY_STEP).powf(lines)).max(MIN_DISTANCE);
+        add(self.pivot, scale(offset, new_distance / distance))
+    }
+
+    // moves camera and pivot so the point under the cursor at pivot
+    // distance follows the cursor. returns new camera position
+    pub fn pan(
+        &mut self,
+        position: [f32; 3],
+        cursor: [f32; 2],
+        angles: [f32; 2],
+        lens: &Lens,
+        fov: f32,
+        view_height: u32,
+    ) -> [f32; 3] {
+        let from = self.pan_from.replace(cursor).unwrap_or(cursor);
+
+        let offset = sub(position, self.pivot);
+        let distance = dot(offset, offset).sqrt();
+        let units_per_pixel = lens.view_size(distance, fov) / view_height.max(1) as f32;
+
+        let [right, up, _] = camera_axes(angles[0], angles[1]);
+        let shift = add(
+            scale(right, (from[0] - cursor[0]) * units_per_pixel),
+            scale(up, (cursor[1] - from[1]) * units_per_pixel),
+        );
+
+        self.pivot = add(self.pivot, shift);
+        add(position, shift)
+    }
+
+    pub fn end_pan(&mut self) {
+        self.pan_from = None;
+    }
+
+    pub fn move_by(&mut self, shift: [f32; 3]) {
+        self.pivot = add(self.pivot, shift);
+    }
+}
+
+// camera of a video circles the pivot by `degrees` over `duration`
+// seconds of shader time, starting from the camera given on command line
+pub struct Turntable {
+    pivot: [f32; 3],
+    start_position: [f32; 3],
+    start_angles: [f32; 2],
+    degrees: f32,
+    duration: f32,
+}
+
+impl Turntable {
+    pub fn new(
+        frame_input: &FrameInput,
+        degrees: f32,
+        duration: f32,
+        pivot: Option<[f32; 3]>,
+    ) -> Turntable {
+        let start_angles = [frame_input.last_angle_x, frame_input.last_angle_y];
+
+        Turntable {
+            pivot: pivot.unwrap_or_else(|| {
+                ground_pivot(
+                    frame_input.camera_position,
+                    start_angles[0],
+                    start_angles[1],
+                )
+            }),
+            start_position: frame_input.camera_position,
+            start_angles,
+            degrees,
+            duration,
+        }
+    }
+
+    pub fn pivot(&self) -> [f32; 3] {
+        self.pivot
+    }
+
+    // camera position and yaw at the time, found from the start
+    // every time, so separately rendered frame ranges fit together
+    pub fn camera(&self, time: f32) -> ([f32; 3], f32) {
+        let angle = (self.degrees * time / self.duration).to_radians();
+        let yaw = self.start_angles[0] + angle;
+
+        let position = turn(
+            self.pivot,
+            self.start_position,
+            self.start_angles,
+            [yaw, self.start_angles[1]],
+        );
+
+        (position, yaw)
+    }
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f32; 3], factor: f32) -> [f32; 3] {
+    [a[0] * factor, a[1] * factor, a[2] * factor]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
@@ -0,0 +1,286 @@
+use glium::backend::Facade;
+use glium::texture::{MipmapsOption, RawImage2d, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+use glium::{IndexBuffer, Program, Surface, Texture2d, VertexBuffer};
+
+use super::render::Vertex;
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+const FIRST_CHAR: u8 = b' ';
+const SCALE: f32 = 2.0;
+
+// size of a character cell on screen, in window pixels
+pub const CHAR_SIZE: [f32; 2] = [6.0 * SCALE, 9.0 * SCALE];
+
+// classic 5x7 lcd font for ascii 32..126,
+// every byte is a column of the glyph, lowest bit is the top row
+const FONT: [[u8; GLYPH_WIDTH]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x00, 0x08, 0x14, 0x22, 0x41], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x41, 0x22, 0x14, 0x08, 0x00], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x01, 0x01], // F
+    [0x3E, 0x41, 0x41, 0x51, 0x32], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x04, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x7F, 0x20, 0x18, 0x20, 0x7F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x03, 0x04, 0x78, 0x04, 0x03], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x00, 0x7F, 0x41, 0x41], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x41, 0x41, 0x7F, 0x00, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x08, 0x14, 0x54, 0x54, 0x3C], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x00, 0x7F, 0x10, 0x28, 0x44], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+const OVERLAY_FRAGMENT_SHADER: &str = "#version 140
+
+uniform vec3 iResolution;
+uniform sampler2D font;
+uniform sampler2D text;
+uniform vec2 text_size;
+uniform float scale;
+uniform vec4 text_color;
+uniform vec4 background_color;
+uniform vec2 origin;
+
+in vec2 fragCoord;
+out vec4 fragColor;
+
+const vec2 CELL = vec2(6.0, 9.0);
+
+void main() {
+    // origin is the top left corner of the text in window pixels
+    vec2 pixel = (vec2(fragCoord.x, iResolution.y - fragCoord.y) - origin) / scale;
+    vec2 cell = floor(pixel / CELL);
+
+    if (pixel.x < 0.0 || pixel.y < 0.0 || cell.x >= text_size.x || cell.y >= text_size.y) {
+        discard;
+    }
+
+    int code = int(texelFetch(text, ivec2(cell), 0).r * 255.0 + 0.5);
+    ivec2 glyph_pixel = ivec2(pixel - cell * CELL);
+
+    float lit = 0.0;
+    if (code >= 32 && glyph_pixel.x < 5 && glyph_pixel.y < 7) {
+        lit = texelFetch(font, ivec2((code - 32) * 5 + glyph_pixel.x, glyph_pixel.y), 0).r;
+    }
+
+    fragColor = mix(background_color, text_color, lit);
+}";
+
+// drawn instead of a shader that failed to compile, when there is no
+// previous good program to fall back to
+pub const ERROR_FRAGMENT_SHADER: &str = "#version 140
+
+uniform vec3 iResolution;
+
+in vec2 fragCoord;
+out vec4 fragColor;
+
+void main() {
+    float stripe = step(0.5, fract((fragCoord.x + fragCoord.y) / 40.0));
+    fragColor = vec4(mix(vec3(0.25, 0.02, 0.02), vec3(0.35, 0.05, 0.05), stripe), 1.0);
+}";
+
+pub struct TextOverlay {
+    program: Program,
+    font_texture: Texture2d,
+}
+
+fn font_image() -> RawImage2d<'static, u8> {
+    let width = FONT.len() * GLYPH_WIDTH;
+    let mut data = vec![0u8; width * GLYPH_HEIGHT * 4];
+
+    for (glyph_index, glyph) in FONT.iter().enumerate() {
+        for (column, bits) in glyph.iter().enumerate() {
+            for row in 0..GLYPH_HEIGHT {
+                if bits & (1 << row) != 0 {
+                    let offset = (row * width + glyph_index * GLYPH_WIDTH + column) * 4;
+                    data[offset..offset + 4].copy_from_slice(&[255, 255, 255, 255]);
+                }
+            }
+        }
+    }
+
+    RawImage2d::from_raw_rgba(data, (width as u32, GLYPH_HEIGHT as u32))
+}
+
+// every char of the text is one texel, red channel holds ascii code
+fn text_image(lines: &[String]) -> (RawImage2d<'static, u8>, (usize, usize)) {
+    let columns = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let rows = lines.len().max(1);
+
+    let mut data = vec![0u8; columns * rows * 4];
+
+    for (row, line) in lines.iter().enumerate() {
+        for (column, c) in line.chars().enumerate() {
+            let code = if c.is_ascii_graphic() { c as u8 } else { FIRST_CHAR };
+            data[(row * columns + column) * 4] = code;
+        }
+    }
+
+    (
+        RawImage2d::from_raw_rgba(data, (columns as u32, rows as u32)),
+        (columns, rows),
+    )
+}
+
+impl TextOverlay {
+    pub fn new<F: Facade>(facade: &F, vertex_shader_src: &str) -> TextOverlay {
+        let program =
+            Program::from_source(facade, vertex_shader_src, OVERLAY_FRAGMENT_SHADER, None)
+                .expect("can not compile built-in overlay shader");
+
+        let font_texture = Texture2d::with_format(
+            facade,
+            font_image(),
+            UncompressedFloatFormat::U8U8U8U8,
+            MipmapsOption::NoMipmap,
+        )
+        .unwrap();
+
+        TextOverlay {
+            program,
+            font_texture,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<F: Facade, S: Surface>(
+        &self,
+        facade: &F,
+        surface: &mut S,
+        vertex_buffer: &VertexBuffer<Vertex>,
+        indices_buffer: &IndexBuffer<u8>,
+        lines: &[String],
+        origin: [f32; 2],
+        text_color: [f32; 4],
+        background_color: [f32; 4],
+    ) {
+        let (image, (columns, rows)) = text_image(lines);
+
+        let text_texture = Texture2d::with_format(
+            facade,
+            image,
+            UncompressedFloatFormat::U8U8U8U8,
+            MipmapsOption::NoMipmap,
+        )
+        .unwrap();
+
+        let (width, height) = surface.get_dimensions();
+
+        surface
+            .draw(
+                vertex_buffer,
+                indices_buffer,
+                &self.program,
+                &uniform! {
+                    iResolution: [width as f32, height as f32, 1.0],
+                    font: self.font_texture
+                        .sampled()
+                        .minify_filter(MinifySamplerFilter::Nearest)
+                        .magnify_filter(MagnifySamplerFilter::Nearest),
+                    text: text_texture
+                        .sampled()
+                        .minify_filter(MinifySamplerFilter::Nearest)
+                        .magnify_filter(MagnifySamplerFilter::Nearest),
+                    text_size: [columns as f32, rows as f32],
+                    scale: SCALE,
+                    origin: origin,
+                    text_color: text_color,
+                    background_color: background_color,
+                },
+                &glium::draw_parameters::DrawParameters {
+                    blend: glium::Blend::alpha_blending(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+    }
+}
@@ -0,0 +1,415 @@
+use glium::backend::Facade;
+use glium::index::{NoIndices, PrimitiveType};
+use glium::{IndexBuffer, Program, Surface, VertexBuffer};
+
+use super::overlay::{TextOverlay, CHAR_SIZE};
+use super::parameters::{Parameter, ParameterType, Parameters, Widget};
+use super::render::Vertex;
+use super::sidecar::SidecarError;
+
+// panel is a grid of text cells: label, widget, value
+const LABEL_COLUMNS: usize = 14;
+const WIDGET_COLUMNS: usize = 14;
+const VALUE_COLUMNS: usize = 8;
+const COLUMNS: usize = LABEL_COLUMNS + 1 + WIDGET_COLUMNS + 1 + VALUE_COLUMNS;
+const MARGIN: f32 = 8.0;
+
+const TEXT_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const BACKGROUND_COLOR: [f32; 4] = [0.05, 0.05, 0.05, 0.75];
+const TRACK_COLOR: [f32; 4] = [0.25, 0.25, 0.25, 1.0];
+const FILL_COLOR: [f32; 4] = [0.45, 0.65, 1.0, 1.0];
+const CHANNEL_COLORS: [[f32; 4]; 4] = [
+    [0.9, 0.3, 0.3, 1.0],
+    [0.3, 0.8, 0.3, 1.0],
+    [0.35, 0.5, 1.0, 1.0],
+    [0.7, 0.7, 0.7, 1.0],
+];
+
+const RECT_VERTEX_SHADER: &str = "#version 140
+
+in vec2 position;
+in vec4 color;
+out vec4 rect_color;
+
+void main() {
+    rect_color = color;
+    gl_Position = vec4(position, 0.0, 1.0);
+}";
+
+const RECT_FRAGMENT_SHADER: &str = "#version 140
+
+in vec4 rect_color;
+out vec4 fragColor;
+
+void main() {
+    fragColor = rect_color;
+}";
+
+#[derive(Clone, Copy)]
+struct RectVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+implement_vertex!(RectVertex, position, color);
+
+#[derive(Clone, Copy, PartialEq)]
+enum RowKind {
+    Header,
+    // component of a float, int or color parameter
+    Slider {
+        index: usize,
+        component: usize,
+        min: f32,
+        max: f32,
+    },
+    Swatch(usize),
+    Checkbox(usize),
+}
+
+struct Row {
+    text: String,
+    kind: RowKind,
+}
+
+// on-screen controls for annotated uniforms, drawn in the top right corner.
+// P shows and hides it, left button drags sliders and toggles checkboxes,
+// right button resets a parameter to its default
+pub struct ParameterPanel {
+    program: Program,
+    visible: bool,
+    cursor: [f32; 2],
+    window_width: f32,
+    // slider being dragged
+    drag: Option<RowKind>,
+    // press was inside the panel, its release belongs here too
+    pressed: bool,
+}
+
+impl ParameterPanel {
+    pub fn new<F: Facade>(facade: &F, window_width: u32) -> ParameterPanel {
+        let program = Program::from_source(facade, RECT_VERTEX_SHADER, RECT_FRAGMENT_SHADER, None)
+            .expect("can not compile built-in panel shader");
+
+        ParameterPanel {
+            program,
+            visible: true,
+            cursor: [0.0, 0.0],
+            window_width: window_width as f32,
+            drag: None,
+            pressed: false,
+        }
+    }
+
+    // panel sits at the right edge, so clicks depend on window width.
+    // it comes with input events, not from drawing, so that replayed
+    // sessions without window hit the same rows
+    pub fn resize(&mut self, window_width: u32) {
+        self.window_width = window_width as f32;
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.drag = None;
+    }
+
+    pub fn move_to(&mut self, parameters: &mut Parameters, x: f32, y: f32) {
+        self.cursor = [x, y];
+
+        if let Some(kind) = self.drag {
+            self.set_slider(parameters, kind);
+        }
+    }
+
+    // true when the click was taken by the panel, changed values are saved
+    pub fn press(&mut self, parameters: &mut Parameters) -> Result<bool, SidecarError> {
+        let kind = match self.row_at_cursor(parameters) {
+            Some(kind) => kind,
+            None => return Ok(false),
+        };
+
+        // release after a failed save still belongs to the panel
+        self.pressed = true;
+        match kind {
+            RowKind::Slider { .. } => {
+                self.drag = Some(kind);
+                self.set_slider(parameters, kind);
+            }
+            RowKind::Checkbox(index) => {
+                let parameter = parameters.definitions()[index].clone();
+                let mut value = parameters.value(&parameter);
+                value[0] = 1.0 - value[0];
+                parameters.set_value(&parameter, value);
+                parameters.save()?;
+            }
+            RowKind::Header | RowKind::Swatch(_) => {}
+        }
+
+        Ok(true)
+    }
+
+    // true when the matching press was taken by the panel
+    pub fn release(&mut self, parameters: &Parameters) -> Result<bool, SidecarError> {
+        let pressed = std::mem::replace(&mut self.pressed, false);
+        if self.drag.take().is_some() {
+            parameters.save()?;
+        }
+
+        Ok(pressed)
+    }
+
+    pub fn reset(&mut self, parameters: &mut Parameters) -> Result<bool, SidecarError> {
+        let index = match self.row_at_cursor(parameters) {
+            Some(RowKind::Slider { index, .. })
+            | Some(RowKind::Swatch(index))
+            | Some(RowKind::Checkbox(index)) => index,
+            Some(RowKind::Header) => return Ok(true),
+            None => return Ok(false),
+        };
+
+        let parameter = parameters.definitions()[index].clone();
+        parameters.reset(&parameter);
+        parameters.save()?;
+        Ok(true)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<F: Facade, S: Surface>(
+        &self,
+        facade: &F,
+        surface: &mut S,
+        overlay: &TextOverlay,
+        vertex_buffer: &VertexBuffer<Vertex>,
+        indices_buffer: &IndexBuffer<u8>,
+        parameters: &Parameters,
+    ) {
+        let (width, height) = surface.get_dimensions();
+
+        if !self.visible || parameters.definitions().is_empty() {
+            return;
+        }
+
+        let rows = rows(parameters);
+        let left = self.left();
+
+        let lines: Vec<String> = rows.iter().map(|row| row.text.clone()).collect();
+        overlay.draw(
+            facade,
+            surface,
+            vertex_buffer,
+            indices_buffer,
+            &lines,
+            [left, MARGIN],
+            TEXT_COLOR,
+            BACKGROUND_COLOR,
+        );
+
+        let mut rects = Vec::new();
+        let widget_left = left + (LABEL_COLUMNS + 1) as f32 * CHAR_SIZE[0];
+        let widget_width = WIDGET_COLUMNS as f32 * CHAR_SIZE[0];
+
+        for (row_index, row) in rows.iter().enumerate() {
+            let top = MARGIN + row_index as f32 * CHAR_SIZE[1];
+            let middle = top + CHAR_SIZE[1] / 2.0;
+
+            match row.kind {
+                RowKind::Header => {}
+                RowKind::Slider {
+                    index,
+                    component,
+                    min,
+                    max,
+                } => {
+                    let parameter = &parameters.definitions()[index];
+                    let value = parameters.value(parameter)[component];
+                    let fill = ((value - min) / (max - min)).clamp(0.0, 1.0) * widget_width;
+                    let color = match parameter.widget {
+                        Widget::Color => CHANNEL_COLORS[component],
+                        _ => FILL_COLOR,
+                    };
+                    rects.push(([widget_left, middle - 3.0, widget_width, 6.0], TRACK_COLOR));
+                    rects.push(([widget_left, middle - 3.0, fill, 6.0], color));
+                    rects.push((
+                        [widget_left + fill - 2.0, top + 2.0, 4.0, CHAR_SIZE[1] - 4.0],
+                        TEXT_COLOR,
+                    ));
+                }
+                RowKind::Swatch(index) => {
+                    let value = parameters.value(&parameters.definitions()[index]);
+                    rects.push((
+                        [widget_left, top + 2.0, widget_width, CHAR_SIZE[1] - 4.0],
+                        [value[0], value[1], value[2], 1.0],
+                    ));
+                }
+                RowKind::Checkbox(index) => {
+                    let value = parameters.value(&parameters.definitions()[index]);
+                    let size = CHAR_SIZE[1] - 4.0;
+                    rects.push(([widget_left, top + 2.0, size, size], TRACK_COLOR));
+                    if value[0] > 0.5 {
+                        rects.push((
+                            [widget_left + 3.0, top + 5.0, size - 6.0, size - 6.0],
+                            FILL_COLOR,
+                        ));
+                    }
+                }
+            }
+        }
+
+        let vertices: Vec<RectVertex> = rects
+            .iter()
+            .flat_map(|(rect, color)| rect_vertices(*rect, *color, (width, height)))
+            .collect();
+        let vertices = VertexBuffer::new(facade, &vertices).unwrap();
+
+        surface
+            .draw(
+                &vertices,
+                NoIndices(PrimitiveType::TrianglesList),
+                &self.program,
+                &glium::uniforms::EmptyUniforms,
+                &glium::draw_parameters::DrawParameters {
+                    blend: glium::Blend::alpha_blending(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+    }
+
+    fn left(&self) -> f32 {
+        self.window_width - MARGIN - COLUMNS as f32 * CHAR_SIZE[0]
+    }
+
+    fn row_at_cursor(&self, parameters: &Parameters) -> Option<RowKind> {
+        if !self.visible || parameters.definitions().is_empty() {
+            return None;
+        }
+
+        let [x, y] = self.cursor;
+        let right = self.left() + COLUMNS as f32 * CHAR_SIZE[0];
+        if x < self.left() || x >= right || y < MARGIN {
+            return None;
+        }
+
+        let row = ((y - MARGIN) / CHAR_SIZE[1]) as usize;
+        rows(parameters).get(row).map(|row| row.kind)
+    }
+
+    fn set_slider(&self, parameters: &mut Parameters, kind: RowKind) {
+        if let RowKind::Slider {
+            index,
+            component,
+            min,
+            max,
+        } = kind
+        {
+            let widget_left = self.left() + (LABEL_COLUMNS + 1) as f32 * CHAR_SIZE[0];
+            let widget_width = WIDGET_COLUMNS as f32 * CHAR_SIZE[0];
+            let t = ((self.cursor[0] - widget_left) / widget_width).clamp(0.0, 1.0);
+
+            let parameter = parameters.definitions()[index].clone();
+            let mut value = parameters.value(&parameter);
+            value[component] = min + t * (max - min);
+            parameters.set_value(&parameter, value);
+        }
+    }
+}
+
+fn rows(parameters: &Parameters) -> Vec<Row> {
+    let mut rows = vec![Row {
+        text: format!("{:<width$}", "parameters (P hides)", width = COLUMNS),
+        kind: RowKind::Header,
+    }];
+
+    for (index, parameter) in parameters.definitions().iter().enumerate() {
+        let value = parameters.value(parameter);
+
+        match parameter.widget {
+            Widget::Slider { min, max } => rows.push(Row {
+                text: row_text(&parameter.name, &number_text(parameter, value[0])),
+                kind: RowKind::Slider {
+                    index,
+                    component: 0,
+                    min,
+                    max,
+                },
+            }),
+            Widget::Color => {
+                let components = parameter.parameter_type.components();
+                let hex: String = value[..3]
+                    .iter()
+                    .map(|component| format!("{:02x}", (component * 255.0).round() as u8))
+                    .collect();
+                rows.push(Row {
+                    text: row_text(&parameter.name, &format!("#{}", hex)),
+                    kind: RowKind::Swatch(index),
+                });
+
+                for (component, name) in ["r", "g", "b", "a"].iter().enumerate().take(components) {
+                    rows.push(Row {
+                        text: row_text(&format!("  {}", name), &format!("{:.3}", value[component])),
+                        kind: RowKind::Slider {
+                            index,
+                            component,
+                            min: 0.0,
+                            max: 1.0,
+                        },
+                    });
+                }
+            }
+            Widget::Checkbox => {
+                let text = if value[0] > 0.5 { "on" } else { "off" };
+                rows.push(Row {
+                    text: row_text(&parameter.name, text),
+                    kind: RowKind::Checkbox(index),
+                });
+            }
+        }
+    }
+
+    rows
+}
+
+fn number_text(parameter: &Parameter, value: f32) -> String {
+    match parameter.parameter_type {
+        ParameterType::Int => format!("{}", value as i32),
+        _ => format!("{:.3}", value),
+    }
+}
+
+// label and value with empty space for the widget between them
+fn row_text(label: &str, value: &str) -> String {
+    let label: String = label.chars().take(LABEL_COLUMNS).collect();
+    let value: String = value.chars().take(VALUE_COLUMNS).collect();
+
+    format!(
+        "{:<label_width$} {:width$} {:>value_width$}",
+        label,
+        "",
+        value,
+        label_width = LABEL_COLUMNS,
+        width = WIDGET_COLUMNS,
+        value_width = VALUE_COLUMNS,
+    )
+}
+
+// two triangles, rect is left, top, width, height in window pixels
+fn rect_vertices(rect: [f32; 4], color: [f32; 4], window: (u32, u32)) -> Vec<RectVertex> {
+    let [left, top, width, height] = rect;
+    let x = |x: f32| x / window.0 as f32 * 2.0 - 1.0;
+    let y = |y: f32| 1.0 - y / window.1 as f32 * 2.0;
+
+    let corners = [
+        [x(left), y(top)],
+        [x(left + width), y(top)],
+        [x(left), y(top + height)],
+        [x(left + width), y(top + height)],
+    ];
+
+    [0, 1, 2, 1, 3, 2]
+        .iter()
+        .map(|&corner| RectVertex {
+            position: corners[corner],
+            color,
+        })
+        .collect()
+}
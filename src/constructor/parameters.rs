@@ -0,0 +1,530 @@
+use glium::uniforms::{UniformValue, Uniforms};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use super::preprocessor::SourceMap;
+use super::shader_error::{ShaderError, ShaderMessage};
+use super::sidecar::{self, sidecar_path, SidecarError};
+
+// saved values are kept next to the shader in "<shader file>.params"
+const VALUES_EXTENSION: &str = "params";
+const WHAT: &str = "parameters";
+
+// annotations of parameters, other "// @word" comments are left to the shader
+const WIDGETS: [&str; 3] = ["slider", "color", "checkbox"];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ParameterType {
+    Float,
+    Int,
+    Bool,
+    Vec3,
+    Vec4,
+}
+
+impl ParameterType {
+    fn from_glsl(name: &str) -> Option<ParameterType> {
+        match name {
+            "float" => Some(ParameterType::Float),
+            "int" => Some(ParameterType::Int),
+            "bool" => Some(ParameterType::Bool),
+            "vec3" => Some(ParameterType::Vec3),
+            "vec4" => Some(ParameterType::Vec4),
+            _ => None,
+        }
+    }
+
+    pub fn components(self) -> usize {
+        match self {
+            ParameterType::Float | ParameterType::Int | ParameterType::Bool => 1,
+            ParameterType::Vec3 => 3,
+            ParameterType::Vec4 => 4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Widget {
+    Slider { min: f32, max: f32 },
+    Color,
+    Checkbox,
+}
+
+// uniform declared with an annotation comment:
+//   uniform float wall_height; // @slider(0, 10, 3.2)
+//   uniform int floors = 3;    // @slider(1, 20)
+//   uniform vec3 tint;         // @color(1, 0.5, 0)
+//   uniform bool windows;      // @checkbox(true)
+// default value is the last annotation argument, then the initializer
+#[derive(Clone, PartialEq, Debug)]
+pub struct Parameter {
+    pub name: String,
+    pub parameter_type: ParameterType,
+    pub widget: Widget,
+    pub default: [f32; 4],
+}
+
+impl Parameter {
+    // rounds ints and bools, keeps slider values in range
+    pub fn clamp(&self, mut value: [f32; 4]) -> [f32; 4] {
+        if let Widget::Slider { min, max } = self.widget {
+            value[0] = value[0].clamp(min, max);
+        }
+
+        match self.parameter_type {
+            ParameterType::Int => value[0] = value[0].round(),
+            ParameterType::Bool => value[0] = if value[0] > 0.5 { 1.0 } else { 0.0 },
+            ParameterType::Vec3 | ParameterType::Vec4 => {
+                for component in value.iter_mut() {
+                    *component = component.clamp(0.0, 1.0);
+                }
+            }
+            ParameterType::Float => {}
+        }
+
+        value
+    }
+
+    fn uniform_value(&self, value: [f32; 4]) -> UniformValue<'static> {
+        match self.parameter_type {
+            ParameterType::Float => UniformValue::Float(value[0]),
+            ParameterType::Int => UniformValue::SignedInt(value[0] as i32),
+            ParameterType::Bool => UniformValue::Bool(value[0] > 0.5),
+            ParameterType::Vec3 => UniformValue::Vec3([value[0], value[1], value[2]]),
+            ParameterType::Vec4 => UniformValue::Vec4(value),
+        }
+    }
+}
+
+// finds annotated uniforms in expanded shader source.
+// errors point to the file and line of the declaration
+pub fn parse_parameters(
+    source: &str,
+    source_map: &SourceMap,
+) -> Result<Vec<Parameter>, ShaderError> {
+    let mut parameters: Vec<Parameter> = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let parameter = match parse_line(line) {
+            Some(Ok(parameter)) => parameter,
+            Some(Err(message)) => {
+                let line_number = index as u32 + 1;
+                let (file, line) = source_map
+                    .locate(line_number)
+                    .unwrap_or((source_map.name(), line_number));
+
+                return Err(ShaderError::Compile {
+                    messages: vec![ShaderMessage {
+                        file: String::from(file),
+                        line: Some(line),
+                        message,
+                    }],
+                });
+            }
+            None => continue,
+        };
+
+        if !parameters.iter().any(|known| known.name == parameter.name) {
+            parameters.push(parameter);
+        }
+    }
+
+    Ok(parameters)
+}
+
+// None for lines that are not annotated uniform declarations
+fn parse_line(line: &str) -> Option<Result<Parameter, String>> {
+    let (code, comment) = line.split_once("//")?;
+    let annotation = comment.trim().strip_prefix('@')?;
+    let kind = annotation
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .next()?;
+    if !WIDGETS.contains(&kind) {
+        return None;
+    }
+
+    let declaration = code.trim().strip_prefix("uniform")?;
+    if !declaration.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    Some(parse_declaration(declaration, annotation.trim()))
+}
+
+fn parse_declaration(declaration: &str, annotation: &str) -> Result<Parameter, String> {
+    let declaration = declaration.trim().trim_end_matches(';');
+
+    let (declaration, initializer) = match declaration.split_once('=') {
+        Some((declaration, initializer)) => (declaration, Some(initializer.trim())),
+        None => (declaration, None),
+    };
+
+    // precision qualifiers don't matter on desktop
+    let words: Vec<&str> = declaration
+        .split_whitespace()
+        .filter(|word| !matches!(*word, "lowp" | "mediump" | "highp"))
+        .collect();
+
+    let (type_name, name) = match words.as_slice() {
+        [type_name, name] => (*type_name, *name),
+        _ => {
+            return Err(format!(
+                "can not read uniform declaration \"{}\"",
+                declaration.trim()
+            ))
+        }
+    };
+
+    let parameter_type = ParameterType::from_glsl(type_name).ok_or_else(|| {
+        format!(
+            "{} {}: parameters can be float, int, bool, vec3 or vec4",
+            type_name, name
+        )
+    })?;
+
+    let (kind, arguments) = match annotation.split_once('(') {
+        Some((kind, arguments)) => {
+            let arguments = arguments
+                .trim_end()
+                .strip_suffix(')')
+                .ok_or_else(|| format!("@{}: missing \")\"", kind.trim()))?;
+            (kind.trim(), parse_numbers(arguments)?)
+        }
+        None => (
+            annotation.split_whitespace().next().unwrap_or(""),
+            Vec::new(),
+        ),
+    };
+
+    let initializer = match initializer {
+        Some(initializer) => Some(parse_initializer(initializer)?),
+        None => None,
+    };
+
+    let wrong_type = || format!("@{} can not be used with {} {}", kind, type_name, name);
+
+    let (widget, default) = match kind {
+        "slider" => {
+            if !matches!(parameter_type, ParameterType::Float | ParameterType::Int) {
+                return Err(wrong_type());
+            }
+            let (min, max, default) = match arguments.as_slice() {
+                [min, max] => (*min, *max, initializer.map_or(*min, |value| value[0])),
+                [min, max, default] => (*min, *max, *default),
+                _ => {
+                    return Err(format!(
+                        "{}: expected @slider(min, max) or @slider(min, max, default)",
+                        name
+                    ))
+                }
+            };
+            if min >= max {
+                return Err(format!(
+                    "{}: slider minimum {} is not less than maximum {}",
+                    name, min, max
+                ));
+            }
+            (Widget::Slider { min, max }, [default, 0.0, 0.0, 0.0])
+        }
+        "color" => {
+            if !matches!(parameter_type, ParameterType::Vec3 | ParameterType::Vec4) {
+                return Err(wrong_type());
+            }
+            let default = match arguments.as_slice() {
+                [] => initializer.unwrap_or([1.0; 4]),
+                [r, g, b] => [*r, *g, *b, 1.0],
+                [r, g, b, a] => [*r, *g, *b, *a],
+                _ => {
+                    return Err(format!(
+                        "{}: expected @color, @color(r, g, b) or @color(r, g, b, a)",
+                        name
+                    ))
+                }
+            };
+            (Widget::Color, default)
+        }
+        "checkbox" => {
+            if !matches!(
+                parameter_type,
+                ParameterType::Bool | ParameterType::Int | ParameterType::Float
+            ) {
+                return Err(wrong_type());
+            }
+            let default = match arguments.as_slice() {
+                [] => initializer.map_or(0.0, |value| value[0]),
+                [default] => *default,
+                _ => return Err(format!("{}: expected @checkbox or @checkbox(true)", name)),
+            };
+            (Widget::Checkbox, [default, 0.0, 0.0, 0.0])
+        }
+        _ => {
+            return Err(format!(
+                "{}: unknown annotation @{}, expected @slider, @color or @checkbox",
+                name, kind
+            ))
+        }
+    };
+
+    let mut parameter = Parameter {
+        name: String::from(name),
+        parameter_type,
+        widget,
+        default,
+    };
+    parameter.default = parameter.clamp(default);
+
+    Ok(parameter)
+}
+
+// "0, 10, 3.2" or "true"
+fn parse_numbers(arguments: &str) -> Result<Vec<f32>, String> {
+    if arguments.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    arguments
+        .split(',')
+        .map(|argument| match argument.trim() {
+            "true" => Ok(1.0),
+            "false" => Ok(0.0),
+            number => number
+                .parse::<f32>()
+                .map_err(|_| format!("\"{}\" is not a number", number)),
+        })
+        .collect()
+}
+
+// "2.0", "true" or "vec3(1.0, 0.5, 0.0)"
+fn parse_initializer(initializer: &str) -> Result<[f32; 4], String> {
+    let arguments = match initializer.split_once('(') {
+        Some((_, arguments)) => arguments.trim_end().trim_end_matches(')'),
+        None => initializer,
+    };
+
+    let numbers = parse_numbers(arguments)?;
+    let mut value = [0.0, 0.0, 0.0, 1.0];
+
+    match numbers.as_slice() {
+        // vec3(0.5) fills all components
+        [single] => value = [*single, *single, *single, 1.0],
+        _ => {
+            for (component, number) in value.iter_mut().zip(numbers) {
+                *component = number;
+            }
+        }
+    }
+
+    Ok(value)
+}
+
+// values of parameters for one shader or project. values of parameters
+// that disappear while the shader is edited are kept until they come back
+pub struct Parameters {
+    definitions: Vec<Parameter>,
+    values: BTreeMap<String, Vec<f32>>,
+    // None when values must not be saved, like in a replayed session
+    path: Option<PathBuf>,
+}
+
+impl Parameters {
+    pub fn load(shader_path: &Path) -> Result<Parameters, SidecarError> {
+        let path = sidecar_path(shader_path, VALUES_EXTENSION);
+        let values = sidecar::read_or_default(WHAT, &path)?;
+
+        Ok(Parameters {
+            definitions: Vec::new(),
+            values,
+            path: Some(path),
+        })
+    }
+
+    // defaults that are never saved, for a file of values that
+    // can't be read, so it is not overwritten
+    pub fn unsaved() -> Parameters {
+        Parameters {
+            definitions: Vec::new(),
+            values: BTreeMap::new(),
+            path: None,
+        }
+    }
+
+    // values changed from now on stay in memory
+    pub fn stop_saving(&mut self) {
+        self.path = None;
+    }
+
+    pub fn set_definitions(&mut self, definitions: Vec<Parameter>) {
+        self.definitions = definitions;
+    }
+
+    pub fn definitions(&self) -> &[Parameter] {
+        &self.definitions
+    }
+
+    // saved value when it fits the parameter, default otherwise
+    pub fn value(&self, parameter: &Parameter) -> [f32; 4] {
+        let components = parameter.parameter_type.components();
+
+        match self.values.get(&parameter.name) {
+            Some(saved) if saved.len() == components => {
+                let mut value = parameter.default;
+                value[..components].copy_from_slice(saved);
+                parameter.clamp(value)
+            }
+            _ => parameter.default,
+        }
+    }
+
+    pub fn set_value(&mut self, parameter: &Parameter, value: [f32; 4]) {
+        let components = parameter.parameter_type.components();
+        let value = parameter.clamp(value);
+        self.values
+            .insert(parameter.name.clone(), value[..components].to_vec());
+    }
+
+    // current values of all parameters the shader declares
+    pub fn values(&self) -> BTreeMap<String, Vec<f32>> {
+        self.definitions
+            .iter()
+            .map(|parameter| {
+                let components = parameter.parameter_type.components();
+                let value = self.value(parameter);
+                (parameter.name.clone(), value[..components].to_vec())
+            })
+            .collect()
+    }
+
+    // values are taken as they are, value() checks them against
+    // the parameter when it is used
+    pub fn set_values(&mut self, values: &BTreeMap<String, Vec<f32>>) {
+        for (name, value) in values {
+            self.values.insert(name.clone(), value.clone());
+        }
+    }
+
+    pub fn reset(&mut self, parameter: &Parameter) {
+        self.values.remove(&parameter.name);
+    }
+
+    pub fn save(&self) -> Result<(), SidecarError> {
+        match &self.path {
+            Some(path) => sidecar::write(WHAT, path, &self.values),
+            None => Ok(()),
+        }
+    }
+}
+
+// adds parameter values to uniforms made with uniform! macro
+pub struct WithParameters<'b, U> {
+    pub uniforms: &'b U,
+    pub parameters: &'b Parameters,
+}
+
+impl<'b, U: Uniforms> Uniforms for WithParameters<'b, U> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        self.uniforms.visit_values(&mut output);
+
+        for parameter in self.parameters.definitions() {
+            let value = self.parameters.value(parameter);
+            output(&parameter.name, parameter.uniform_value(value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameter(line: &str) -> Parameter {
+        match parse_line(line) {
+            Some(Ok(parameter)) => parameter,
+            other => panic!("{}: {:?}", line, other),
+        }
+    }
+
+    fn error(line: &str) -> String {
+        match parse_line(line) {
+            Some(Err(message)) => message,
+            other => panic!("{}: {:?}", line, other),
+        }
+    }
+
+    #[test]
+    fn annotated_uniforms_are_parameters() {
+        assert_eq!(
+            parameter("uniform float wall_height; // @slider(0, 10, 3.2)"),
+            Parameter {
+                name: String::from("wall_height"),
+                parameter_type: ParameterType::Float,
+                widget: Widget::Slider {
+                    min: 0.0,
+                    max: 10.0
+                },
+                default: [3.2, 0.0, 0.0, 0.0],
+            }
+        );
+        assert_eq!(
+            parameter("uniform int floors = 3;    // @slider(1, 20)"),
+            Parameter {
+                name: String::from("floors"),
+                parameter_type: ParameterType::Int,
+                widget: Widget::Slider {
+                    min: 1.0,
+                    max: 20.0
+                },
+                default: [3.0, 0.0, 0.0, 0.0],
+            }
+        );
+        assert_eq!(
+            parameter("uniform vec3 tint;         // @color(1, 0.5, 0)"),
+            Parameter {
+                name: String::from("tint"),
+                parameter_type: ParameterType::Vec3,
+                widget: Widget::Color,
+                default: [1.0, 0.5, 0.0, 1.0],
+            }
+        );
+        assert_eq!(
+            parameter("uniform bool windows;      // @checkbox(true)"),
+            Parameter {
+                name: String::from("windows"),
+                parameter_type: ParameterType::Bool,
+                widget: Widget::Checkbox,
+                default: [1.0, 0.0, 0.0, 0.0],
+            }
+        );
+    }
+
+    // comments like these are the shader's own
+    #[test]
+    fn other_lines_are_left_alone() {
+        assert_eq!(parse_line("uniform float scale; // @todo tune this"), None);
+        assert_eq!(parse_line("uniform vec3 sun; // see @color below"), None);
+        assert_eq!(parse_line("float height = 1.0; // @slider(0, 10)"), None);
+        assert_eq!(parse_line("uniform float plain;"), None);
+    }
+
+    #[test]
+    fn malformed_annotations_are_errors() {
+        assert_eq!(
+            error("uniform float height; // @slider(10, 0)"),
+            "height: slider minimum 10 is not less than maximum 0"
+        );
+        assert_eq!(
+            error("uniform float height; // @slider(0)"),
+            "height: expected @slider(min, max) or @slider(min, max, default)"
+        );
+        assert_eq!(
+            error("uniform float height; // @slider(0, ten)"),
+            "\"ten\" is not a number"
+        );
+        assert_eq!(
+            error("uniform float height; // @slider(0, 10"),
+            "@slider: missing \")\""
+        );
+        assert_eq!(
+            error("uniform float height; // @color"),
+            "@color can not be used with float height"
+        );
+    }
+}
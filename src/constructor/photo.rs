@@ -0,0 +1,218 @@
+use glium::backend::Facade;
+use glium::draw_parameters::{Blend, BlendingFunction, LinearBlendingFactor};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{MipmapsOption, UncompressedFloatFormat};
+use glium::{DrawParameters, IndexBuffer, Program, Surface, Texture2d, VertexBuffer};
+use serde::{Deserialize, Serialize};
+
+use super::lens::focal_length;
+use super::render::Vertex;
+
+// scene units are meters, focal lengths are in mm
+const MILLIMETERS_PER_UNIT: f32 = 1000.0;
+// iso with exposure 1, shaders draw at this sensitivity
+const BASE_ISO: f32 = 100.0;
+// lens samples turn by golden angle, so any number of them covers the aperture evenly
+const GOLDEN_ANGLE: f32 = 2.399_963;
+// pixel offsets and shutter times of samples come from these low discrepancy sequences
+const PIXEL_STEP: [f32; 2] = [0.754_877_7, 0.569_840_3];
+const SHUTTER_STEP: f32 = 0.618_034;
+
+pub const DEFAULT_FOCUS_DISTANCE: f32 = 10.0;
+
+const RESOLVE_FRAGMENT_SHADER: &str = "#version 140
+
+uniform vec3 iResolution;
+uniform sampler2D samples;
+uniform float exposure;
+
+in vec2 fragCoord;
+out vec4 fragColor;
+
+void main() {
+    vec4 color = texelFetch(samples, ivec2(fragCoord), 0);
+    fragColor = vec4(color.rgb * exposure, color.a);
+}";
+
+// settings of a photographic camera. shaders including lib/camera.glsl
+// get depth of field, every shader gets motion blur and exposure:
+//   photo: (f_stop: Some(2.8), focus_distance: 6.5, autofocus: false, shutter: 0.02, iso: 100.0, ev: 0.0),
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Photo {
+    // None is a pinhole, everything is sharp
+    pub f_stop: Option<f32>,
+    // distance of the sharp plane in front of the camera
+    pub focus_distance: f32,
+    // focus follows the surface at the image center
+    pub autofocus: bool,
+    // seconds the shutter is open around the frame time, 0 stops motion
+    pub shutter: f32,
+    pub iso: f32,
+    // exposure compensation in stops
+    pub ev: f32,
+}
+
+impl Default for Photo {
+    fn default() -> Photo {
+        Photo {
+            f_stop: None,
+            focus_distance: DEFAULT_FOCUS_DISTANCE,
+            autofocus: true,
+            shutter: 0.0,
+            iso: BASE_ISO,
+            ev: 0.0,
+        }
+    }
+}
+
+impl Photo {
+    // brightness the image is multiplied by. shutter and f-stop don't
+    // change it, so blur and depth of field can be tuned on a finished look
+    pub fn exposure(&self) -> f32 {
+        2f32.powf(self.ev) * self.iso / BASE_ISO
+    }
+
+    // lens radius in scene units of a camera with the fov
+    pub fn aperture_radius(&self, fov: f32) -> f32 {
+        match self.f_stop {
+            Some(f_stop) => focal_length(fov) / f_stop / 2.0 / MILLIMETERS_PER_UNIT,
+            None => 0.0,
+        }
+    }
+
+    // autofocus only matters when something can be out of focus
+    pub fn focusing(&self) -> bool {
+        self.autofocus && self.f_stop.is_some()
+    }
+}
+
+// one of the samples a frame is averaged from
+pub struct Sample {
+    // point on the unit disk of the aperture
+    pub lens: [f32; 2],
+    // offset inside the pixel, -0.5 to 0.5
+    pub pixel: [f32; 2],
+    // time from the frame time in parts of shutter time, -0.5 to 0.5
+    pub shutter: f32,
+}
+
+impl Sample {
+    // samples don't change from frame to frame, so video doesn't flicker.
+    // a single sample is the plain pinhole camera
+    pub fn new(index: u32, count: u32) -> Sample {
+        if count <= 1 {
+            return Sample {
+                lens: [0.0, 0.0],
+                pixel: [0.0, 0.0],
+                shutter: 0.0,
+            };
+        }
+
+        let radius = ((index as f32 + 0.5) / count as f32).sqrt();
+        let angle = index as f32 * GOLDEN_ANGLE;
+
+        Sample {
+            lens: [radius * angle.cos(), radius * angle.sin()],
+            pixel: [
+                (index as f32 * PIXEL_STEP[0]).fract() - 0.5,
+                (index as f32 * PIXEL_STEP[1]).fract() - 0.5,
+            ],
+            shutter: (index as f32 * SHUTTER_STEP).fract() - 0.5,
+        }
+    }
+}
+
+// samples are added up in a float image and exposed onto the target
+pub struct Accumulator {
+    program: Program,
+    texture: Option<Texture2d>,
+}
+
+impl Accumulator {
+    pub fn new<F: Facade>(facade: &F, vertex_shader_src: &str) -> Accumulator {
+        let program =
+            Program::from_source(facade, vertex_shader_src, RESOLVE_FRAGMENT_SHADER, None)
+                .expect("can not compile built-in resolve shader");
+
+        Accumulator {
+            program,
+            texture: None,
+        }
+    }
+
+    // cleared image to draw samples to with sample_parameters
+    pub fn start<F: Facade>(
+        &mut self,
+        facade: &F,
+        resolution: (u32, u32),
+    ) -> SimpleFrameBuffer<'_> {
+        let resized = self
+            .texture
+            .as_ref()
+            .is_none_or(|texture| texture.dimensions() != resolution);
+
+        if resized {
+            self.texture = Some(
+                Texture2d::empty_with_format(
+                    facade,
+                    UncompressedFloatFormat::F32F32F32F32,
+                    MipmapsOption::NoMipmap,
+                    resolution.0,
+                    resolution.1,
+                )
+                .unwrap(),
+            );
+        }
+
+        let texture = self.texture.as_ref().unwrap();
+        let mut target = SimpleFrameBuffer::new(facade, texture).unwrap();
+        target.clear_color(0.0, 0.0, 0.0, 0.0);
+        target
+    }
+
+    // every sample adds its part of the average
+    pub fn sample_parameters(count: u32) -> DrawParameters<'static> {
+        let weight = 1.0 / count.max(1) as f32;
+        let add = BlendingFunction::Addition {
+            source: LinearBlendingFactor::ConstantColor,
+            destination: LinearBlendingFactor::One,
+        };
+
+        DrawParameters {
+            blend: Blend {
+                color: add,
+                alpha: add,
+                constant_value: (weight, weight, weight, weight),
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn resolve<S: Surface>(
+        &self,
+        surface: &mut S,
+        vertex_buffer: &VertexBuffer<Vertex>,
+        indices_buffer: &IndexBuffer<u8>,
+        exposure: f32,
+    ) {
+        let texture = match &self.texture {
+            Some(texture) => texture,
+            None => return,
+        };
+        let (width, height) = texture.dimensions();
+
+        surface
+            .draw(
+                vertex_buffer,
+                indices_buffer,
+                &self.program,
+                &uniform! {
+                    iResolution: [width as f32, height as f32, 1.0],
+                    samples: texture.sampled(),
+                    exposure: exposure,
+                },
+                &Default::default(),
+            )
+            .unwrap();
+    }
+}
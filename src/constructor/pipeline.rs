@@ -0,0 +1,574 @@
+use glium::backend::Facade;
+use glium::framebuffer::{MultiOutputFrameBuffer, SimpleFrameBuffer};
+use glium::texture::{MipmapsOption, UncompressedFloatFormat};
+use glium::uniforms::{
+    MagnifySamplerFilter, MinifySamplerFilter, SamplerBehavior, SamplerWrapFunction, UniformValue,
+    Uniforms,
+};
+use glium::{DrawParameters, IndexBuffer, Program, Rect, Surface, Texture2d, VertexBuffer};
+use std::path::PathBuf;
+
+use super::collision::PROBE_POINT;
+use super::parameters::Parameter;
+use super::project::{BufferName, Input, PassDescription, Project};
+use super::render::{create_error_program, create_probe_program, create_program, Vertex};
+use super::scene::{is_scene_file, Scene};
+use super::shader_error::ShaderError;
+use super::shadertoy::{ShaderToyInput, WithShaderToyInput};
+use super::textures::{load_texture, sampler_behavior, Texture};
+
+// optional second output of the image pass, world position of the
+// surface seen in the pixel with w 1, or w 0 where nothing is hit:
+//   layout(location = 1) out vec4 hit_point;
+const HIT_POINT_OUTPUT: &str = "hit_point";
+
+// shadertoy defaults for buffer inputs
+const BUFFER_SAMPLER: SamplerBehavior = SamplerBehavior {
+    wrap_function: (
+        SamplerWrapFunction::Clamp,
+        SamplerWrapFunction::Clamp,
+        SamplerWrapFunction::Clamp,
+    ),
+    minify_filter: MinifySamplerFilter::Linear,
+    magnify_filter: MagnifySamplerFilter::Linear,
+    depth_texture_comparison: None,
+    max_anisotropy: 1,
+};
+
+enum Source {
+    Buffer(BufferName),
+    // textures are loaded once, when the project is loaded
+    Texture(Texture, SamplerBehavior),
+}
+
+// input bound to a sampler uniform, channel is set for iChannelN
+struct Binding {
+    name: String,
+    channel: Option<usize>,
+    source: Source,
+}
+
+struct Pass {
+    program: Program,
+    bindings: Vec<Binding>,
+    parameters: Vec<Parameter>,
+}
+
+impl Pass {
+    fn new<F: Facade>(
+        facade: &F,
+        description: &PassDescription,
+        include_dirs: &[PathBuf],
+    ) -> Result<Pass, ShaderError> {
+        let channels = description
+            .channels
+            .iter()
+            .map(|(channel, input)| (format!("iChannel{}", channel), Some(*channel), input));
+        let samplers = description
+            .samplers
+            .iter()
+            .map(|(name, input)| (name.clone(), None, input));
+
+        let mut bindings = Vec::new();
+
+        for (name, channel, input) in channels.clone().chain(samplers.clone()) {
+            let source = match input {
+                Input::Buffer(buffer) => Source::Buffer(*buffer),
+                Input::Texture { sampler, .. }
+                | Input::Cubemap { sampler, .. }
+                | Input::CubemapCross { sampler, .. }
+                | Input::Volume { sampler, .. } => {
+                    Source::Texture(load_texture(facade, input)?, sampler_behavior(sampler))
+                }
+            };
+
+            bindings.push(Binding {
+                name,
+                channel,
+                source,
+            });
+        }
+
+        let (program, parameters) = create_program(
+            facade,
+            &description.shader,
+            &description.channel_types(),
+            include_dirs,
+        )?;
+
+        // glium refuses to draw with an input of other type than the sampler,
+        // so that is found here. samplers the shader doesn't use are skipped
+        for (name, _, input) in channels.chain(samplers) {
+            let uniform = match program.get_uniform(&name) {
+                Some(uniform) => uniform,
+                None => continue,
+            };
+
+            let channel_type = input.channel_type();
+            if uniform.ty != channel_type.uniform_type() {
+                return Err(ShaderError::Texture {
+                    path: input.path().unwrap_or(&description.shader).clone(),
+                    message: format!(
+                        "{} needs {}, shader declares it as {:?}",
+                        name,
+                        channel_type.glsl_type(),
+                        uniform.ty
+                    ),
+                });
+            }
+        }
+
+        Ok(Pass {
+            program,
+            bindings,
+            parameters,
+        })
+    }
+}
+
+// float render target of a buffer pass. one texture holds the latest
+// finished frame and is read while the other one is drawn, then they swap
+struct Buffer {
+    name: BufferName,
+    pass: Pass,
+    textures: Vec<Texture2d>,
+    latest: usize,
+}
+
+// passes of a project: buffers A to D, then the image pass
+// which draws to the screen or video target
+pub struct Pipeline {
+    buffers: Vec<Buffer>,
+    image: Pass,
+    // image pass evaluating its distance field, see collision::probe_source
+    probe: Option<Program>,
+    // scene files are measured on the cpu instead of the probe
+    scene: Option<Scene>,
+    resolution: (u32, u32),
+}
+
+impl Pipeline {
+    pub fn new<F: Facade>(
+        facade: &F,
+        project: &Project,
+        include_dirs: &[PathBuf],
+    ) -> Result<Pipeline, ShaderError> {
+        let mut buffers = Vec::new();
+
+        for (name, description) in project.buffers.iter() {
+            buffers.push(Buffer {
+                name: *name,
+                pass: Pass::new(facade, description, include_dirs)?,
+                textures: Vec::new(),
+                latest: 0,
+            });
+        }
+
+        let scene = if is_scene_file(&project.image.shader) {
+            Some(Scene::load(&project.image.shader)?)
+        } else {
+            None
+        };
+        let probe = match scene {
+            Some(_) => None,
+            None => create_probe_program(
+                facade,
+                &project.image.shader,
+                &project.image.channel_types(),
+                include_dirs,
+            ),
+        };
+
+        Ok(Pipeline {
+            buffers,
+            image: Pass::new(facade, &project.image, include_dirs)?,
+            probe,
+            scene,
+            resolution: (0, 0),
+        })
+    }
+
+    // single pass with built-in error shader
+    pub fn error<F: Facade>(facade: &F) -> Pipeline {
+        Pipeline {
+            buffers: Vec::new(),
+            image: Pass {
+                program: create_error_program(facade),
+                bindings: Vec::new(),
+                parameters: Vec::new(),
+            },
+            probe: None,
+            scene: None,
+            resolution: (0, 0),
+        }
+    }
+
+    // annotated uniforms of all passes, passes can share a parameter by name
+    pub fn parameters(&self) -> Vec<Parameter> {
+        let passes = self
+            .buffers
+            .iter()
+            .map(|buffer| &buffer.pass)
+            .chain(Some(&self.image));
+
+        let mut parameters: Vec<Parameter> = Vec::new();
+        for parameter in passes.flat_map(|pass| pass.parameters.iter()) {
+            if !parameters.iter().any(|known| known.name == parameter.name) {
+                parameters.push(parameter.clone());
+            }
+        }
+        parameters
+    }
+
+    // buffers start black and are cleared when resolution changes
+    fn resize<F: Facade>(&mut self, facade: &F, resolution: (u32, u32)) -> Result<(), ShaderError> {
+        for buffer in self.buffers.iter_mut() {
+            buffer.textures = (0..2)
+                .map(|_| {
+                    let texture = float_texture(facade, resolution.0, resolution.1)?;
+                    texture.as_surface().clear_color(0.0, 0.0, 0.0, 0.0);
+                    Ok(texture)
+                })
+                .collect::<Result<_, ShaderError>>()?;
+            buffer.latest = 0;
+        }
+
+        self.resolution = resolution;
+        Ok(())
+    }
+
+    pub fn draw<F: Facade, S: Surface, U: Uniforms>(
+        &mut self,
+        facade: &F,
+        surface: &mut S,
+        vertex_buffer: &VertexBuffer<Vertex>,
+        indices_buffer: &IndexBuffer<u8>,
+        uniforms: &U,
+        shader_toy_input: &ShaderToyInput,
+    ) -> Result<(), ShaderError> {
+        self.draw_buffers(
+            facade,
+            vertex_buffer,
+            indices_buffer,
+            uniforms,
+            shader_toy_input,
+        )?;
+        self.draw_image(
+            surface,
+            vertex_buffer,
+            indices_buffer,
+            uniforms,
+            shader_toy_input,
+            &DrawParameters::default(),
+        )
+    }
+
+    // buffers go one frame forward
+    pub fn draw_buffers<F: Facade, U: Uniforms>(
+        &mut self,
+        facade: &F,
+        vertex_buffer: &VertexBuffer<Vertex>,
+        indices_buffer: &IndexBuffer<u8>,
+        uniforms: &U,
+        shader_toy_input: &ShaderToyInput,
+    ) -> Result<(), ShaderError> {
+        let resolution = (
+            shader_toy_input.i_resolution[0] as u32,
+            shader_toy_input.i_resolution[1] as u32,
+        );
+
+        if resolution != self.resolution {
+            self.resize(facade, resolution)?;
+        }
+
+        for index in 0..self.buffers.len() {
+            let buffer = &self.buffers[index];
+            let next = 1 - buffer.latest;
+
+            let mut target = SimpleFrameBuffer::new(facade, &buffer.textures[next])
+                .map_err(|error| ShaderError::Draw(error.to_string()))?;
+
+            draw_pass(
+                &mut target,
+                &buffer.pass.program,
+                &buffer.pass,
+                &self.buffers,
+                vertex_buffer,
+                indices_buffer,
+                uniforms,
+                shader_toy_input,
+                &DrawParameters::default(),
+            )?;
+
+            self.buffers[index].latest = next;
+        }
+
+        Ok(())
+    }
+
+    // image pass alone can run several times a frame, e.g. for samples
+    // of one photo, buffers are drawn once before it
+    pub fn draw_image<S: Surface, U: Uniforms>(
+        &self,
+        surface: &mut S,
+        vertex_buffer: &VertexBuffer<Vertex>,
+        indices_buffer: &IndexBuffer<u8>,
+        uniforms: &U,
+        shader_toy_input: &ShaderToyInput,
+        draw_parameters: &DrawParameters,
+    ) -> Result<(), ShaderError> {
+        draw_pass(
+            surface,
+            &self.image.program,
+            &self.image,
+            &self.buffers,
+            vertex_buffer,
+            indices_buffer,
+            uniforms,
+            shader_toy_input,
+            draw_parameters,
+        )
+    }
+
+    // name of the scene material at the point, None for shaders
+    // and surfaces without material
+    pub fn material_at(&self, point: [f32; 3]) -> Option<&str> {
+        let scene = self.scene.as_ref()?;
+        scene.material_name(scene.material(point))
+    }
+
+    // surface point in the pixel of the frame, counted from bottom
+    // left. only the image pass runs, for the one pixel, buffers stay as they are.
+    // None when the shader doesn't write hit_point or nothing is there
+    pub fn pick<F: Facade, U: Uniforms>(
+        &mut self,
+        facade: &F,
+        vertex_buffer: &VertexBuffer<Vertex>,
+        indices_buffer: &IndexBuffer<u8>,
+        uniforms: &U,
+        shader_toy_input: &ShaderToyInput,
+        pixel: (u32, u32),
+    ) -> Result<Option<[f32; 3]>, ShaderError> {
+        if self
+            .image
+            .program
+            .get_frag_data_location(HIT_POINT_OUTPUT)
+            .is_none()
+        {
+            return Ok(None);
+        }
+
+        // autofocus picks before the first frame is drawn
+        let resolution = (
+            shader_toy_input.i_resolution[0] as u32,
+            shader_toy_input.i_resolution[1] as u32,
+        );
+        if resolution != self.resolution {
+            self.resize(facade, resolution)?;
+        }
+
+        let (width, height) = self.resolution;
+        if pixel.0 >= width || pixel.1 >= height {
+            return Ok(None);
+        }
+
+        let texture = float_texture(facade, width, height)?;
+        let mut target = MultiOutputFrameBuffer::new(facade, [(HIT_POINT_OUTPUT, &texture)])
+            .map_err(|error| ShaderError::Draw(error.to_string()))?;
+        target.clear_color(0.0, 0.0, 0.0, 0.0);
+
+        let rect = Rect {
+            left: pixel.0,
+            bottom: pixel.1,
+            width: 1,
+            height: 1,
+        };
+
+        draw_pass(
+            &mut target,
+            &self.image.program,
+            &self.image,
+            &self.buffers,
+            vertex_buffer,
+            indices_buffer,
+            uniforms,
+            shader_toy_input,
+            &DrawParameters {
+                scissor: Some(rect),
+                ..Default::default()
+            },
+        )?;
+
+        let pixels: Vec<Vec<(f32, f32, f32, f32)>> = texture
+            .main_level()
+            .first_layer()
+            .into_image(None)
+            .unwrap()
+            .raw_read(&rect);
+        let (x, y, z, hit) = pixels[0][0];
+
+        if hit > 0.5 {
+            Ok(Some([x, y, z]))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // distance and gradient of the scene at the points, one pixel of
+    // the probe each. None when the shader has no distance function,
+    // or the probe can't be drawn, the frame reports that error
+    pub fn probe<F: Facade, U: Uniforms>(
+        &self,
+        facade: &F,
+        vertex_buffer: &VertexBuffer<Vertex>,
+        indices_buffer: &IndexBuffer<u8>,
+        uniforms: &U,
+        shader_toy_input: &ShaderToyInput,
+        points: &[[f32; 3]],
+    ) -> Option<Vec<[f32; 4]>> {
+        if let Some(scene) = &self.scene {
+            return Some(
+                points
+                    .iter()
+                    .map(|point| {
+                        let [x, y, z] = scene.gradient(*point);
+                        [scene.distance(*point), x, y, z]
+                    })
+                    .collect(),
+            );
+        }
+
+        let program = self.probe.as_ref()?;
+        if points.is_empty() {
+            return Some(Vec::new());
+        }
+
+        let texture = float_texture(facade, points.len() as u32, 1).ok()?;
+        let mut target = SimpleFrameBuffer::new(facade, &texture).ok()?;
+
+        for (index, point) in points.iter().enumerate() {
+            draw_pass(
+                &mut target,
+                program,
+                &self.image,
+                &self.buffers,
+                vertex_buffer,
+                indices_buffer,
+                &WithPoint {
+                    uniforms,
+                    point: *point,
+                },
+                shader_toy_input,
+                &DrawParameters {
+                    scissor: Some(Rect {
+                        left: index as u32,
+                        bottom: 0,
+                        width: 1,
+                        height: 1,
+                    }),
+                    ..Default::default()
+                },
+            )
+            .ok()?;
+        }
+
+        let pixels: Vec<Vec<(f32, f32, f32, f32)>> = texture
+            .main_level()
+            .first_layer()
+            .into_image(None)
+            .unwrap()
+            .raw_read(&Rect {
+                left: 0,
+                bottom: 0,
+                width: points.len() as u32,
+                height: 1,
+            });
+
+        Some(
+            pixels[0]
+                .iter()
+                .map(|&(distance, x, y, z)| [distance, x, y, z])
+                .collect(),
+        )
+    }
+}
+
+// uniforms with the point a probe evaluates the distance field at
+struct WithPoint<'b, U> {
+    uniforms: &'b U,
+    point: [f32; 3],
+}
+
+impl<'b, U: Uniforms> Uniforms for WithPoint<'b, U> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        self.uniforms.visit_values(&mut output);
+        output(PROBE_POINT, UniformValue::Vec3(self.point));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_pass<S: Surface, U: Uniforms>(
+    surface: &mut S,
+    program: &Program,
+    // bindings of the pass are used with the program
+    pass: &Pass,
+    buffers: &[Buffer],
+    vertex_buffer: &VertexBuffer<Vertex>,
+    indices_buffer: &IndexBuffer<u8>,
+    uniforms: &U,
+    shader_toy_input: &ShaderToyInput,
+    draw_parameters: &DrawParameters,
+) -> Result<(), ShaderError> {
+    let mut shader_toy_input = shader_toy_input.clone();
+    let mut textures = Vec::new();
+
+    for binding in pass.bindings.iter() {
+        let (value, resolution) = match &binding.source {
+            Source::Buffer(name) => {
+                let buffer = buffers
+                    .iter()
+                    .find(|buffer| buffer.name == *name)
+                    .expect("project is validated on load");
+                let texture = &buffer.textures[buffer.latest];
+                let (width, height) = texture.dimensions();
+                (
+                    UniformValue::Texture2d(texture, Some(BUFFER_SAMPLER)),
+                    [width as f32, height as f32, 1.0],
+                )
+            }
+            Source::Texture(texture, sampler) => {
+                (texture.uniform_value(*sampler), texture.resolution())
+            }
+        };
+
+        if let Some(channel) = binding.channel {
+            shader_toy_input.i_channel_resolution[channel] = resolution;
+        }
+        textures.push((binding.name.as_str(), value));
+    }
+
+    surface
+        .draw(
+            vertex_buffer,
+            indices_buffer,
+            program,
+            &WithShaderToyInput {
+                uniforms,
+                shader_toy_input: &shader_toy_input,
+                textures,
+            },
+            draw_parameters,
+        )
+        .map_err(|error| ShaderError::Draw(error.to_string()))
+}
+
+fn float_texture<F: Facade>(facade: &F, width: u32, height: u32) -> Result<Texture2d, ShaderError> {
+    Texture2d::empty_with_format(
+        facade,
+        UncompressedFloatFormat::F32F32F32F32,
+        MipmapsOption::NoMipmap,
+        width,
+        height,
+    )
+    .map_err(|error| ShaderError::Draw(error.to_string()))
+}
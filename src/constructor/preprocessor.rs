@@ -0,0 +1,363 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::shader_error::{ShaderError, ShaderMessage};
+
+// library from shaders/lib, available to every shader even when
+// the files are not on disk next to it
+const BUILTIN_LIBRARY: &[(&str, &str)] = &[
+    ("lib/sdf.glsl", include_str!("../../shaders/lib/sdf.glsl")),
+    (
+        "lib/march.glsl",
+        include_str!("../../shaders/lib/march.glsl"),
+    ),
+    (
+        "lib/camera.glsl",
+        include_str!("../../shaders/lib/camera.glsl"),
+    ),
+];
+
+// where a line of expanded source came from
+pub struct SourceMap {
+    // display names of included sources, first is the shader itself
+    names: Vec<String>,
+    // source index and line number from 1 for every expanded line
+    lines: Vec<(usize, u32)>,
+    // files on disk, built-in library is not listed
+    files: Vec<PathBuf>,
+}
+
+impl SourceMap {
+    // file name and line for line number reported by the compiler
+    pub fn locate(&self, line: u32) -> Option<(&str, u32)> {
+        let index = (line as usize).checked_sub(1)?;
+        let (source, line) = self.lines.get(index)?;
+        Some((&self.names[*source], *line))
+    }
+
+    // display name of the shader itself
+    pub fn name(&self) -> &str {
+        &self.names[0]
+    }
+
+    // names of included sources in order of inclusion
+    pub fn includes(&self) -> &[String] {
+        &self.names[1..]
+    }
+}
+
+pub struct Preprocessed {
+    pub source: String,
+    pub source_map: SourceMap,
+}
+
+enum Include {
+    File(PathBuf),
+    Builtin(&'static str, &'static str),
+}
+
+struct Preprocessor<'a> {
+    include_dirs: &'a [PathBuf],
+    source: String,
+    source_map: SourceMap,
+    // canonical paths of expanded files, a file reached through
+    // different paths like "templates/../lib" is still included once
+    expanded: Vec<PathBuf>,
+}
+
+// expands `#include "file"` lines. file is looked up relative to the including
+// file, then in include directories, then in built-in library. every file is
+// included once, later includes of it are skipped like with include guards
+pub fn preprocess(path: &Path, include_dirs: &[PathBuf]) -> Result<Preprocessed, ShaderError> {
+    let mut preprocessor = Preprocessor::new(include_dirs);
+    preprocessor.expand_file(path)?;
+
+    Ok(Preprocessed {
+        source: preprocessor.source,
+        source_map: preprocessor.source_map,
+    })
+}
+
+// same for source that is not in a file, like generated shaders.
+// includes are looked up in include directories and built-in library
+pub fn preprocess_source(
+    name: &str,
+    text: &str,
+    include_dirs: &[PathBuf],
+) -> Result<Preprocessed, ShaderError> {
+    let mut preprocessor = Preprocessor::new(include_dirs);
+    preprocessor.expand(name, None, text)?;
+
+    Ok(Preprocessed {
+        source: preprocessor.source,
+        source_map: preprocessor.source_map,
+    })
+}
+
+// shader and files it includes, as far as they can be found.
+// they are watched even when the shader has errors
+pub fn included_files(path: &Path, include_dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut preprocessor = Preprocessor::new(include_dirs);
+    let _ = preprocessor.expand_file(path);
+
+    let mut files = preprocessor.source_map.files;
+    if files.is_empty() {
+        files.push(path.to_path_buf());
+    }
+    files
+}
+
+impl<'a> Preprocessor<'a> {
+    fn new(include_dirs: &[PathBuf]) -> Preprocessor<'_> {
+        Preprocessor {
+            include_dirs,
+            source: String::new(),
+            source_map: SourceMap {
+                names: Vec::new(),
+                lines: Vec::new(),
+                files: Vec::new(),
+            },
+            expanded: Vec::new(),
+        }
+    }
+
+    fn expand_file(&mut self, path: &Path) -> Result<(), ShaderError> {
+        let text = fs::read_to_string(path).map_err(|error| ShaderError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        self.expand(&path.display().to_string(), Some(path), &text)
+    }
+
+    fn expand(&mut self, name: &str, path: Option<&Path>, text: &str) -> Result<(), ShaderError> {
+        let source = self.source_map.names.len();
+        self.source_map.names.push(String::from(name));
+        if let Some(path) = path {
+            self.source_map.files.push(path.to_path_buf());
+            self.expanded.push(canonical(path));
+        }
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index as u32 + 1;
+
+            let include = match parse_include(line) {
+                Some(include) => include,
+                None => {
+                    self.push_line(line, source, line_number);
+                    continue;
+                }
+            };
+
+            let error = |message: String| ShaderError::Compile {
+                messages: vec![ShaderMessage {
+                    file: String::from(name),
+                    line: Some(line_number),
+                    message,
+                }],
+            };
+
+            let include = include.map_err(error)?;
+
+            let dir = path.and_then(Path::parent).unwrap_or_else(|| Path::new(""));
+            let found = self
+                .find(dir, include)
+                .ok_or_else(|| error(format!("can not find include file \"{}\"", include)))?;
+
+            // directive stays as an empty line, so the line count doesn't change
+            self.push_line("", source, line_number);
+
+            match found {
+                Include::File(file) => {
+                    if self.expanded.contains(&canonical(&file)) {
+                        continue;
+                    }
+                    let text = fs::read_to_string(&file).map_err(|io_error| {
+                        error(format!("can not read {}: {}", file.display(), io_error))
+                    })?;
+                    self.expand(&file.display().to_string(), Some(&file), &text)?;
+                }
+                Include::Builtin(builtin, text) => {
+                    let name = format!("<built-in {}>", builtin);
+                    if self.source_map.names.contains(&name) {
+                        continue;
+                    }
+                    self.expand(&name, None, text)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push_line(&mut self, line: &str, source: usize, line_number: u32) {
+        self.source.push_str(line);
+        self.source.push('\n');
+        self.source_map.lines.push((source, line_number));
+    }
+
+    fn find(&self, dir: &Path, include: &str) -> Option<Include> {
+        let candidates = Some(dir)
+            .into_iter()
+            .chain(self.include_dirs.iter().map(PathBuf::as_path));
+
+        for dir in candidates {
+            let file = dir.join(include);
+            if file.is_file() {
+                return Some(Include::File(file));
+            }
+        }
+
+        BUILTIN_LIBRARY
+            .iter()
+            .find(|(name, _)| *name == include)
+            .map(|(name, text)| Include::Builtin(name, text))
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+// Some(Ok(file)) for `#include "file"`, Some(Err(..)) for malformed directive
+fn parse_include(line: &str) -> Option<Result<&str, String>> {
+    let directive = line.trim_start().strip_prefix('#')?.trim_start();
+    let rest = directive.strip_prefix("include")?;
+
+    let file = rest
+        .trim()
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .filter(|file| !file.is_empty() && !file.contains('"'));
+
+    Some(file.ok_or_else(|| format!("expected #include \"file\", got \"{}\"", line.trim())))
+}
+
+// files of the tests are written to a directory of their own in temp
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "constructor-preprocessor-{}-{}",
+            test,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+
+        for (name, text) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+
+        dir
+    }
+
+    fn expanded(path: &Path, include_dirs: &[PathBuf]) -> Preprocessed {
+        preprocess(path, include_dirs).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    // next to the including file, then include directories, then built-in library
+    #[test]
+    fn includes_are_looked_up_in_order() {
+        let dir = write_files(
+            "lookup",
+            &[
+                (
+                    "shader/main.frag",
+                    "#include \"a.glsl\"\n#include \"b.glsl\"\n#include \"lib/march.glsl\"\n#include \"lib/sdf.glsl\"\n",
+                ),
+                ("shader/a.glsl", "// a next to the shader\n"),
+                ("include/a.glsl", "// a in include directory\n"),
+                ("include/b.glsl", "// b in include directory\n"),
+                ("include/lib/march.glsl", "// march in include directory\n"),
+            ],
+        );
+        let include_dirs = [dir.join("include")];
+
+        let preprocessed = expanded(&dir.join("shader/main.frag"), &include_dirs);
+
+        let source = &preprocessed.source;
+        assert!(source.contains("// a next to the shader"), "{}", source);
+        assert!(!source.contains("// a in include directory"), "{}", source);
+        assert!(source.contains("// b in include directory"), "{}", source);
+        assert!(
+            source.contains("// march in include directory"),
+            "{}",
+            source
+        );
+        assert!(source.contains("float sd_sphere"), "{}", source);
+        assert_eq!(
+            preprocessed.source_map.includes(),
+            [
+                dir.join("shader/a.glsl").display().to_string(),
+                dir.join("include/b.glsl").display().to_string(),
+                dir.join("include/lib/march.glsl").display().to_string(),
+                String::from("<built-in lib/sdf.glsl>"),
+            ]
+        );
+
+        let missing = preprocess_source("<test>", "#include \"missing.glsl\"", &include_dirs);
+        assert!(missing.is_err());
+    }
+
+    // files including each other, also through paths that differ
+    #[test]
+    fn files_are_included_once() {
+        let dir = write_files(
+            "cycle",
+            &[
+                ("main.frag", "#include \"a.glsl\"\n#include \"a.glsl\"\n"),
+                ("a.glsl", "// a\n#include \"lib/b.glsl\"\n"),
+                (
+                    "lib/b.glsl",
+                    "// b\n#include \"../a.glsl\"\n#include \"../main.frag\"\n",
+                ),
+            ],
+        );
+
+        let preprocessed = expanded(&dir.join("main.frag"), &[]);
+
+        assert_eq!(preprocessed.source.matches("// a").count(), 1);
+        assert_eq!(preprocessed.source.matches("// b").count(), 1);
+        assert_eq!(
+            preprocessed.source_map.includes(),
+            [
+                dir.join("a.glsl").display().to_string(),
+                dir.join("lib/b.glsl").display().to_string(),
+            ]
+        );
+    }
+
+    // lines of the expanded source point back to the files they came from
+    #[test]
+    fn lines_are_located() {
+        let preprocessed = preprocess_source(
+            "<test>",
+            "#version 420\n#include \"lib/sdf.glsl\"\nvoid main() {}\n",
+            &[],
+        )
+        .unwrap_or_else(|error| panic!("{}", error));
+        let source_map = &preprocessed.source_map;
+        let library_lines = BUILTIN_LIBRARY[0].1.lines().count() as u32;
+
+        assert_eq!(source_map.name(), "<test>");
+        assert_eq!(source_map.locate(0), None);
+        assert_eq!(source_map.locate(1), Some(("<test>", 1)));
+        // directive stays as an empty line
+        assert_eq!(source_map.locate(2), Some(("<test>", 2)));
+        assert_eq!(source_map.locate(3), Some(("<built-in lib/sdf.glsl>", 1)));
+        assert_eq!(
+            source_map.locate(2 + library_lines),
+            Some(("<built-in lib/sdf.glsl>", library_lines))
+        );
+        assert_eq!(source_map.locate(3 + library_lines), Some(("<test>", 3)));
+        assert_eq!(source_map.locate(4 + library_lines), None);
+        assert_eq!(
+            preprocessed.source.lines().count() as u32,
+            3 + library_lines
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::lens::Lens;
+use super::parameters::Parameters;
+use super::photo::Photo;
+use super::render::FrameInput;
+use super::sidecar::{self, sidecar_path, SidecarError};
+
+// presets are kept next to the shader in "<shader file>.presets"
+const PRESETS_EXTENSION: &str = "presets";
+
+// everything that makes a look: camera, xyz_change and parameter values.
+// file is a list of them and can be edited by hand, e.g. to rename:
+//   [
+//       (
+//           name: "courtyard",
+//           camera_position: (0.0, 1.0, 0.0),
+//           yaw: 90.0,
+//           pitch: -10.0,
+//           speed: 200.0,
+//           xyz_change: (1.0, 1.0, 1.0),
+//           parameters: {"wall_height": [3.2]},
+//       ),
+//   ]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub camera_position: [f32; 3],
+    // degrees, like --yaw and --pitch
+    pub yaw: f32,
+    pub pitch: f32,
+    pub speed: f32,
+    pub xyz_change: [f32; 3],
+    // presets saved before lens and photo settings get the defaults
+    #[serde(default)]
+    pub lens: Lens,
+    #[serde(default)]
+    pub photo: Photo,
+    #[serde(default)]
+    pub parameters: BTreeMap<String, Vec<f32>>,
+}
+
+impl Preset {
+    pub fn capture(name: String, frame_input: &FrameInput, parameters: &Parameters) -> Preset {
+        Preset {
+            name,
+            camera_position: frame_input.camera_position,
+            yaw: frame_input.last_angle_x.to_degrees(),
+            pitch: frame_input.last_angle_y.to_degrees(),
+            speed: frame_input.camera_speed,
+            xyz_change: frame_input.xyz_change,
+            lens: frame_input.lens,
+            photo: frame_input.photo,
+            parameters: parameters.values(),
+        }
+    }
+
+    // parameters missing in the preset keep their values
+    pub fn apply(&self, frame_input: &mut FrameInput, parameters: &mut Parameters) {
+        frame_input.camera_position = self.camera_position;
+        frame_input.last_angle_x = self.yaw.to_radians();
+        frame_input.last_angle_y = self.pitch.to_radians();
+        frame_input.camera_speed = self.speed;
+        frame_input.xyz_change = self.xyz_change;
+        frame_input.lens = self.lens;
+        frame_input.photo = self.photo;
+        // drag in progress starts again from the new angles
+        frame_input.mouse_button3_first_click = true;
+        frame_input.transition = None;
+
+        parameters.set_values(&self.parameters);
+    }
+}
+
+pub enum PresetError {
+    File(SidecarError),
+    NotFound { path: PathBuf, name: String },
+    Empty { path: PathBuf },
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresetError::File(error) => write!(f, "{}", error),
+            PresetError::NotFound { path, name } => {
+                write!(f, "no preset \"{}\" in {}", name, path.display())
+            }
+            PresetError::Empty { path } => {
+                write!(f, "no presets in {}, F5 saves one", path.display())
+            }
+        }
+    }
+}
+
+impl From<SidecarError> for PresetError {
+    fn from(error: SidecarError) -> PresetError {
+        PresetError::File(error)
+    }
+}
+
+// presets of one shader or project. file is read again on every use,
+// so presets edited by hand are picked up without restart
+pub struct Presets {
+    path: PathBuf,
+    // presets of a replayed session, they are used instead of the file
+    replayed: Option<Vec<Preset>>,
+    // last saved or loaded preset
+    current: Option<usize>,
+}
+
+impl Presets {
+    pub fn new(shader_path: &Path) -> Presets {
+        Presets {
+            path: sidecar_path(shader_path, PRESETS_EXTENSION),
+            replayed: None,
+            current: None,
+        }
+    }
+
+    // presets as they are now and the current one, kept in a recorded session
+    pub fn snapshot(&self) -> Result<(Vec<Preset>, Option<usize>), SidecarError> {
+        Ok((self.read()?, self.current))
+    }
+
+    // from now on presets are the recorded ones, saved ones go to them
+    pub fn replay(&mut self, presets: Vec<Preset>, current: Option<usize>) {
+        self.replayed = Some(presets);
+        self.current = current;
+    }
+
+    // current look becomes a new preset at the end of the list
+    pub fn save(
+        &mut self,
+        frame_input: &FrameInput,
+        parameters: &Parameters,
+    ) -> Result<String, PresetError> {
+        let mut presets = self.read()?;
+
+        let name = (presets.len() + 1..)
+            .map(|number| format!("preset {}", number))
+            .find(|name| !presets.iter().any(|preset| preset.name == *name))
+            .expect("some number is free");
+
+        let index = presets.len();
+        presets.push(Preset::capture(name.clone(), frame_input, parameters));
+        self.write(presets)?;
+        self.current = Some(index);
+
+        match self.replayed {
+            Some(_) => Ok(format!("saved {}", name)),
+            None => Ok(format!("saved {} to {}", name, self.path.display())),
+        }
+    }
+
+    // loads next (step 1) or previous (step -1) preset, wrapping around.
+    // step 0 loads current preset again, dropping changes made after it
+    pub fn cycle(
+        &mut self,
+        step: isize,
+        frame_input: &mut FrameInput,
+        parameters: &mut Parameters,
+    ) -> Result<String, PresetError> {
+        let presets = self.read()?;
+
+        if presets.is_empty() {
+            return Err(PresetError::Empty {
+                path: self.path.clone(),
+            });
+        }
+
+        let count = presets.len() as isize;
+        let index = match self.current {
+            Some(current) => (current as isize + step).rem_euclid(count) as usize,
+            None if step < 0 => presets.len() - 1,
+            None => 0,
+        };
+
+        let preset = &presets[index];
+        preset.apply(frame_input, parameters);
+        parameters.save()?;
+        self.current = Some(index);
+
+        Ok(format!(
+            "loaded {} ({}/{})",
+            preset.name,
+            index + 1,
+            presets.len()
+        ))
+    }
+
+    // preset given on command line
+    pub fn load(
+        &mut self,
+        name: &str,
+        frame_input: &mut FrameInput,
+        parameters: &mut Parameters,
+    ) -> Result<(), PresetError> {
+        let presets = self.read()?;
+
+        let index = presets
+            .iter()
+            .position(|preset| preset.name == name)
+            .ok_or_else(|| PresetError::NotFound {
+                path: self.path.clone(),
+                name: String::from(name),
+            })?;
+
+        presets[index].apply(frame_input, parameters);
+        self.current = Some(index);
+
+        Ok(())
+    }
+
+    fn read(&self) -> Result<Vec<Preset>, SidecarError> {
+        match &self.replayed {
+            Some(presets) => Ok(presets.clone()),
+            None => sidecar::read_or_default("presets", &self.path),
+        }
+    }
+
+    fn write(&mut self, presets: Vec<Preset>) -> Result<(), SidecarError> {
+        match &mut self.replayed {
+            Some(replayed) => {
+                *replayed = presets;
+                Ok(())
+            }
+            None => sidecar::write("presets", &self.path, &presets),
+        }
+    }
+}
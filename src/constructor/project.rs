@@ -0,0 +1,288 @@
+use ron::extensions::Extensions;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::scene::is_scene_file;
+use super::shader_error::ShaderError;
+use super::shadertoy::{ChannelType, CHANNEL_COUNT};
+
+// files with this extension describe passes, any other file is a single shader.
+// scene files end with it too, they are single shaders
+const PROJECT_EXTENSION: &str = "ron";
+
+// offscreen buffers, rendered in this order before the image pass
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+pub enum BufferName {
+    A,
+    B,
+    C,
+    D,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Wrap {
+    Clamp,
+    Repeat,
+    Mirror,
+}
+
+// how a texture is loaded and sampled, every field is optional:
+//   sampler: (filter: Nearest, wrap: Clamp, mipmaps: false, srgb: true, vflip: false)
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default)]
+pub struct SamplerOptions {
+    pub filter: Filter,
+    pub wrap: Wrap,
+    pub mipmaps: bool,
+    // color textures like photos are stored in srgb, data textures are linear
+    pub srgb: bool,
+    // flip rows so image is upright with (0, 0) at bottom left.
+    // by default images and volumes are flipped and cube maps are not
+    pub vflip: Option<bool>,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> SamplerOptions {
+        SamplerOptions {
+            filter: Filter::Linear,
+            wrap: Wrap::Repeat,
+            mipmaps: true,
+            srgb: false,
+            vflip: None,
+        }
+    }
+}
+
+// what a pass sees in iChannelN or in a named sampler
+#[derive(Clone, Debug, Deserialize)]
+pub enum Input {
+    // latest finished frame of the buffer: buffers rendered earlier give
+    // this frame, the pass itself and later buffers give previous frame
+    Buffer(BufferName),
+    // png or jpeg
+    Texture {
+        path: PathBuf,
+        #[serde(default)]
+        sampler: SamplerOptions,
+    },
+    // faces in order +X, -X, +Y, -Y, +Z, -Z
+    Cubemap {
+        faces: [PathBuf; 6],
+        #[serde(default)]
+        sampler: SamplerOptions,
+    },
+    // one image with faces in horizontal (4x3) or vertical (3x4) cross
+    CubemapCross {
+        path: PathBuf,
+        #[serde(default)]
+        sampler: SamplerOptions,
+    },
+    // slices stacked from top to bottom in one image, depth is number
+    // of slices and defaults to image width
+    Volume {
+        path: PathBuf,
+        #[serde(default)]
+        depth: Option<u32>,
+        #[serde(default)]
+        sampler: SamplerOptions,
+    },
+}
+
+impl Input {
+    pub fn channel_type(&self) -> ChannelType {
+        match self {
+            Input::Buffer(_) | Input::Texture { .. } => ChannelType::Texture2d,
+            Input::Cubemap { .. } | Input::CubemapCross { .. } => ChannelType::Cubemap,
+            Input::Volume { .. } => ChannelType::Texture3d,
+        }
+    }
+
+    // file errors about the input point to, buffers have none
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.paths().into_iter().next()
+    }
+
+    fn paths(&self) -> Vec<&PathBuf> {
+        match self {
+            Input::Buffer(_) => Vec::new(),
+            Input::Texture { path, .. } => vec![path],
+            Input::Cubemap { faces, .. } => faces.iter().collect(),
+            Input::CubemapCross { path, .. } => vec![path],
+            Input::Volume { path, .. } => vec![path],
+        }
+    }
+
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        match self {
+            Input::Buffer(_) => Vec::new(),
+            Input::Texture { path, .. } => vec![path],
+            Input::Cubemap { faces, .. } => faces.iter_mut().collect(),
+            Input::CubemapCross { path, .. } => vec![path],
+            Input::Volume { path, .. } => vec![path],
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct PassDescription {
+    pub shader: PathBuf,
+    #[serde(default)]
+    pub channels: BTreeMap<usize, Input>,
+    // inputs bound to samplers declared by the shader under these names
+    #[serde(default)]
+    pub samplers: BTreeMap<String, Input>,
+}
+
+impl PassDescription {
+    fn inputs(&self) -> impl Iterator<Item = &Input> {
+        self.channels.values().chain(self.samplers.values())
+    }
+
+    fn inputs_mut(&mut self) -> impl Iterator<Item = &mut Input> {
+        self.channels.values_mut().chain(self.samplers.values_mut())
+    }
+
+    // channel types for declarations added to shadertoy shaders
+    pub fn channel_types(&self) -> [ChannelType; CHANNEL_COUNT] {
+        let mut types = [ChannelType::Texture2d; CHANNEL_COUNT];
+
+        for (channel, input) in self.channels.iter() {
+            types[*channel] = input.channel_type();
+        }
+
+        types
+    }
+}
+
+// project file, shader and texture paths are relative to it:
+//   (
+//       buffers: {
+//           A: (shader: "buffer_a.frag", channels: { 0: Buffer(A) }),
+//       },
+//       image: (
+//           shader: "image.frag",
+//           channels: { 0: Buffer(A), 1: Texture(path: "site.jpg", sampler: (srgb: true)) },
+//           samplers: { "facade_albedo": Texture(path: "brick.png") },
+//       ),
+//   )
+#[derive(Deserialize)]
+pub struct Project {
+    #[serde(default)]
+    pub buffers: BTreeMap<BufferName, PassDescription>,
+    pub image: PassDescription,
+}
+
+impl Project {
+    pub fn load(path: &Path) -> Result<Project, ShaderError> {
+        if path.extension() != Some(OsStr::new(PROJECT_EXTENSION)) || is_scene_file(path) {
+            return Ok(Project {
+                buffers: BTreeMap::new(),
+                image: PassDescription {
+                    shader: path.to_path_buf(),
+                    channels: BTreeMap::new(),
+                    samplers: BTreeMap::new(),
+                },
+            });
+        }
+
+        let project_error = |message: String| ShaderError::Project {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let source = fs::read_to_string(path).map_err(|error| project_error(error.to_string()))?;
+
+        // optional fields can be written without Some(...)
+        let mut project: Project = ron::Options::default()
+            .with_default_extension(Extensions::IMPLICIT_SOME)
+            .from_str(&source)
+            .map_err(|error| project_error(error.to_string()))?;
+
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        for pass in project.passes_mut() {
+            pass.shader = dir.join(&pass.shader);
+
+            for input in pass.inputs_mut() {
+                for input_path in input.paths_mut() {
+                    *input_path = dir.join(&input_path);
+                }
+            }
+        }
+
+        project.validate().map_err(project_error)?;
+
+        Ok(project)
+    }
+
+    // fragment shaders of all passes
+    pub fn shaders(&self) -> impl Iterator<Item = &Path> {
+        self.passes().map(|pass| pass.shader.as_path())
+    }
+
+    fn passes_mut(&mut self) -> impl Iterator<Item = &mut PassDescription> {
+        self.buffers.values_mut().chain(Some(&mut self.image))
+    }
+
+    fn passes(&self) -> impl Iterator<Item = &PassDescription> {
+        self.buffers.values().chain(Some(&self.image))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for pass in self.passes() {
+            if let Some(channel) = pass
+                .channels
+                .keys()
+                .find(|channel| **channel >= CHANNEL_COUNT)
+            {
+                return Err(format!(
+                    "{}: there is no iChannel{}, channels are 0 to {}",
+                    pass.shader.display(),
+                    channel,
+                    CHANNEL_COUNT - 1
+                ));
+            }
+
+            for input in pass.inputs() {
+                match input {
+                    Input::Buffer(name) if !self.buffers.contains_key(name) => {
+                        return Err(format!(
+                            "{}: reads buffer {:?} which is not declared",
+                            pass.shader.display(),
+                            name
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // files whose change should reload the project
+    pub fn files(&self, path: &Path) -> Vec<PathBuf> {
+        let mut files = vec![path.to_path_buf()];
+
+        for pass in self.passes() {
+            let inputs = pass.inputs().flat_map(Input::paths);
+
+            for file in Some(&pass.shader).into_iter().chain(inputs) {
+                if !files.contains(file) {
+                    files.push(file.clone());
+                }
+            }
+        }
+
+        files
+    }
+}
@@ -1,65 +1,227 @@
+use glium::backend::{Context, Facade};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{MipmapsOption, TextureCreationError, UncompressedFloatFormat};
+use glium::uniforms::MagnifySamplerFilter;
 use glium::Display;
 use glium::IndexBuffer;
 use glium::Program;
 use glium::Surface;
+use glium::Texture2d;
 use glium::VertexBuffer;
 use glm::{cos, sin};
 use image;
-use image::Pixel;
-use rand;
-use rand::Rng;
 use std::f32::consts::PI;
-use std::fs;
-use std::io::Read;
+use std::fmt;
+use std::mem;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::time;
 
-const DIS_WIDTH: f32 = 1792.0;
-const DIS_HIEGHT: f32 = 768.0;
+use super::camera_path::{CameraPath, PathEditor};
+use super::capture::{CaptureError, FrameSink};
+use super::cli::{Options, VideoOptions};
+use super::collision::{
+    probe_source, slide, with_ground, DistanceField, FieldSample, Ground, Walk, CAMERA_RADIUS,
+    WALK_SPEED,
+};
+use super::headless::{create_headless_context, HeadlessError};
+use super::input::{apply_input, InputEvent};
+use super::lens::{image_distance, Lens, LensView};
+use super::orbit::{Orbit, Turntable};
+use super::overlay::{TextOverlay, CHAR_SIZE, ERROR_FRAGMENT_SHADER};
+use super::panel::ParameterPanel;
+use super::parameters::{parse_parameters, Parameter, Parameters, WithParameters};
+use super::photo::{Accumulator, Photo, Sample};
+use super::pipeline::Pipeline;
+use super::preprocessor::{included_files, preprocess, preprocess_source, Preprocessed, SourceMap};
+use super::presets::{PresetError, Presets};
+use super::project::Project;
+use super::scene::{is_scene_file, Scene, SCENE_SHADER_NAME};
+use super::session::{Recorder, Replay, SessionFiles};
+use super::shader_error::ShaderError;
+use super::shadertoy::{
+    date, is_shadertoy_shader, wrap_main_image, ChannelType, ShaderToyInput, ShaderToyMouse,
+    CHANNEL_COUNT,
+};
+use super::sidecar::SidecarError;
+use super::views::{CameraTransition, Views};
+use super::watcher::FileWatcher;
 
 #[derive(Clone, Copy)]
-struct Vertex {
+pub struct Vertex {
     position: [f32; 2],
     coordinates: [f32; 2],
 }
 
 implement_vertex!(Vertex, position, coordinates);
 
-struct ShaderToyInput {
-    i_resolution: [f32; 3],
-    i_time: f32,
-    i_time_delta: f32,
-    i_frame: i32,
-    i_frame_rate: f32,
-    i_mouse: [f32; 4],
-}
-
 struct ShaderInput {
     aspect: f32,
     camera_position: [f32; 3],
     rotation_matrix: [[f32; 3]; 3],
     xyz_change: [f32; 3],
     static_time: f32,
+    fov: f32,
+    image_distance: f32,
+    projection: i32,
+    lens_shift: [f32; 2],
+    view_height: f32,
+    focus_distance: f32,
+    aperture_radius: f32,
+    lens_sample: [f32; 2],
+    pixel_jitter: [f32; 2],
     shader_toy_input: ShaderToyInput,
+    // camera before the lens and how fast it goes,
+    // samples of motion blur move it within the shutter time
+    camera_angles: [f32; 2],
+    lens: Lens,
+    velocity: [f32; 3],
+    angular_velocity: [f32; 2],
+}
+
+impl ShaderInput {
+    // input of one sample of the frame, its time is offset by a part of the
+    // shutter time and the camera goes along its motion by that time
+    fn sample(&self, sample: &Sample, shutter: f32) -> ShaderInput {
+        let offset = sample.shutter * shutter;
+        let camera_position = [
+            self.camera_position[0] + self.velocity[0] * offset,
+            self.camera_position[1] + self.velocity[1] * offset,
+            self.camera_position[2] + self.velocity[2] * offset,
+        ];
+        let angles = [
+            self.camera_angles[0] + self.angular_velocity[0] * offset,
+            self.camera_angles[1] + self.angular_velocity[1] * offset,
+        ];
+        let (rotation_matrix, view) = view_rotation(&self.lens, angles, self.fov);
+
+        let mut shader_toy_input = self.shader_toy_input.clone();
+        shader_toy_input.i_time += offset;
+
+        ShaderInput {
+            camera_position,
+            rotation_matrix,
+            static_time: self.static_time + offset,
+            projection: view.projection,
+            lens_shift: view.shift,
+            lens_sample: sample.lens,
+            pixel_jitter: sample.pixel,
+            shader_toy_input,
+            ..*self
+        }
+    }
+}
+
+// change per second of camera position or angles
+fn speed<const N: usize>(from: [f32; N], to: [f32; N], time: f32) -> [f32; N] {
+    let mut speed = [0.0; N];
+    if time > 0.0 {
+        for ((speed, from), to) in speed.iter_mut().zip(from.iter()).zip(to.iter()) {
+            *speed = (to - from) / time;
+        }
+    }
+    speed
+}
+
+// rotation_matrix of shaders for camera angles seen through the lens
+fn view_rotation(lens: &Lens, angles: [f32; 2], fov: f32) -> ([[f32; 3]; 3], LensView) {
+    let view = lens.view(angles, fov);
+    let [yaw, pitch] = view.angles;
+
+    let rotation_matrix = [
+        [cos(yaw), sin(pitch) * sin(yaw), cos(pitch) * sin(yaw)],
+        [0.0, cos(pitch), -sin(pitch)],
+        [-sin(yaw), sin(pitch) * cos(yaw), cos(pitch) * cos(yaw)],
+    ];
+
+    (rotation_matrix, view)
+}
+
+// window or headless gl context
+pub enum RenderContext {
+    Window(Display),
+    Headless(Rc<Context>),
+}
+
+impl Facade for RenderContext {
+    fn get_context(&self) -> &Rc<Context> {
+        match self {
+            RenderContext::Window(display) => display.get_context(),
+            RenderContext::Headless(context) => context,
+        }
+    }
+}
+
+impl RenderContext {
+    pub fn request_redraw(&self) {
+        if let RenderContext::Window(display) = self {
+            display.gl_window().window().request_redraw();
+        }
+    }
 }
 
 pub struct RenderData {
     pub frame_input: FrameInput,
-    pub display: Display,
-    program: Program,
+    pub display: RenderContext,
+    pipeline: Pipeline,
+    // pipeline from before the last reload, it takes over
+    // when the new one compiles but fails to draw
+    fallback: Option<Pipeline>,
     vertex_buffer: VertexBuffer<Vertex>,
     indices_buffer: IndexBuffer<u8>,
     frame_counter: u32,
-    is_video: bool,
-    max_video_time: f32,
+    video: Option<Video>,
+    // fragment shader or project file
+    project_path: PathBuf,
+    include_dirs: Vec<PathBuf>,
+    shader_watcher: FileWatcher,
+    shader_error: Option<ShaderError>,
+    overlay: TextOverlay,
+    // values of annotated uniforms, saved next to the shader
+    pub parameters: Parameters,
+    pub panel: ParameterPanel,
+    pub presets: Presets,
+    pub views: Views,
+    pub path_editor: PathEditor,
+    // samples of every frame, averaged for depth of field and motion blur
+    samples: u32,
+    accumulator: Accumulator,
+    // short note shown in the window, like a loaded preset name
+    message: Option<(String, time::Instant)>,
+    // window input is written to a session file or comes from one
+    recorder: Option<Recorder>,
+    replay: Option<Replay>,
+}
+
+const MESSAGE_TIME: time::Duration = time::Duration::from_secs(3);
+
+// speed of camera paths and turntables for motion blur is
+// found from where they are this much later, in seconds
+const MOTION_STEP: f32 = 0.01;
+
+struct Video {
+    frames: Range<u32>,
+    sink: FrameSink,
+    // frames are rendered here at exact requested resolution,
+    // window shows scaled copy of it
+    target: Texture2d,
 }
 
 pub struct FrameInput {
     pub mouse_input_x: f32,
     pub mouse_input_y: f32,
+    pub shader_toy_mouse: ShaderToyMouse,
     pub display_width: u32,
     pub display_height: u32,
     pub time: time::SystemTime,
     pub static_time: f32,
+    pub time_step: f32,
+    // video frames and recorded sessions go by fixed time step instead
+    // of wall clock, so the same frame always looks the same
+    pub offline: bool,
+    // averaged over recent frames, like iFrameRate on shadertoy
+    frame_rate: f32,
     pub delta_time: time::SystemTime,
     pub camera_position: [f32; 3],
     pub xyz_change: [f32; 3],
@@ -73,6 +235,8 @@ pub struct FrameInput {
     pub j_pressed: bool,
     pub k_pressed: bool,
     pub l_pressed: bool,
+    pub ctrl_pressed: bool,
+    pub right_button_pressed: bool,
     pub mouse_button3_pressed: bool,
     pub mouse_button3_first_click: bool,
     pub saved_angle_x: f32,
@@ -82,15 +246,156 @@ pub struct FrameInput {
     pub saved_mouse_input_x: f32,
     pub saved_mouse_input_y: f32,
     pub camera_speed: f32,
+    // flight to a recalled view
+    pub transition: Option<CameraTransition>,
+    // vertical field of view in degrees, shaders get it as fov uniform
+    pub fov: f32,
+    // projection and shift of lib/camera.glsl
+    pub lens: Lens,
+    // aperture, focus, shutter and exposure
+    pub photo: Photo,
+    // camera follows the path while it is set: in shader time when
+    // rendering video, from path_time on when it is previewed in the window
+    pub camera_path: Option<CameraPath>,
+    pub path_time: Option<f32>,
+    // orbit camera instead of the fly one while it is set
+    pub orbit: Option<Orbit>,
+    // video camera circles a pivot, see --turntable
+    pub turntable: Option<Turntable>,
+    // camera slides along surfaces of the scene instead of going through
+    pub collision: bool,
+    // person walking on the scene instead of the flying camera
+    pub walking: bool,
+    pub walk: Walk,
+    pub space_pressed: bool,
+    // keys moved the camera by this in the frame, not applied yet
+    movement: [f32; 3],
 }
 
+impl FrameInput {
+    // offscreen image is letterboxed in the window, see draw_preview.
+    // returns scale and offset of the image in window pixels
+    fn letterbox(&self, resolution: (u32, u32)) -> (f32, [f32; 2]) {
+        let scale = (self.display_width as f32 / resolution.0 as f32)
+            .min(self.display_height as f32 / resolution.1 as f32);
+        let offset = [
+            (self.display_width as f32 - resolution.0 as f32 * scale) / 2.0,
+            (self.display_height as f32 - resolution.1 as f32 * scale) / 2.0,
+        ];
+        (scale, offset)
+    }
 
+    // image pixel under the cursor, from bottom left like fragCoord
+    fn image_pixel(&self, resolution: (u32, u32), cursor: [f32; 2]) -> Option<(u32, u32)> {
+        let (scale, offset) = self.letterbox(resolution);
+        let x = (cursor[0] - offset[0]) / scale;
+        let y = resolution.1 as f32 - (cursor[1] - offset[1]) / scale;
 
-impl FrameInput {
-    fn calculate_data(&mut self) -> ShaderInput {
-        let delta = self.delta_time.elapsed().unwrap().as_secs_f32();
+        if x < 0.0 || y < 0.0 || x >= resolution.0 as f32 || y >= resolution.1 as f32 {
+            return None;
+        }
+        Some((x as u32, y as u32))
+    }
 
-        self.static_time += 0.016666667;
+    // applies movement of the keys through the scene and returns how far the
+    // camera went. walking goes level whichever way the camera looks,
+    // scripted cameras go where they are told
+    fn apply_movement(&mut self, field: &mut dyn DistanceField, time_step: f32) -> [f32; 3] {
+        let movement = std::mem::take(&mut self.movement);
+        let before = self.camera_position;
+        let scripted =
+            self.camera_path.is_some() || self.turntable.is_some() || self.transition.is_some();
+
+        if self.walking && !scripted {
+            let mut forward = 0.0;
+            let mut right = 0.0;
+            if self.w_pressed {
+                forward += 1.0;
+            }
+            if self.s_pressed {
+                forward -= 1.0;
+            }
+            if self.d_pressed {
+                right += 1.0;
+            }
+            if self.a_pressed {
+                right -= 1.0;
+            }
+
+            let yaw = self.last_angle_x;
+            let distance = WALK_SPEED * time_step;
+            let walked = [
+                (sin(yaw) * forward + cos(yaw) * right) * distance,
+                0.0,
+                (cos(yaw) * forward - sin(yaw) * right) * distance,
+            ];
+
+            self.camera_position =
+                self.walk
+                    .step(field, before, walked, time_step, self.space_pressed);
+        } else if self.collision && movement != [0.0, 0.0, 0.0] {
+            self.camera_position = slide(field, before, movement, CAMERA_RADIUS);
+        } else {
+            self.camera_position = [
+                before[0] + movement[0],
+                before[1] + movement[1],
+                before[2] + movement[2],
+            ];
+        }
+
+        if self.camera_position[1] < 0.1 {
+            self.camera_position[1] = 0.1
+        };
+
+        let moved = [
+            self.camera_position[0] - before[0],
+            self.camera_position[1] - before[1],
+            self.camera_position[2] - before[2],
+        ];
+
+        // flying in orbit mode takes the pivot along
+        if let Some(orbit) = &mut self.orbit {
+            orbit.move_by(moved);
+        }
+
+        moved
+    }
+
+    // resolution is the size of the image shader draws to,
+    // it differs from window size when rendering offscreen
+    fn calculate_data(&mut self, resolution: (u32, u32), frame_counter: u32) -> ShaderInput {
+        let delta = if self.offline {
+            self.time_step
+        } else {
+            // clock set back gives zero, here and for i_time below
+            self.delta_time.elapsed().unwrap_or_default().as_secs_f32()
+        };
+
+        // frame n is drawn at time n * time_step
+        let static_time = self.static_time;
+        self.static_time += self.time_step;
+
+        let (i_time, i_time_delta, i_date) = if self.offline {
+            let frame_time = self.time + time::Duration::from_secs_f32(static_time);
+            (static_time, self.time_step, date(frame_time))
+        } else {
+            (
+                self.time.elapsed().unwrap_or_default().as_secs_f32(),
+                delta,
+                date(time::SystemTime::now()),
+            )
+        };
+
+        if i_time_delta > 0.0 {
+            self.frame_rate = if self.frame_rate == 0.0 {
+                1.0 / i_time_delta
+            } else {
+                self.frame_rate * 0.9 + 0.1 / i_time_delta
+            };
+        }
+
+        let (scale, offset) = self.letterbox(resolution);
+        let i_mouse = self.shader_toy_mouse.uniform(resolution.1, scale, offset);
 
         let mut movement_vector = [0.0, 0.0, 0.0];
 
@@ -131,6 +436,11 @@ impl FrameInput {
             self.xyz_change[2] -= 0.01;
         };
 
+        let angles_before = [self.last_angle_x, self.last_angle_y];
+        let position_before = self.camera_position;
+        // speed of the camera along a path or turntable, known ahead
+        let mut motion = None;
+
         if self.mouse_button3_pressed {
             if self.mouse_button3_first_click {
                 self.mouse_button3_first_click = false;
@@ -149,19 +459,91 @@ impl FrameInput {
                 .clamp(-PI / 2., PI / 2.);
         }
 
-        let rotation_matrix = [
-            [
-                cos(self.last_angle_x),
-                sin(self.last_angle_y) * sin(self.last_angle_x),
-                cos(self.last_angle_y) * sin(self.last_angle_x),
-            ],
-            [0.0, cos(self.last_angle_y), -sin(self.last_angle_y)],
-            [
-                -sin(self.last_angle_x),
-                sin(self.last_angle_y) * cos(self.last_angle_x),
-                cos(self.last_angle_y) * cos(self.last_angle_x),
-            ],
-        ];
+        if let Some(orbit) = &mut self.orbit {
+            let angles = [self.last_angle_x, self.last_angle_y];
+            self.camera_position = orbit.rotate(self.camera_position, angles_before, angles);
+
+            if self.right_button_pressed {
+                self.camera_position = orbit.pan(
+                    self.camera_position,
+                    [self.mouse_input_x, self.mouse_input_y],
+                    self.lens.view(angles, self.fov).angles,
+                    &self.lens,
+                    self.fov,
+                    self.display_height,
+                );
+            } else {
+                orbit.end_pan();
+            }
+        }
+
+        // moving or turning the camera stops the flight
+        if self.w_pressed
+            || self.s_pressed
+            || self.a_pressed
+            || self.d_pressed
+            || self.mouse_button3_pressed
+            || self.right_button_pressed
+        {
+            self.transition = None;
+        }
+
+        if let Some(transition) = &mut self.transition {
+            let running = transition.step(i_time_delta);
+            self.camera_position = transition.position();
+            let [angle_x, angle_y] = transition.angles();
+            self.last_angle_x = angle_x;
+            self.last_angle_y = angle_y;
+            if !running {
+                self.transition = None;
+            }
+        }
+
+        if let Some(camera_path) = &self.camera_path {
+            let path_time = self.path_time.unwrap_or(static_time);
+            let camera = camera_path.sample(path_time);
+            self.camera_position = camera.position;
+            self.last_angle_x = camera.yaw;
+            self.last_angle_y = camera.pitch;
+            self.fov = camera.fov;
+            movement_vector = [0.0, 0.0, 0.0];
+
+            let ahead = camera_path.sample(path_time + MOTION_STEP);
+            motion = Some((
+                ahead.position,
+                [ahead.yaw, ahead.pitch],
+                camera.position,
+                [camera.yaw, camera.pitch],
+            ));
+
+            // preview in the window stops at the last keyframe
+            if let Some(preview_time) = &mut self.path_time {
+                *preview_time += delta;
+                if path_time > camera_path.end() {
+                    self.camera_path = None;
+                }
+            }
+        }
+
+        if let Some(turntable) = &self.turntable {
+            let (position, yaw) = turntable.camera(static_time);
+            self.camera_position = position;
+            self.last_angle_x = yaw;
+            movement_vector = [0.0, 0.0, 0.0];
+
+            let (ahead_position, ahead_yaw) = turntable.camera(static_time + MOTION_STEP);
+            motion = Some((
+                ahead_position,
+                [ahead_yaw, self.last_angle_y],
+                position,
+                [yaw, self.last_angle_y],
+            ));
+        }
+
+        // two-point and isometric views turn the camera their own way,
+        // keys move it the way it looks
+        let camera_angles = [self.last_angle_x, self.last_angle_y];
+        let (rotation_matrix, view) = view_rotation(&self.lens, camera_angles, self.fov);
 
         let rotated_movement_vector = [
             movement_vector[0] * rotation_matrix[0][0]
@@ -175,63 +557,73 @@ impl FrameInput {
                 + movement_vector[2] * rotation_matrix[2][2],
         ];
 
-        self.camera_position = [
-            self.camera_position[0] + rotated_movement_vector[0],
-            self.camera_position[1] + rotated_movement_vector[1],
-            self.camera_position[2] + rotated_movement_vector[2],
-        ];
-
-        if self.camera_position[1] < 0.1 {
-            self.camera_position[1] = 0.1
+        // keys move the camera after this, through the scene, see apply_movement
+        self.movement = rotated_movement_vector;
+
+        // free camera is as fast as it moved since the last frame,
+        // apply_movement adds the keys
+        let (velocity, angular_velocity) = match motion {
+            Some((ahead, ahead_angles, position, angles)) => (
+                speed(position, ahead, MOTION_STEP),
+                speed(angles, ahead_angles, MOTION_STEP),
+            ),
+            None => (
+                speed(position_before, self.camera_position, delta),
+                speed(angles_before, camera_angles, delta),
+            ),
         };
 
-        let mut planes = [
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-            [0.0f32, 1.0f32, 0.0f32],
-        ];
-
         ShaderInput {
-            aspect: self.display_width as f32 / self.display_height as f32,
+            aspect: resolution.0 as f32 / resolution.1 as f32,
 
             camera_position: self.camera_position,
 
-            rotation_matrix: rotation_matrix,
+            rotation_matrix,
 
             xyz_change: self.xyz_change,
 
-            static_time: self.static_time,
+            static_time,
+
+            fov: self.fov,
+
+            image_distance: image_distance(self.fov),
+
+            projection: view.projection,
+
+            lens_shift: view.shift,
+
+            view_height: self.lens.view_height,
+
+            focus_distance: self.photo.focus_distance,
+
+            aperture_radius: self.photo.aperture_radius(self.fov),
+
+            lens_sample: [0.0, 0.0],
+
+            pixel_jitter: [0.0, 0.0],
+
+            camera_angles,
+
+            lens: self.lens,
+
+            velocity,
+
+            angular_velocity,
 
             shader_toy_input: ShaderToyInput {
-                i_resolution: [self.display_width as f32, self.display_height as f32, 1.0],
-                i_time: self.time.elapsed().unwrap().as_secs_f32(),
-                i_time_delta: delta,
-                i_frame: 0, //                                                             <========== FIX THIS
-                i_frame_rate: 0.0, //                                                      <========== FIX THIS
-                i_mouse: [self.mouse_input_x, self.mouse_input_y, 0.0, 0.0], //             <========== fix W ans Z cooedinates
+                i_time,
+                i_time_delta,
+                i_frame: frame_counter as i32,
+                i_frame_rate: self.frame_rate,
+                i_mouse,
+                i_date,
+                ..ShaderToyInput::new(resolution)
             },
         }
     }
 }
 
-fn create_buffers(display: &Display) -> (VertexBuffer<Vertex>, IndexBuffer<u8>) {
+fn create_buffers<F: Facade>(display: &F) -> (VertexBuffer<Vertex>, IndexBuffer<u8>) {
     let shape = vec![
         Vertex {
             position: [-1.0, 1.0],
@@ -256,66 +648,14 @@ fn create_buffers(display: &Display) -> (VertexBuffer<Vertex>, IndexBuffer<u8>)
     let indices_buffer = glium::index::IndexBuffer::new(
         display,
         glium::index::PrimitiveType::TrianglesList,
-        &vec![0u8, 1u8, 2u8, 1u8, 3u8, 2u8],
+        &[0u8, 1u8, 2u8, 1u8, 3u8, 2u8],
     )
     .unwrap();
 
     (vertex_buffer, indices_buffer)
 }
 
-use std::env;
-
-fn create_shaders() -> (String, String) {
-    let mut args: Vec<String> = env::args().collect();
-
-    let mut fragment_file: fs::File;
-
-    if args.len() > 1 {
-        let open_result = fs::File::open(args[1].as_str());
-
-        match open_result {
-            Ok(file) => fragment_file = file,
-
-            Err(_) => {
-                args[1] = String::from("shaders/") + &args[1];
-                fragment_file = fs::File::open(args[1].as_str()).unwrap();
-            }
-        }
-    } else {
-        let open_result = fs::File::open("fragment_shader.frag");
-
-        match open_result {
-            Ok(file) => fragment_file = file,
-            Err(_) => {
-                fragment_file = fs::File::open("shaders/fragment_shader.frag").unwrap();
-            }
-        }
-    }
-    /*
-    let open_file = fs::File::open("vertex_shader.vert");
-    let mut vertex_file: fs::File;
-    match open_file {
-        Ok(file) =>
-        {
-            vertex_file = file;
-        },
-        Err(_) =>
-        {
-            vertex_file = fs::File::open("shaders/vertex_shader.vert").unwrap();
-        },
-    }
-
-    let mut vertex_shader_src = String::new();
-    */
-    let mut fragment_shader_src = String::new();
-
-    //vertex_file.read_to_string(&mut vertex_shader_src).unwrap();
-    fragment_file
-        .read_to_string(&mut fragment_shader_src)
-        .unwrap();
-
-    let vertex_shader_src = String::from(
-        "#version 140
+const VERTEX_SHADER_SRC: &str = "#version 140
 
     uniform vec3 iResolution;
     
@@ -326,190 +666,314 @@ fn create_shaders() -> (String, String) {
     void main() {
         fragCoord = coordinates * iResolution.xy;
         gl_Position = vec4(position, 0.0, 1.0);
-    }",
-    );
+    }";
+
+fn create_shaders(
+    fragment_path: &Path,
+    channels: &[ChannelType; CHANNEL_COUNT],
+    include_dirs: &[PathBuf],
+) -> Result<(String, String, SourceMap, Vec<Parameter>), ShaderError> {
+    if is_scene_file(fragment_path) {
+        return create_scene_shaders(&Scene::load(fragment_path)?, channels, include_dirs);
+    }
 
-    /*
-    let mut fragment_shader_src = String::from("#version 420
+    shaders_from(preprocess(fragment_path, include_dirs)?, channels)
+}
 
-    uniform float aspect;
-    uniform vec3 camera_position;
-    uniform mat3 rotation_matrix;
-    uniform vec3 iResolution;
-    uniform float iTime;
-    uniform float iTimeDelta;
-    uniform int iFrame;
-    uniform float iFrameRate;
-    uniform vec4 iMouse;
+// scene built from primitives drawn by the shared ray marching template
+pub fn create_scene_shaders(
+    scene: &Scene,
+    channels: &[ChannelType; CHANNEL_COUNT],
+    include_dirs: &[PathBuf],
+) -> Result<(String, String, SourceMap, Vec<Parameter>), ShaderError> {
+    let preprocessed = preprocess_source(SCENE_SHADER_NAME, &scene.shader_source(), include_dirs)?;
+    shaders_from(preprocessed, channels)
+}
 
-    #define time iTime
-    #define MAX_STEPS 150
-    #define MIN_DIST 0.001
-    #define MAX_DIST 200.
-    in vec2 fragCoord;
-    out vec4 fragColor;
+fn shaders_from(
+    preprocessed: Preprocessed,
+    channels: &[ChannelType; CHANNEL_COUNT],
+) -> Result<(String, String, SourceMap, Vec<Parameter>), ShaderError> {
+    let Preprocessed {
+        source: mut fragment_shader_src,
+        source_map,
+    } = preprocessed;
 
+    let parameters = parse_parameters(&fragment_shader_src, &source_map)?;
 
-    mat2 rotate(float angle) {
-        //angle *= 0.017453;
-        float c = cos(angle);
-        float s = sin(angle);
-        return mat2(c, -s, s, c);
+    // shaders pasted from shadertoy get declarations and main() around mainImage
+    if is_shadertoy_shader(&fragment_shader_src) {
+        fragment_shader_src = wrap_main_image(&fragment_shader_src, channels);
     }
 
-    float sd_sphere(vec3 p, float radius) {
-        return length(p) - radius;
-    }
+    let vertex_shader_src = String::from(VERTEX_SHADER_SRC);
 
-    float sd_box(vec3 p, vec3 b) {
-        vec3 d = abs(p) - b;
-        return min(max(d.x,max(d.y,d.z)),0.0) + length(max(d,0.0));
-    }
+    Ok((
+        vertex_shader_src,
+        fragment_shader_src,
+        source_map,
+        parameters,
+    ))
+}
 
-    float sd_torus(vec3 p, float radius1, float radius2) {
-        float x = length(p.xz) - radius1;
-        return length(vec2(x, p.y)) - radius2;
-    }
+// channels tell which sampler types to declare in shadertoy shaders,
+// include_dirs are searched for #include files.
+// annotated uniforms of the shader are returned with the program
+pub fn create_program<F: Facade>(
+    display: &F,
+    fragment_path: &Path,
+    channels: &[ChannelType; CHANNEL_COUNT],
+    include_dirs: &[PathBuf],
+) -> Result<(Program, Vec<Parameter>), ShaderError> {
+    let (vertex_shader_src, fragment_shader_src, source_map, parameters) =
+        create_shaders(fragment_path, channels, include_dirs)?;
 
-    float sd_capsule(vec3 p, vec3 b, float radius) {
+    let program = glium::Program::from_source(
+        display,
+        vertex_shader_src.as_str(),
+        fragment_shader_src.as_str(),
+        None,
+    )
+    .map_err(|error| ShaderError::from_program_error(error, &source_map))?;
 
-        float d = dot(b, p) / dot(b, b);
-        if (d <= 0.0) return length(p) - radius;
-        if (d >= 1.0) return length(p - b) - radius;
-        return length((b * d) - p) - radius;
-    }
+    Ok((program, parameters))
+}
 
-    float sd_inf_cylinder(vec3 p, float radius) {
-        return length(p.xz) - radius;
-    }
+// program of the image pass evaluating its distance field, for collision.
+// None when there is no distance function or it doesn't build as a probe
+pub fn create_probe_program<F: Facade>(
+    display: &F,
+    fragment_path: &Path,
+    channels: &[ChannelType; CHANNEL_COUNT],
+    include_dirs: &[PathBuf],
+) -> Option<Program> {
+    let (vertex_shader_src, fragment_shader_src, _, _) =
+        create_shaders(fragment_path, channels, include_dirs).ok()?;
+    let probe_src = probe_source(&fragment_shader_src)?;
+
+    glium::Program::from_source(
+        display,
+        vertex_shader_src.as_str(),
+        probe_src.as_str(),
+        None,
+    )
+    .ok()
+}
 
-    float map(vec3 p) {
-        p -= vec3(-0.5, 0.0, 0.7);
-        vec3 point = p - vec3(0., 1., 2.);
-        point.xz *= rotate(iTime);
-        //point.x *= 4.;
-        float d = sd_box(point, vec3(1., 1.4, 1.));
-        d = max(d, -sd_sphere(p - vec3(0., 3., 1.), 1.3));
-        d = max(d, -sd_torus(p - vec3(0., 0., 3.), 2.3, 1.));
-        d = min(d, sd_sphere(p - vec3(1.5, 0.4, 1.6), 0.9));
-        return d;
-    }
+pub fn create_error_program<F: Facade>(display: &F) -> Program {
+    glium::Program::from_source(display, VERTEX_SHADER_SRC, ERROR_FRAGMENT_SHADER, None)
+        .expect("can not compile built-in error shader")
+}
 
-    vec3 get_normal(vec3 p) {
-        vec2 e = vec2(0.001, -0.001);
-        vec3 a = p + e.yxx;
-        vec3 b = p + e.xyx;
-        vec3 c = p + e.xxy;
-        vec3 d = p + e.yyy;
+fn create_context(width: u32, height: u32) -> (Display, glium::glutin::event_loop::EventLoop<()>) {
+    // 1. The **winit::EventsLoop** for handling events.
+    let events_loop = glium::glutin::event_loop::EventLoop::new();
 
-        float fa = map(a);
-        float fb = map(b);
-        float fc = map(c);
-        float fd = map(d);
+    // 2. Parameters for building the Window.
+    let wb = glium::glutin::window::WindowBuilder::new()
+        .with_inner_size(glium::glutin::dpi::LogicalSize::new(width, height))
+        .with_title("Constructor");
 
-        return normalize(
-            e.yxx * fa +
-            e.xyx * fb +
-            e.xxy * fc +
-            e.yyy * fd
-        );
-    }
+    // 3. Parameters for building the OpenGL context.
+    let cb = glium::glutin::ContextBuilder::new()
+        .with_hardware_acceleration(Some(true))
+        .with_vsync(true);
 
-    vec2 ray_march(vec3 ray_origin, vec3 ray_direction) {
-        vec3 color = vec3(0, 0, 0);
-        float total_distance = 0.;
+    // 4. Build the Display with the given window and OpenGL context parameters and register the
+    //    window with the events_loop.
+    let display = glium::Display::new(wb, cb, &events_loop).unwrap();
 
-        int i = 0;
-        for (; i < MAX_STEPS; i++) {
-            float d = map(ray_origin);
-            total_distance += d;
+    (display, events_loop)
+}
 
-            if (d < 0.) {
-                color.z = 1.;
-                return vec2(total_distance, float(i));
-            }
-            if (d < MIN_DIST) {
-                color.x = 1.;
-                return vec2(total_distance, float(i));
-            }
-            if (total_distance > MAX_DIST) {
-                color.y = 1.;
-                return vec2(total_distance, float(i));
-            }
+pub enum StartError {
+    Capture(CaptureError),
+    Headless(HeadlessError),
+    Shader(ShaderError),
+    Preset(PresetError),
+    // camera path or session
+    File(SidecarError),
+    VideoTarget {
+        resolution: (u32, u32),
+        error: TextureCreationError,
+    },
+}
 
-            ray_origin += ray_direction * d;
+impl fmt::Display for StartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartError::Capture(error) => write!(f, "{}", error),
+            StartError::Headless(error) => write!(f, "{}", error),
+            StartError::Shader(error) => write!(f, "{}", error),
+            StartError::Preset(error) => write!(f, "{}", error),
+            StartError::File(error) => write!(f, "{}", error),
+            StartError::VideoTarget { resolution, error } => write!(
+                f,
+                "can not create {}x{} video frame: {}",
+                resolution.0, resolution.1, error
+            ),
         }
-        //color.z = 1.;
-        return vec2(total_distance, float(i));
     }
+}
 
-    void main() {
-        vec2 uv = (fragCoord / iResolution.xy - 0.5) * 2.;
-        uv.x *= aspect;
+impl From<CaptureError> for StartError {
+    fn from(error: CaptureError) -> StartError {
+        StartError::Capture(error)
+    }
+}
+
+impl From<HeadlessError> for StartError {
+    fn from(error: HeadlessError) -> StartError {
+        StartError::Headless(error)
+    }
+}
+
+impl From<SidecarError> for StartError {
+    fn from(error: SidecarError) -> StartError {
+        StartError::File(error)
+    }
+}
+
+impl From<PresetError> for StartError {
+    fn from(error: PresetError) -> StartError {
+        StartError::Preset(error)
+    }
+}
 
-        vec3 ray_direction = normalize(vec3(uv, 1.));
-        ray_direction *= rotation_matrix;
+pub fn create_render_data_and_eventloop(
+    options: Options,
+) -> Result<(RenderData, glium::glutin::event_loop::EventLoop<()>), StartError> {
+    // output is checked before the window is opened
+    let sink = create_sink(&options.video)?;
 
-        vec2 dist_and_color = ray_march(camera_position, ray_direction);
+    let (display, events_loop) = create_context(options.width, options.height);
 
-        vec3 normal = get_normal(dist_and_color.x * ray_direction + camera_position);
+    let render_data = create_render_data(RenderContext::Window(display), options, sink)?;
 
-        float shade = dot(normal, normalize(vec3(0.2, 1, 0.5)));
+    if let Some(error) = &render_data.shader_error {
+        eprintln!("{}", error);
+    }
 
-        fragColor = vec4(vec3(dist_and_color.y / MAX_STEPS. * 2, .0, .0), 1.);
-    }");
-    */
-    (vertex_shader_src, fragment_shader_src)
+    Ok((render_data, events_loop))
 }
 
-fn create_context() -> (Display, glium::glutin::event_loop::EventLoop<()>) {
-    // 1. The **winit::EventsLoop** for handling events.
-    let events_loop = glium::glutin::event_loop::EventLoop::new();
+// renders video without window, works on machines without display server
+pub fn create_headless_render_data(options: Options) -> Result<RenderData, StartError> {
+    let sink = create_sink(&options.video)?;
 
-    // 2. Parameters for building the Window.
-    let wb = glium::glutin::window::WindowBuilder::new()
-        .with_inner_size(glium::glutin::dpi::LogicalSize::new(DIS_WIDTH, DIS_HIEGHT))
-        .with_title("Constructor");
+    let resolution = match &options.video {
+        Some(video) => video.resolution,
+        None => (options.width, options.height),
+    };
 
-    // 3. Parameters for building the OpenGL context.
-    let cb = glium::glutin::ContextBuilder::new()
-        .with_hardware_acceleration(Some(true))
-        .with_vsync(true);
+    let context = create_headless_context(resolution)?;
 
-    // 4. Build the Display with the given window and OpenGL context parameters and register the
-    //    window with the events_loop.
-    let display = glium::Display::new(wb, cb, &events_loop).unwrap();
+    let mut render_data = create_render_data(RenderContext::Headless(context), options, sink)?;
 
-    (display, events_loop)
+    // nobody would see the error shader
+    match render_data.shader_error.take() {
+        Some(error) => Err(StartError::Shader(error)),
+        None => Ok(render_data),
+    }
 }
 
-pub fn create_render_data_and_eventloop() -> (RenderData, glium::glutin::event_loop::EventLoop<()>)
-{
-    let (display, events_loop) = create_context();
+fn create_sink(video: &Option<VideoOptions>) -> Result<Option<FrameSink>, CaptureError> {
+    match video {
+        Some(video) => FrameSink::new(video.capture.clone(), video.fps).map(Some),
+        None => Ok(None),
+    }
+}
 
-    let (vertex_buffer, indices_buffer) = create_buffers(&display);
+fn create_render_data(
+    context: RenderContext,
+    options: Options,
+    sink: Option<FrameSink>,
+) -> Result<RenderData, StartError> {
+    let (time_step, video) = match (options.video, sink) {
+        (Some(video), Some(sink)) => {
+            let (width, height) = video.resolution;
+            // 16 bit target keeps precision for 16 bit png output
+            let target = Texture2d::empty_with_format(
+                &context,
+                UncompressedFloatFormat::U16U16U16U16,
+                MipmapsOption::NoMipmap,
+                width,
+                height,
+            )
+            .map_err(|error| StartError::VideoTarget {
+                resolution: (width, height),
+                error,
+            })?;
+
+            (
+                1.0 / video.fps,
+                Some(Video {
+                    frames: video.frames,
+                    sink,
+                    target,
+                }),
+            )
+        }
+        // without video shader time follows 60 fps display
+        _ => (1.0 / 60.0, None),
+    };
 
-    let (vertex_shader_src, fragment_shader_src) = create_shaders();
+    let (vertex_buffer, indices_buffer) = create_buffers(&context);
 
-    let program = glium::Program::from_source(
-        &display,
-        vertex_shader_src.as_str(),
-        fragment_shader_src.as_str(),
-        None,
-    )
-    .unwrap();
+    let project_path = options.shader;
+    let include_dirs = options.include_dirs;
+    let mut shader_watcher = FileWatcher::new(vec![project_path.clone()]);
+    // values that can't be read are left in their file and shown in the window
+    let (mut parameters, parameters_error) = match Parameters::load(&project_path) {
+        Ok(parameters) => (parameters, None),
+        Err(error) => (Parameters::unsaved(), Some(error)),
+    };
+    let mut presets = Presets::new(&project_path);
+    let mut views = Views::new(&project_path);
+    let mut path_editor = PathEditor::new(&project_path);
+    let camera_path = match &options.camera_path {
+        Some(path) => Some(CameraPath::load(path)?),
+        None => None,
+    };
+
+    // app stays alive with broken shader, error is shown in the window
+    // and the shader is rebuilt as soon as the file is fixed
+    let (pipeline, shader_error) = match load_pipeline(
+        &context,
+        &project_path,
+        &include_dirs,
+        &mut shader_watcher,
+    ) {
+        Ok(pipeline) => {
+            parameters.set_definitions(pipeline.parameters());
+            (pipeline, None)
+        }
+        Err(error) => (Pipeline::error(&context), Some(error)),
+    };
+
+    let overlay = TextOverlay::new(&context, VERTEX_SHADER_SRC);
+    let accumulator = Accumulator::new(&context, VERTEX_SHADER_SRC);
 
-    let (display_width, display_height) = display.get_framebuffer_dimensions();
+    let (display_width, display_height) = context.get_context().get_framebuffer_dimensions();
 
-    let frame_input = FrameInput {
+    let mut panel = ParameterPanel::new(&context, display_width);
+
+    // video can start in the middle, time goes as if previous frames were rendered
+    let first_frame = video.as_ref().map_or(0, |video| video.frames.start);
+
+    let mut frame_input = FrameInput {
         mouse_input_x: 0.0,
         mouse_input_y: 0.0,
-        display_width: display_width,
-        display_height: display_height,
+        shader_toy_mouse: ShaderToyMouse::default(),
+        display_width,
+        display_height,
         time: time::SystemTime::now(),
-        static_time: 0.0,
+        static_time: first_frame as f32 * time_step,
+        time_step,
+        offline: video.is_some() || options.record.is_some(),
+        frame_rate: 0.0,
         delta_time: time::SystemTime::now(),
-        camera_position: [0.0, 1.0, 0.0],
+        camera_position: options.camera_position,
         xyz_change: [1.0, 1.0, 1.0],
         w_pressed: false,
         s_pressed: false,
@@ -521,110 +985,714 @@ pub fn create_render_data_and_eventloop() -> (RenderData, glium::glutin::event_l
         j_pressed: false,
         k_pressed: false,
         l_pressed: false,
+        ctrl_pressed: false,
+        right_button_pressed: false,
         mouse_button3_pressed: false,
         mouse_button3_first_click: true,
         saved_angle_x: 0.0,
         saved_angle_y: 0.0,
-        last_angle_x: 0.0,
-        last_angle_y: 0.0,
+        last_angle_x: options.yaw,
+        last_angle_y: options.pitch,
         saved_mouse_input_x: 0.0,
         saved_mouse_input_y: 0.0,
-        camera_speed: 200.,
+        camera_speed: options.speed,
+        transition: None,
+        fov: options.fov,
+        lens: options.lens,
+        photo: options.photo,
+        camera_path,
+        path_time: None,
+        orbit: None,
+        turntable: None,
+        collision: options.collision,
+        walking: options.walk.enabled,
+        walk: Walk::new(options.walk.eye_height, options.walk.step_height),
+        space_pressed: false,
+        movement: [0.0, 0.0, 0.0],
     };
 
-    let args: Vec<String> = env::args().collect();
+    // preset given on command line wins over camera options
+    if let Some(name) = &options.preset {
+        presets.load(name, &mut frame_input, &mut parameters)?;
+    }
+
+    if let Some(turntable) = &options.turntable {
+        let turntable = Turntable::new(
+            &frame_input,
+            turntable.degrees,
+            turntable.duration,
+            turntable.pivot,
+        );
+        frame_input.turntable = Some(turntable);
+    }
 
-    let is_video = if args.len() > 2 {
-        args[2] == "-v"
-    } else {
-        false
+    // replay starts where recording did and leaves files of the shader alone
+    let mut samples = options.samples;
+    let replay = options.replay.map(|session| {
+        session.apply_start(&mut frame_input, &mut parameters, &mut samples);
+        parameters.stop_saving();
+        session
+            .files
+            .replay(&mut presets, &mut views, &mut path_editor);
+        panel.resize(frame_input.display_width);
+        frame_input.static_time = 0.0;
+        Replay::new(session)
+    });
+
+    let recorder = match options.record {
+        Some(path) => Some(Recorder::new(
+            path,
+            &project_path,
+            &frame_input,
+            &parameters,
+            samples,
+            SessionFiles::snapshot(&presets, &views, &path_editor)?,
+        )?),
+        None => None,
     };
 
-    let max_video_time = if args.len() > 3 {
-        let result = args[3].parse::<f32>().unwrap();
-        result
-    } else {
-        0.0f32
+    let mut render_data = RenderData {
+        display: context,
+        pipeline,
+        fallback: None,
+        vertex_buffer,
+        indices_buffer,
+        frame_input,
+        frame_counter: first_frame,
+        video,
+        shader_watcher,
+        project_path,
+        include_dirs,
+        shader_error,
+        overlay,
+        parameters,
+        panel,
+        presets,
+        views,
+        path_editor,
+        samples,
+        accumulator,
+        message: None,
+        recorder,
+        replay,
     };
 
-    (
-        RenderData {
-            display: display,
-            program: program,
-            vertex_buffer: vertex_buffer,
-            indices_buffer: indices_buffer,
-            frame_input: frame_input,
-            frame_counter: 0u32,
-            is_video: is_video,
-            max_video_time: max_video_time,
-        },
-        events_loop,
-    )
+    if let Some(turntable) = &render_data.frame_input.turntable {
+        let [x, y, z] = turntable.pivot();
+        show_message(
+            &mut render_data,
+            format!("turntable around {}, {}, {}", x, y, z),
+        );
+    }
+
+    // shown after the turntable pivot, so it stays on screen
+    if let Some(error) = parameters_error {
+        show_message(
+            &mut render_data,
+            format!("{}, changed values are not saved", error),
+        );
+    }
+
+    // input before the first frame of the video changes how it looks,
+    // so replay goes through those frames without drawing them
+    if render_data.replay.is_some() {
+        render_data.frame_counter = 0;
+        for _ in 0..first_frame {
+            let resolution = render_resolution(&render_data);
+            take_replay_events(&mut render_data);
+            frame_shader_input(&mut render_data, resolution);
+            render_data.frame_counter += 1;
+        }
+    }
+
+    Ok(render_data)
 }
 
-pub fn render_frame(render_data: &mut RenderData) -> bool {
-    let mut frame = render_data.display.draw();
+// input from the window. while a session is replayed the window only shows it
+pub fn handle_input(render_data: &mut RenderData, event: InputEvent) {
+    if render_data.replay.is_some() {
+        return;
+    }
 
-    let shader_input = render_data.frame_input.calculate_data();
+    if let Some(recorder) = &mut render_data.recorder {
+        recorder.record(render_data.frame_counter, &event);
+    }
 
-    frame
-        .draw(
-            &render_data.vertex_buffer,
-            &render_data.indices_buffer,
-            &render_data.program,
-            &uniform! {
-                aspect: shader_input.aspect,
-                camera_position: shader_input.camera_position,
-                rotation_matrix: shader_input.rotation_matrix,
-                xyz_change: shader_input.xyz_change,
-                iResolution: shader_input.shader_toy_input.i_resolution,
-                iTime: shader_input.shader_toy_input.i_time,
-                iTimeDelta: shader_input.shader_toy_input.i_time_delta,
-                iFrame: shader_input.shader_toy_input.i_frame,
-                iFrameRate: shader_input.shader_toy_input.i_frame_rate,
-                iMouse: shader_input.shader_toy_input.i_mouse,
-                iStaticTime: shader_input.static_time,
+    apply_input(render_data, &event);
+}
 
-            },
-            &glium::draw_parameters::DrawParameters::default(),
-        )
-        .unwrap();
+// writes recorded session, called when the window closes
+pub fn finish_session(render_data: &mut RenderData) {
+    if let Some(recorder) = &mut render_data.recorder {
+        match recorder.finish(render_data.frame_counter) {
+            Ok(message) => eprintln!("{}", message),
+            Err(error) => eprintln!("{}", error),
+        }
+    }
+}
 
-    frame.finish().unwrap();
+fn take_replay_events(render_data: &mut RenderData) {
+    let events = match &mut render_data.replay {
+        Some(replay) => replay.take_events(render_data.frame_counter),
+        None => return,
+    };
+
+    for event in events.iter() {
+        apply_input(render_data, event);
+    }
+}
 
-    if render_data.is_video {
-        let frame_texture: Vec<Vec<(u8, u8, u8, u8)>> =
-            render_data.display.read_front_buffer().unwrap();
-        // println!("{}, {}", frame_texture.len(), frame_texture[0].len());
-        let image_buffer = image::ImageBuffer::from_fn(
+fn render_resolution(render_data: &RenderData) -> (u32, u32) {
+    match &render_data.video {
+        Some(video) => video.target.dimensions(),
+        None => (
             render_data.frame_input.display_width,
             render_data.frame_input.display_height,
-            |x, y| {
-                let (r, g, b, a) = frame_texture[y as usize][x as usize];
-                image::Rgba([r, g, b, a])
+        ),
+    }
+}
+
+// printed and shown in the window for a few seconds
+pub fn show_message(render_data: &mut RenderData, message: String) {
+    eprintln!("{}", message);
+    render_data.message = Some((message, time::Instant::now()));
+}
+
+// rebuilds program from the fragment file if it was changed on disk.
+// if new source doesn't compile, last good program keeps running
+// and the error is shown on top of it
+fn reload_shader(render_data: &mut RenderData) {
+    if !render_data.shader_watcher.changed() {
+        return;
+    }
+
+    match load_pipeline(
+        &render_data.display,
+        &render_data.project_path,
+        &render_data.include_dirs,
+        &mut render_data.shader_watcher,
+    ) {
+        Ok(pipeline) => {
+            render_data
+                .parameters
+                .set_definitions(pipeline.parameters());
+            render_data.fallback = Some(mem::replace(&mut render_data.pipeline, pipeline));
+            render_data.shader_error = None;
+            // stdout can be taken by video stream
+            eprintln!("reloaded {}", render_data.project_path.display());
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            render_data.shader_error = Some(error);
+        }
+    }
+}
+
+// draw error is shown like a compile error, and the pipeline that
+// ran before the last reload draws again. without one it's the error shader
+fn fall_back(render_data: &mut RenderData, error: ShaderError) {
+    eprintln!("{}", error);
+    render_data.pipeline = match render_data.fallback.take() {
+        Some(pipeline) => {
+            render_data
+                .parameters
+                .set_definitions(pipeline.parameters());
+            pipeline
+        }
+        None => Pipeline::error(&render_data.display),
+    };
+    render_data.shader_error = Some(error);
+}
+
+// project is read again on every reload, so passes can be added or removed
+// without restart. watcher follows all shaders the project uses
+// and files they include
+fn load_pipeline<F: Facade>(
+    facade: &F,
+    project_path: &Path,
+    include_dirs: &[PathBuf],
+    watcher: &mut FileWatcher,
+) -> Result<Pipeline, ShaderError> {
+    let project = Project::load(project_path)?;
+
+    let mut files = project.files(project_path);
+    for shader in project.shaders() {
+        for file in included_files(shader, include_dirs) {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+    }
+    watcher.set_paths(files);
+
+    Pipeline::new(facade, &project, include_dirs)
+}
+
+// uniforms every pass gets, besides shadertoy ones and parameters
+macro_rules! scene_uniforms {
+    ($shader_input:expr) => {
+        uniform! {
+            aspect: $shader_input.aspect,
+            camera_position: $shader_input.camera_position,
+            rotation_matrix: $shader_input.rotation_matrix,
+            xyz_change: $shader_input.xyz_change,
+            iStaticTime: $shader_input.static_time,
+            fov: $shader_input.fov,
+            image_distance: $shader_input.image_distance,
+            projection: $shader_input.projection,
+            lens_shift: $shader_input.lens_shift,
+            view_height: $shader_input.view_height,
+            focus_distance: $shader_input.focus_distance,
+            aperture_radius: $shader_input.aperture_radius,
+            lens_sample: $shader_input.lens_sample,
+            pixel_jitter: $shader_input.pixel_jitter,
+        }
+    };
+}
+
+fn draw_scene<S: Surface>(
+    display: &RenderContext,
+    surface: &mut S,
+    pipeline: &mut Pipeline,
+    vertex_buffer: &VertexBuffer<Vertex>,
+    indices_buffer: &IndexBuffer<u8>,
+    shader_input: &ShaderInput,
+    parameters: &Parameters,
+) -> Result<(), ShaderError> {
+    pipeline.draw(
+        display,
+        surface,
+        vertex_buffer,
+        indices_buffer,
+        &WithParameters {
+            uniforms: &scene_uniforms!(shader_input),
+            parameters,
+        },
+        &shader_input.shader_toy_input,
+    )
+}
+
+// distance field of the shader for collision, evaluated by its probe
+// with uniforms of the frame. shaders without one have only the ground
+struct SceneField<'a> {
+    display: &'a RenderContext,
+    pipeline: &'a Pipeline,
+    vertex_buffer: &'a VertexBuffer<Vertex>,
+    indices_buffer: &'a IndexBuffer<u8>,
+    shader_input: &'a ShaderInput,
+    parameters: &'a Parameters,
+}
+
+impl<'a> DistanceField for SceneField<'a> {
+    fn sample(&mut self, points: &[[f32; 3]]) -> Vec<FieldSample> {
+        let probed = self.pipeline.probe(
+            self.display,
+            self.vertex_buffer,
+            self.indices_buffer,
+            &WithParameters {
+                uniforms: &scene_uniforms!(self.shader_input),
+                parameters: self.parameters,
             },
+            &self.shader_input.shader_toy_input,
+            points,
         );
 
-        let path = format!(
-            "{}{}{}",
-            "/home/maffi44/Pictures/fractal/",
-            render_data.frame_counter.to_string().as_str(),
-            ".png"
+        match probed {
+            Some(values) => with_ground(
+                values
+                    .into_iter()
+                    .map(|[distance, x, y, z]| FieldSample {
+                        distance,
+                        gradient: [x, y, z],
+                    })
+                    .collect(),
+                points,
+            ),
+            None => Ground.sample(points),
+        }
+    }
+}
+
+// input of the next frame, with the camera moved by keys through the scene
+fn frame_shader_input(render_data: &mut RenderData, resolution: (u32, u32)) -> ShaderInput {
+    let mut shader_input = render_data
+        .frame_input
+        .calculate_data(resolution, render_data.frame_counter);
+
+    let time_step = shader_input.shader_toy_input.i_time_delta;
+    let moved = render_data.frame_input.apply_movement(
+        &mut SceneField {
+            display: &render_data.display,
+            pipeline: &render_data.pipeline,
+            vertex_buffer: &render_data.vertex_buffer,
+            indices_buffer: &render_data.indices_buffer,
+            shader_input: &shader_input,
+            parameters: &render_data.parameters,
+        },
+        time_step,
+    );
+
+    shader_input.camera_position = render_data.frame_input.camera_position;
+    if time_step > 0.0 {
+        for (velocity, moved) in shader_input.velocity.iter_mut().zip(moved.iter()) {
+            *velocity += moved / time_step;
+        }
+    }
+
+    shader_input
+}
+
+// frame of the physical camera: samples with their own lens point, pixel
+// offset and time are averaged and exposed. plain frame of one sample
+// at exposure 1 is drawn straight to the surface
+#[allow(clippy::too_many_arguments)]
+fn draw_photo<S: Surface>(
+    display: &RenderContext,
+    surface: &mut S,
+    pipeline: &mut Pipeline,
+    accumulator: &mut Accumulator,
+    vertex_buffer: &VertexBuffer<Vertex>,
+    indices_buffer: &IndexBuffer<u8>,
+    shader_input: &ShaderInput,
+    parameters: &Parameters,
+    photo: &Photo,
+    samples: u32,
+) -> Result<(), ShaderError> {
+    let exposure = photo.exposure();
+
+    if samples <= 1 && exposure == 1.0 {
+        return draw_scene(
+            display,
+            surface,
+            pipeline,
+            vertex_buffer,
+            indices_buffer,
+            shader_input,
+            parameters,
+        );
+    }
+
+    pipeline.draw_buffers(
+        display,
+        vertex_buffer,
+        indices_buffer,
+        &WithParameters {
+            uniforms: &scene_uniforms!(shader_input),
+            parameters,
+        },
+        &shader_input.shader_toy_input,
+    )?;
+
+    let resolution = (
+        shader_input.shader_toy_input.i_resolution[0] as u32,
+        shader_input.shader_toy_input.i_resolution[1] as u32,
+    );
+    let sample_parameters = Accumulator::sample_parameters(samples);
+    let mut target = accumulator.start(display, resolution);
+
+    for index in 0..samples {
+        let sample_input = shader_input.sample(&Sample::new(index, samples), photo.shutter);
+
+        pipeline.draw_image(
+            &mut target,
+            vertex_buffer,
+            indices_buffer,
+            &WithParameters {
+                uniforms: &scene_uniforms!(sample_input),
+                parameters,
+            },
+            &sample_input.shader_toy_input,
+            &sample_parameters,
+        )?;
+    }
+    drop(target);
+
+    accumulator.resolve(surface, vertex_buffer, indices_buffer, exposure);
+    Ok(())
+}
+
+// focus goes to the surface at the image center before the frame is drawn.
+// it stays where it was when the shader doesn't write hit_point or sees sky
+fn autofocus(render_data: &mut RenderData, resolution: (u32, u32), shader_input: &mut ShaderInput) {
+    if !render_data.frame_input.photo.focusing() {
+        return;
+    }
+
+    let point = render_data.pipeline.pick(
+        &render_data.display,
+        &render_data.vertex_buffer,
+        &render_data.indices_buffer,
+        &WithParameters {
+            uniforms: &scene_uniforms!(shader_input),
+            parameters: &render_data.parameters,
+        },
+        &shader_input.shader_toy_input,
+        (resolution.0 / 2, resolution.1 / 2),
+    );
+
+    let point = match point {
+        Ok(point) => point,
+        Err(error) => return fall_back(render_data, error),
+    };
+
+    if let Some(point) = point {
+        // distance along the view direction, focus is a plane
+        let matrix = shader_input.rotation_matrix;
+        let forward = [matrix[0][2], matrix[1][2], matrix[2][2]];
+        let position = shader_input.camera_position;
+        let distance = (0..3)
+            .map(|axis| (point[axis] - position[axis]) * forward[axis])
+            .sum::<f32>();
+
+        if distance > 0.0 {
+            render_data.frame_input.photo.focus_distance = distance;
+            shader_input.focus_distance = distance;
+        }
+    }
+}
+
+// new orbit pivot on the surface under the cursor, when the shader
+// tells where its surface is. frame must be drawn already
+fn pick_pivot(render_data: &mut RenderData, resolution: (u32, u32), shader_input: &ShaderInput) {
+    let frame_input = &mut render_data.frame_input;
+
+    let cursor = match frame_input.orbit.as_mut().and_then(|orbit| orbit.pick.take()) {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    let pixel = match frame_input.image_pixel(resolution, cursor) {
+        Some(pixel) => pixel,
+        None => return,
+    };
+
+    let point = render_data.pipeline.pick(
+        &render_data.display,
+        &render_data.vertex_buffer,
+        &render_data.indices_buffer,
+        &WithParameters {
+            uniforms: &scene_uniforms!(shader_input),
+            parameters: &render_data.parameters,
+        },
+        &shader_input.shader_toy_input,
+        pixel,
+    );
+
+    let point = match point {
+        Ok(point) => point,
+        Err(error) => return fall_back(render_data, error),
+    };
+
+    if let (Some(point), Some(orbit)) = (point, &mut render_data.frame_input.orbit) {
+        orbit.pivot = point;
+
+        if let Some(material) = render_data.pipeline.material_at(point) {
+            let message = format!("pivot on {}", material);
+            show_message(render_data, message);
+        }
+    }
+}
+
+// fits offscreen image into the window keeping its aspect ratio
+fn draw_preview<S: Surface>(surface: &mut S, target: &Texture2d) {
+    let (window_width, window_height) = surface.get_dimensions();
+    let (width, height) = target.dimensions();
+
+    let scale = (window_width as f32 / width as f32).min(window_height as f32 / height as f32);
+    let preview_width = (width as f32 * scale) as i32;
+    let preview_height = (height as f32 * scale) as i32;
+
+    surface.clear_color(0.0, 0.0, 0.0, 1.0);
+
+    target.as_surface().blit_color(
+        &glium::Rect {
+            left: 0,
+            bottom: 0,
+            width,
+            height,
+        },
+        surface,
+        &glium::BlitTarget {
+            left: (window_width as i32 - preview_width) as u32 / 2,
+            bottom: (window_height as i32 - preview_height) as u32 / 2,
+            width: preview_width,
+            height: preview_height,
+        },
+        MagnifySamplerFilter::Linear,
+    );
+}
+
+fn read_target(target: &Texture2d) -> image::DynamicImage {
+    let (width, height) = target.dimensions();
+
+    let pixels: Vec<Vec<(u16, u16, u16, u16)>> = target
+        .main_level()
+        .first_layer()
+        .into_image(None)
+        .unwrap()
+        .raw_read(&glium::Rect {
+            left: 0,
+            bottom: 0,
+            width,
+            height,
+        });
+
+    // rows of gl textures go from bottom to top
+    let image_buffer = image::ImageBuffer::from_fn(width, height, |x, y| {
+        let (r, g, b, a) = pixels[(height - 1 - y) as usize][x as usize];
+        image::Rgba([r, g, b, a])
+    });
+
+    image::DynamicImage::ImageRgba16(image_buffer)
+}
+
+// returns Ok(true) when video or replayed session is finished
+pub fn render_frame(render_data: &mut RenderData) -> Result<bool, CaptureError> {
+    reload_shader(render_data);
+
+    take_replay_events(render_data);
+
+    let resolution = render_resolution(render_data);
+
+    let mut shader_input = frame_shader_input(render_data, resolution);
+
+    autofocus(render_data, resolution, &mut shader_input);
+
+    // pipeline that fails to draw is replaced after the frame
+    let mut draw_error = None;
+
+    if let Some(video) = &render_data.video {
+        let drawn = match SimpleFrameBuffer::new(&render_data.display, &video.target) {
+            Ok(mut target) => draw_photo(
+                &render_data.display,
+                &mut target,
+                &mut render_data.pipeline,
+                &mut render_data.accumulator,
+                &render_data.vertex_buffer,
+                &render_data.indices_buffer,
+                &shader_input,
+                &render_data.parameters,
+                &render_data.frame_input.photo,
+                render_data.samples,
+            ),
+            Err(error) => Err(ShaderError::Draw(error.to_string())),
+        };
+        draw_error = drawn.err();
+    }
+
+    if let RenderContext::Window(display) = &render_data.display {
+        let mut frame = display.draw();
+
+        let drawn = match &render_data.video {
+            Some(video) => {
+                draw_preview(&mut frame, &video.target);
+                Ok(())
+            }
+            None => draw_photo(
+                &render_data.display,
+                &mut frame,
+                &mut render_data.pipeline,
+                &mut render_data.accumulator,
+                &render_data.vertex_buffer,
+                &render_data.indices_buffer,
+                &shader_input,
+                &render_data.parameters,
+                &render_data.frame_input.photo,
+                render_data.samples,
+            ),
+        };
+
+        if let Err(error) = drawn {
+            frame.clear_color(0.0, 0.0, 0.0, 1.0);
+            draw_error = Some(error);
+        }
+
+        render_data.panel.draw(
+            display,
+            &mut frame,
+            &render_data.overlay,
+            &render_data.vertex_buffer,
+            &render_data.indices_buffer,
+            &render_data.parameters,
         );
 
-        image_buffer.save(path).expect("can not save");
+        // autofocus looks at the image center, letterboxed video has it there too
+        if render_data.frame_input.photo.focusing() {
+            let (width, height) = frame.get_dimensions();
+            let glyph_center = [CHAR_SIZE[0] * 2.5 / 6.0, CHAR_SIZE[1] * 3.5 / 9.0];
+            render_data.overlay.draw(
+                display,
+                &mut frame,
+                &render_data.vertex_buffer,
+                &render_data.indices_buffer,
+                &[String::from("+")],
+                [
+                    width as f32 / 2.0 - glyph_center[0],
+                    height as f32 / 2.0 - glyph_center[1],
+                ],
+                [1.0, 1.0, 1.0, 0.8],
+                [0.0, 0.0, 0.0, 0.0],
+            );
+        }
+
+        if let Some((message, shown)) = &render_data.message {
+            if shown.elapsed() < MESSAGE_TIME && render_data.shader_error.is_none() {
+                render_data.overlay.draw(
+                    display,
+                    &mut frame,
+                    &render_data.vertex_buffer,
+                    &render_data.indices_buffer,
+                    std::slice::from_ref(message),
+                    [8.0, 8.0],
+                    [0.9, 0.9, 0.9, 1.0],
+                    [0.05, 0.05, 0.05, 0.75],
+                );
+            }
+        }
+
+        if let Some(error) = &render_data.shader_error {
+            render_data.overlay.draw(
+                display,
+                &mut frame,
+                &render_data.vertex_buffer,
+                &render_data.indices_buffer,
+                &error.lines(),
+                [8.0, 8.0],
+                [1.0, 0.85, 0.85, 1.0],
+                [0.1, 0.0, 0.0, 0.8],
+            );
+        }
+
+        frame.finish().unwrap();
+    }
+
+    if let Some(error) = draw_error {
+        fall_back(render_data, error);
+    }
+
+    pick_pivot(render_data, resolution, &shader_input);
+
+    if let Some(video) = &mut render_data.video {
+        let image = read_target(&video.target);
+
+        video.sink.write(render_data.frame_counter, image)?;
     }
 
     render_data.frame_counter += 1;
 
     render_data.frame_input.delta_time = time::SystemTime::now();
 
-    if render_data.is_video {
-        if render_data.frame_input.static_time >= render_data.max_video_time {
-            return true;
+    if let Some(video) = &mut render_data.video {
+        if render_data.frame_counter >= video.frames.end {
+            video.sink.finish()?;
+            return Ok(true);
         }
     }
-    //println!("{}", shader_input.shader_toy_input.i_time);
-    return false;
 
+    if let Some(replay) = &render_data.replay {
+        if render_data.video.is_none() && replay.finished(render_data.frame_counter) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
 }
@@ -0,0 +1,1574 @@
+// scenes built from typed primitives, transforms and boolean operations.
+// they compile to the map() function of a shared ray marching template,
+// so they are drawn like any shader with a distance function. scenes are
+// written in scene files, "<name>.scene.ron" or "<name>.scene.json", which
+// the viewer opens like shaders:
+//
+//   (
+//       camera: (position: (0.0, 1.6, -12.0), pitch: 5.0),
+//       sun: (direction: (0.5, 0.8, 0.3)),
+//       lights: [(position: (0.0, 2.5, 0.0), color: (1.0, 0.8, 0.6), intensity: 4.0)],
+//       materials: { "concrete": (color: (0.55, 0.55, 0.52)) },
+//       nodes: [
+//           Plane(normal: (0.0, 1.0, 0.0), offset: 0.0),
+//           Material(material: "concrete", node: Translate(
+//               offset: (0.0, 1.5, 0.0),
+//               node: Subtract([Box(size: (6.0, 3.0, 8.0)), Cylinder(radius: 1.0, height: 4.0)]),
+//           )),
+//       ],
+//   )
+//
+// shapes are centered at the origin like in lib/sdf.glsl, transforms
+// move them into place. scene units are meters. scenes can be built in rust
+// too, the crate has a library for it:
+//
+//   let house = Node::cuboid([6.0, 3.0, 8.0])
+//       .translate([0.0, 1.5, 0.0])
+//       .subtract(Node::cylinder(1.0, 4.0).translate([0.0, 1.5, 0.0]));
+//   let source = Scene::new(house).shader_source();
+
+use glam::{Vec2, Vec2Swizzles, Vec3, Vec3Swizzles};
+use ron::extensions::Extensions;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use super::sdf::{
+    op_mirror_x, op_onion, op_repeat, rotate, sd_box, sd_capsule, sd_cylinder, sd_inf_cylinder,
+    sd_octahedron, sd_plane, sd_round_box, sd_sphere, sd_torus, Solid,
+};
+use super::shader_error::ShaderError;
+
+// ray marching, lighting and camera. materials, lights and
+// map() of the scene replace the marker line
+const TEMPLATE: &str = include_str!("../../shaders/lib/scene.glsl");
+const TEMPLATE_MARKER: &str = "// scene";
+
+// name of the generated shader in error messages
+pub const SCENE_SHADER_NAME: &str = "<scene>";
+
+const RON_EXTENSION: &str = ".scene.ron";
+const JSON_EXTENSION: &str = ".scene.json";
+
+// distance of operations without nodes, nothing is there
+const EMPTY_DISTANCE: f32 = 1e10;
+
+// nodes without material, like a massing model of card
+const DEFAULT_COLOR: [f32; 3] = [0.6, 0.6, 0.6];
+
+// central differences of the gradient, same as the probe of collision
+const GRADIENT_STEP: f32 = 0.001;
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum Node {
+    Sphere {
+        radius: f32,
+    },
+    // full size along the axes
+    Box {
+        size: [f32; 3],
+    },
+    // ring in xz plane, thickness is the radius of the tube
+    Torus {
+        radius: f32,
+        thickness: f32,
+    },
+    // segment along y between centers of the rounded ends
+    Capsule {
+        radius: f32,
+        length: f32,
+    },
+    // flat ends, along y
+    Cylinder {
+        radius: f32,
+        height: f32,
+    },
+    // everything behind the plane, which is offset from the origin along the normal
+    Plane {
+        normal: [f32; 3],
+        offset: f32,
+    },
+    // box with edges rounded by the radius, at most half of the smallest size
+    RoundBox {
+        size: [f32; 3],
+        radius: f32,
+    },
+    // corners are size away from the center, on the axes
+    Octahedron {
+        size: f32,
+    },
+    // along y without ends, like a column through the whole scene
+    InfiniteCylinder {
+        radius: f32,
+    },
+    Translate {
+        offset: [f32; 3],
+        node: Box<Node>,
+    },
+    // right-handed around the axis: positive degrees around y turn z towards x
+    Rotate {
+        axis: Axis,
+        degrees: f32,
+        node: Box<Node>,
+    },
+    // same scale along every axis, so the distance stays a distance
+    Scale {
+        factor: f32,
+        node: Box<Node>,
+    },
+    // hollow node, walls are thickness on both sides of its surface
+    Shell {
+        thickness: f32,
+        node: Box<Node>,
+    },
+    // copies of the node every period along the axes, the
+    // node has to fit in half of the period around the origin
+    Repeat {
+        period: [f32; 3],
+        node: Box<Node>,
+    },
+    // the node on the positive x side and its mirror image on the negative one
+    MirrorX {
+        node: Box<Node>,
+    },
+    // material of the scene for the node, nodes inside can have their own
+    Material {
+        material: String,
+        node: Box<Node>,
+    },
+    Union(Vec<Node>),
+    Intersect(Vec<Node>),
+    // first node with the others cut out of it, cuts get its material
+    Subtract(Vec<Node>),
+    // blend is the size of the rounding where the nodes meet
+    SmoothUnion {
+        blend: f32,
+        nodes: Vec<Node>,
+    },
+    SmoothIntersect {
+        blend: f32,
+        nodes: Vec<Node>,
+    },
+    SmoothSubtract {
+        blend: f32,
+        nodes: Vec<Node>,
+    },
+}
+
+// builders for scenes made in rust, see the top of the file
+impl Node {
+    pub fn sphere(radius: f32) -> Node {
+        Node::Sphere { radius }
+    }
+
+    pub fn cuboid(size: [f32; 3]) -> Node {
+        Node::Box { size }
+    }
+
+    pub fn torus(radius: f32, thickness: f32) -> Node {
+        Node::Torus { radius, thickness }
+    }
+
+    pub fn capsule(radius: f32, length: f32) -> Node {
+        Node::Capsule { radius, length }
+    }
+
+    pub fn cylinder(radius: f32, height: f32) -> Node {
+        Node::Cylinder { radius, height }
+    }
+
+    pub fn plane(normal: [f32; 3], offset: f32) -> Node {
+        Node::Plane { normal, offset }
+    }
+
+    pub fn round_cuboid(size: [f32; 3], radius: f32) -> Node {
+        Node::RoundBox { size, radius }
+    }
+
+    pub fn octahedron(size: f32) -> Node {
+        Node::Octahedron { size }
+    }
+
+    pub fn infinite_cylinder(radius: f32) -> Node {
+        Node::InfiniteCylinder { radius }
+    }
+
+    pub fn translate(self, offset: [f32; 3]) -> Node {
+        Node::Translate {
+            offset,
+            node: Box::new(self),
+        }
+    }
+
+    pub fn rotate(self, axis: Axis, degrees: f32) -> Node {
+        Node::Rotate {
+            axis,
+            degrees,
+            node: Box::new(self),
+        }
+    }
+
+    pub fn scale(self, factor: f32) -> Node {
+        Node::Scale {
+            factor,
+            node: Box::new(self),
+        }
+    }
+
+    pub fn shell(self, thickness: f32) -> Node {
+        Node::Shell {
+            thickness,
+            node: Box::new(self),
+        }
+    }
+
+    pub fn repeat(self, period: [f32; 3]) -> Node {
+        Node::Repeat {
+            period,
+            node: Box::new(self),
+        }
+    }
+
+    pub fn mirror_x(self) -> Node {
+        Node::MirrorX {
+            node: Box::new(self),
+        }
+    }
+
+    pub fn material(self, material: &str) -> Node {
+        Node::Material {
+            material: String::from(material),
+            node: Box::new(self),
+        }
+    }
+
+    // chains like a.union(b).union(c) make one operation of three nodes
+    pub fn union(self, other: Node) -> Node {
+        match self {
+            Node::Union(mut nodes) => {
+                nodes.push(other);
+                Node::Union(nodes)
+            }
+            node => Node::Union(vec![node, other]),
+        }
+    }
+
+    pub fn intersect(self, other: Node) -> Node {
+        match self {
+            Node::Intersect(mut nodes) => {
+                nodes.push(other);
+                Node::Intersect(nodes)
+            }
+            node => Node::Intersect(vec![node, other]),
+        }
+    }
+
+    pub fn subtract(self, other: Node) -> Node {
+        match self {
+            Node::Subtract(mut nodes) => {
+                nodes.push(other);
+                Node::Subtract(nodes)
+            }
+            node => Node::Subtract(vec![node, other]),
+        }
+    }
+
+    pub fn smooth_union(self, other: Node, blend: f32) -> Node {
+        Node::SmoothUnion {
+            blend,
+            nodes: vec![self, other],
+        }
+    }
+
+    pub fn smooth_intersect(self, other: Node, blend: f32) -> Node {
+        Node::SmoothIntersect {
+            blend,
+            nodes: vec![self, other],
+        }
+    }
+
+    pub fn smooth_subtract(self, other: Node, blend: f32) -> Node {
+        Node::SmoothSubtract {
+            blend,
+            nodes: vec![self, other],
+        }
+    }
+}
+
+impl Node {
+    // variant name of the scene file
+    fn name(&self) -> &'static str {
+        match self {
+            Node::Sphere { .. } => "Sphere",
+            Node::Box { .. } => "Box",
+            Node::Torus { .. } => "Torus",
+            Node::Capsule { .. } => "Capsule",
+            Node::Cylinder { .. } => "Cylinder",
+            Node::Plane { .. } => "Plane",
+            Node::RoundBox { .. } => "RoundBox",
+            Node::Octahedron { .. } => "Octahedron",
+            Node::InfiniteCylinder { .. } => "InfiniteCylinder",
+            Node::Translate { .. } => "Translate",
+            Node::Rotate { .. } => "Rotate",
+            Node::Scale { .. } => "Scale",
+            Node::Shell { .. } => "Shell",
+            Node::Repeat { .. } => "Repeat",
+            Node::MirrorX { .. } => "MirrorX",
+            Node::Material { .. } => "Material",
+            Node::Union(_) => "Union",
+            Node::Intersect(_) => "Intersect",
+            Node::Subtract(_) => "Subtract",
+            Node::SmoothUnion { .. } => "SmoothUnion",
+            Node::SmoothIntersect { .. } => "SmoothIntersect",
+            Node::SmoothSubtract { .. } => "SmoothSubtract",
+        }
+    }
+
+    // nodes the node is made of
+    fn children(&self) -> &[Node] {
+        match self {
+            Node::Translate { node, .. }
+            | Node::Rotate { node, .. }
+            | Node::Scale { node, .. }
+            | Node::Shell { node, .. }
+            | Node::Repeat { node, .. }
+            | Node::MirrorX { node }
+            | Node::Material { node, .. } => std::slice::from_ref(node.as_ref()),
+            Node::Union(nodes)
+            | Node::Intersect(nodes)
+            | Node::Subtract(nodes)
+            | Node::SmoothUnion { nodes, .. }
+            | Node::SmoothIntersect { nodes, .. }
+            | Node::SmoothSubtract { nodes, .. } => nodes,
+            _ => &[],
+        }
+    }
+
+    // values the distance functions can measure with, like
+    // "Union > Translate > Box: size must be positive, not -1"
+    fn validate(&self) -> Result<(), String> {
+        let checked = match self {
+            Node::Sphere { radius } => positive("radius", *radius),
+            Node::Box { size } => size.iter().try_for_each(|value| positive("size", *value)),
+            Node::Torus { radius, thickness } => {
+                positive("radius", *radius).and(positive("thickness", *thickness))
+            }
+            Node::Capsule { radius, length } => {
+                positive("radius", *radius).and(positive("length", *length))
+            }
+            Node::Cylinder { radius, height } => {
+                positive("radius", *radius).and(positive("height", *height))
+            }
+            Node::Plane { normal, offset } => finite("normal", normal)
+                .and(finite("offset", &[*offset]))
+                .and(positive("normal length", Vec3::from(*normal).length())),
+            Node::RoundBox { size, radius } => size
+                .iter()
+                .try_for_each(|value| positive("size", *value))
+                .and(positive("radius", *radius))
+                .and(at_most(
+                    "radius",
+                    *radius,
+                    "half of the size",
+                    Vec3::from(*size).min_element() / 2.0,
+                )),
+            Node::Octahedron { size } => positive("size", *size),
+            Node::InfiniteCylinder { radius } => positive("radius", *radius),
+            Node::Translate { offset, .. } => finite("offset", offset),
+            Node::Rotate { degrees, .. } => finite("degrees", &[*degrees]),
+            Node::Scale { factor, .. } => positive("factor", *factor),
+            Node::Shell { thickness, .. } => positive("thickness", *thickness),
+            Node::Repeat { period, .. } => period
+                .iter()
+                .try_for_each(|value| positive("period", *value)),
+            Node::SmoothUnion { blend, .. }
+            | Node::SmoothIntersect { blend, .. }
+            | Node::SmoothSubtract { blend, .. } => positive("blend", *blend),
+            Node::MirrorX { .. }
+            | Node::Material { .. }
+            | Node::Union(_)
+            | Node::Intersect(_)
+            | Node::Subtract(_) => Ok(()),
+        };
+        checked.map_err(|error| format!("{}: {}", self.name(), error))?;
+
+        self.children().iter().try_for_each(|node| {
+            node.validate()
+                .map_err(|error| format!("{} > {}", self.name(), error))
+        })
+    }
+
+    // names of materials the node and nodes inside it use
+    fn materials<'a>(&'a self, names: &mut Vec<&'a str>) {
+        if let Node::Material { material, .. } = self {
+            names.push(material);
+        }
+        for node in self.children() {
+            node.materials(names);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct Material {
+    pub color: [f32; 3],
+}
+
+// light from far away, it casts the soft shadows
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default)]
+pub struct Sun {
+    // towards the sun
+    pub direction: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+impl Default for Sun {
+    fn default() -> Sun {
+        Sun {
+            direction: [0.5, 0.8, 0.3],
+            color: [0.9, 0.85, 0.75],
+            intensity: 1.0,
+        }
+    }
+}
+
+// point light, it fades with square of the distance
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct Light {
+    pub position: [f32; 3],
+    #[serde(default = "white")]
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+fn white() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+// view the scene opens with, command line options go first. degrees
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SceneCamera {
+    pub position: Option<[f32; 3]>,
+    pub yaw: Option<f32>,
+    pub pitch: Option<f32>,
+    pub fov: Option<f32>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Scene {
+    #[serde(default)]
+    pub camera: SceneCamera,
+    #[serde(default)]
+    pub sun: Sun,
+    #[serde(default)]
+    pub lights: Vec<Light>,
+    #[serde(default)]
+    pub materials: BTreeMap<String, Material>,
+    // union of these is the scene
+    pub nodes: Vec<Node>,
+}
+
+impl Scene {
+    // default lights and no materials
+    pub fn new(node: Node) -> Scene {
+        Scene {
+            camera: SceneCamera::default(),
+            sun: Sun::default(),
+            lights: Vec::new(),
+            materials: BTreeMap::new(),
+            nodes: vec![node],
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Scene, ShaderError> {
+        let scene_error = |message: String| ShaderError::Scene {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let source = fs::read_to_string(path).map_err(|error| scene_error(error.to_string()))?;
+
+        let scene: Scene = if path.to_string_lossy().ends_with(JSON_EXTENSION) {
+            serde_json::from_str(&source).map_err(|error| scene_error(error.to_string()))?
+        } else {
+            // optional fields can be written without Some(...)
+            ron::Options::default()
+                .with_default_extension(Extensions::IMPLICIT_SOME)
+                .from_str(&source)
+                .map_err(|error| scene_error(error.to_string()))?
+        };
+
+        scene.validate().map_err(scene_error)?;
+
+        Ok(scene)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (index, node) in self.nodes.iter().enumerate() {
+            node.validate()
+                .map_err(|error| format!("node {}: {}", index + 1, error))?;
+        }
+
+        let sun = &self.sun;
+        finite("sun direction", &sun.direction)
+            .and(finite("sun color", &sun.color))
+            .and(finite("sun intensity", &[sun.intensity]))?;
+        for light in self.lights.iter() {
+            finite("light position", &light.position)
+                .and(finite("light color", &light.color))
+                .and(finite("light intensity", &[light.intensity]))?;
+        }
+
+        let mut names = Vec::new();
+        for node in self.nodes.iter() {
+            node.materials(&mut names);
+        }
+
+        match names
+            .iter()
+            .find(|name| !self.materials.contains_key(**name))
+        {
+            Some(name) => Err(format!("material \"{}\" is not in materials", name)),
+            None => Ok(()),
+        }
+    }
+
+    // materials, lights, `vec2 map_material(vec3 p)` with distance and
+    // material of the scene and `float map(vec3 p)` for lib/march.glsl
+    pub fn glsl(&self) -> String {
+        let mut code = String::new();
+
+        let sun = &self.sun;
+        writeln!(
+            code,
+            "const vec3 SUN_DIRECTION = {};",
+            vec3(normalize(sun.direction))
+        )
+        .unwrap();
+        writeln!(
+            code,
+            "const vec3 SUN_COLOR = {};",
+            vec3(scale(sun.color, sun.intensity))
+        )
+        .unwrap();
+
+        // glsl has no empty arrays, loops over lights stop at LIGHT_COUNT
+        let count = self.lights.len();
+        let positions: Vec<[f32; 3]> = self.lights.iter().map(|light| light.position).collect();
+        let colors: Vec<[f32; 3]> = self
+            .lights
+            .iter()
+            .map(|light| scale(light.color, light.intensity))
+            .collect();
+        writeln!(code, "#define LIGHT_COUNT {}", count).unwrap();
+        writeln!(
+            code,
+            "const vec3 LIGHT_POSITIONS[{}] = {};",
+            count.max(1),
+            vec3_array(&positions)
+        )
+        .unwrap();
+        writeln!(
+            code,
+            "const vec3 LIGHT_COLORS[{}] = {};",
+            count.max(1),
+            vec3_array(&colors)
+        )
+        .unwrap();
+
+        // material 0 is for nodes without material
+        let colors: Vec<[f32; 3]> = Some(DEFAULT_COLOR)
+            .into_iter()
+            .chain(self.materials.values().map(|material| material.color))
+            .collect();
+        writeln!(
+            code,
+            "const vec3 MATERIAL_COLORS[{}] = {};",
+            colors.len(),
+            vec3_array(&colors)
+        )
+        .unwrap();
+
+        let mut generator = Generator {
+            code: String::new(),
+            count: 0,
+            materials: self.materials.keys().map(String::as_str).collect(),
+        };
+        let distance = generator.combine(&self.nodes, "p", 0, "op_union", None);
+
+        write!(
+            code,
+            "
+vec2 map_material(vec3 p) {{
+{}    return {};
+}}
+
+float map(vec3 p) {{
+    return map_material(p).x;
+}}
+",
+            generator.code, distance
+        )
+        .unwrap();
+
+        code
+    }
+
+    // whole fragment shader drawing the scene, #include lines
+    // still have to be expanded by the preprocessor
+    pub fn shader_source(&self) -> String {
+        TEMPLATE.replacen(TEMPLATE_MARKER, &self.glsl(), 1)
+    }
+
+    // to the nearest surface, negative inside
+    pub fn distance(&self, point: [f32; 3]) -> f32 {
+        self.solid(Vec3::from(point)).distance
+    }
+
+    // points away from the surface, central differences like get_normal()
+    // of lib/march.glsl but not normalized
+    pub fn gradient(&self, point: [f32; 3]) -> [f32; 3] {
+        let map = |p: Vec3| self.solid(p).distance;
+
+        let p = Vec3::from(point);
+        let e = Vec2::new(GRADIENT_STEP, 0.0);
+        let gradient = Vec3::new(
+            map(p + e.xyy()) - map(p - e.xyy()),
+            map(p + e.yxy()) - map(p - e.yxy()),
+            map(p + e.yyx()) - map(p - e.yyx()),
+        ) / (2.0 * e.x);
+        gradient.into()
+    }
+
+    // of the nearest surface, 0 without material, then materials in order of their names
+    pub fn material(&self, point: [f32; 3]) -> usize {
+        self.solid(Vec3::from(point)).material
+    }
+
+    // the scene measured on the cpu, same as map_material() of the shader
+    fn solid(&self, p: Vec3) -> Solid {
+        let materials: Vec<&str> = self.materials.keys().map(String::as_str).collect();
+        evaluate_all(&self.nodes, p, 0, &materials, Solid::union)
+    }
+
+    // None for nodes without material
+    pub fn material_name(&self, material: usize) -> Option<&str> {
+        self.materials
+            .keys()
+            .nth(material.checked_sub(1)?)
+            .map(String::as_str)
+    }
+}
+
+// "<name>.scene.ron" and "<name>.scene.json"
+pub fn is_scene_file(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    name.ends_with(RON_EXTENSION) || name.ends_with(JSON_EXTENSION)
+}
+
+// distance with material of the node at the point, the way
+// Generator writes it for the shader
+fn evaluate(node: &Node, p: Vec3, material: usize, materials: &[&str]) -> Solid {
+    let distance = match node {
+        Node::Sphere { radius } => sd_sphere(p, *radius),
+        Node::Box { size } => sd_box(p, Vec3::from(*size) / 2.0),
+        Node::Torus { radius, thickness } => sd_torus(p, *radius, *thickness),
+        Node::Capsule { radius, length } => sd_capsule(
+            p + Vec3::new(0.0, length / 2.0, 0.0),
+            Vec3::new(0.0, *length, 0.0),
+            *radius,
+        ),
+        Node::Cylinder { radius, height } => sd_cylinder(
+            p + Vec3::new(0.0, height / 2.0, 0.0),
+            Vec3::new(0.0, *height, 0.0),
+            *radius,
+        ),
+        Node::Plane { normal, offset } => sd_plane(p, Vec3::from(normalize(*normal)), *offset),
+        Node::RoundBox { size, radius } => sd_round_box(p, Vec3::from(*size) / 2.0, *radius),
+        Node::Octahedron { size } => sd_octahedron(p, *size),
+        Node::InfiniteCylinder { radius } => sd_inf_cylinder(p, *radius),
+        Node::Translate { offset, node } => {
+            return evaluate(node, p - Vec3::from(*offset), material, materials)
+        }
+        Node::Rotate {
+            axis,
+            degrees,
+            node,
+        } => {
+            let p = match axis {
+                Axis::X => {
+                    let yz = rotate(p.yz(), -degrees.to_radians());
+                    Vec3::new(p.x, yz.x, yz.y)
+                }
+                Axis::Y => {
+                    let xz = rotate(p.xz(), degrees.to_radians());
+                    Vec3::new(xz.x, p.y, xz.y)
+                }
+                Axis::Z => {
+                    let xy = rotate(p.xy(), -degrees.to_radians());
+                    Vec3::new(xy.x, xy.y, p.z)
+                }
+            };
+            return evaluate(node, p, material, materials);
+        }
+        Node::Scale { factor, node } => {
+            let solid = evaluate(node, p / *factor, material, materials);
+            return Solid::new(solid.distance * factor, solid.material);
+        }
+        Node::Shell { thickness, node } => {
+            let solid = evaluate(node, p, material, materials);
+            return Solid::new(op_onion(solid.distance, *thickness), solid.material);
+        }
+        Node::Repeat { period, node } => {
+            return evaluate(node, op_repeat(p, Vec3::from(*period)), material, materials)
+        }
+        Node::MirrorX { node } => return evaluate(node, op_mirror_x(p), material, materials),
+        Node::Material {
+            material: name,
+            node,
+        } => {
+            let material = materials
+                .iter()
+                .position(|material| material == name)
+                .map_or(0, |index| index + 1);
+            return evaluate(node, p, material, materials);
+        }
+        Node::Union(nodes) => return evaluate_all(nodes, p, material, materials, Solid::union),
+        Node::Intersect(nodes) => {
+            return evaluate_all(nodes, p, material, materials, Solid::intersect)
+        }
+        Node::Subtract(nodes) => {
+            return evaluate_all(nodes, p, material, materials, Solid::subtract)
+        }
+        Node::SmoothUnion { blend, nodes } => {
+            return evaluate_all(nodes, p, material, materials, |a, b| {
+                a.smooth_union(b, *blend)
+            })
+        }
+        Node::SmoothIntersect { blend, nodes } => {
+            return evaluate_all(nodes, p, material, materials, |a, b| {
+                a.smooth_intersect(b, *blend)
+            })
+        }
+        Node::SmoothSubtract { blend, nodes } => {
+            return evaluate_all(nodes, p, material, materials, |a, b| {
+                a.smooth_subtract(b, *blend)
+            })
+        }
+    };
+
+    Solid::new(distance, material)
+}
+
+// operator applied to nodes from the first one on, like Generator::combine
+fn evaluate_all<F: Fn(Solid, Solid) -> Solid>(
+    nodes: &[Node],
+    p: Vec3,
+    material: usize,
+    materials: &[&str],
+    operator: F,
+) -> Solid {
+    let mut nodes = nodes.iter();
+    let first = match nodes.next() {
+        Some(node) => evaluate(node, p, material, materials),
+        None => return Solid::new(EMPTY_DISTANCE, material),
+    };
+
+    nodes.fold(first, |result, node| {
+        operator(result, evaluate(node, p, material, materials))
+    })
+}
+
+// map_material() is written as a list of variables numbered in order: points
+// of transformed space p1, p2.. and distance with material of nodes d3, d4..
+struct Generator<'a> {
+    code: String,
+    count: usize,
+    // in order of their ids from 1
+    materials: Vec<&'a str>,
+}
+
+impl<'a> Generator<'a> {
+    fn name(&mut self, prefix: &str) -> String {
+        self.count += 1;
+        format!("{}{}", prefix, self.count)
+    }
+
+    fn line(&mut self, line: &str) {
+        writeln!(self.code, "    {}", line).unwrap();
+    }
+
+    fn point(&mut self, expression: String) -> String {
+        let name = self.name("p");
+        self.line(&format!("vec3 {} = {};", name, expression));
+        name
+    }
+
+    // declares distance and material of the node at the point, returns its variable
+    fn distance(&mut self, node: &Node, point: &str, material: usize) -> String {
+        let distance = match node {
+            Node::Sphere { radius } => format!("sd_sphere({}, {})", point, float(*radius)),
+            Node::Box { size } => format!(
+                "sd_box({}, {})",
+                point,
+                vec3([size[0] / 2.0, size[1] / 2.0, size[2] / 2.0])
+            ),
+            Node::Torus { radius, thickness } => format!(
+                "sd_torus({}, {}, {})",
+                point,
+                float(*radius),
+                float(*thickness)
+            ),
+            // lib/sdf.glsl segments start at the origin
+            Node::Capsule { radius, length } => format!(
+                "sd_capsule({} + {}, {}, {})",
+                point,
+                vec3([0.0, length / 2.0, 0.0]),
+                vec3([0.0, *length, 0.0]),
+                float(*radius)
+            ),
+            Node::Cylinder { radius, height } => format!(
+                "sd_cylinder({} + {}, {}, {})",
+                point,
+                vec3([0.0, height / 2.0, 0.0]),
+                vec3([0.0, *height, 0.0]),
+                float(*radius)
+            ),
+            Node::Plane { normal, offset } => format!(
+                "sd_plane({}, {}, {})",
+                point,
+                vec3(normalize(*normal)),
+                float(*offset)
+            ),
+            Node::RoundBox { size, radius } => format!(
+                "sd_round_box({}, {}, {})",
+                point,
+                vec3([size[0] / 2.0, size[1] / 2.0, size[2] / 2.0]),
+                float(*radius)
+            ),
+            Node::Octahedron { size } => format!("sd_octahedron({}, {})", point, float(*size)),
+            Node::InfiniteCylinder { radius } => {
+                format!("sd_inf_cylinder({}, {})", point, float(*radius))
+            }
+            Node::Translate { offset, node } => {
+                let moved = self.point(format!("{} - {}", point, vec3(*offset)));
+                return self.distance(node, &moved, material);
+            }
+            // space turns the other way than the node
+            Node::Rotate {
+                axis,
+                degrees,
+                node,
+            } => {
+                let (plane, angle) = match axis {
+                    Axis::X => ("yz", -degrees.to_radians()),
+                    Axis::Y => ("xz", degrees.to_radians()),
+                    Axis::Z => ("xy", -degrees.to_radians()),
+                };
+                let turned = self.point(String::from(point));
+                self.line(&format!(
+                    "{}.{} *= rotate({});",
+                    turned,
+                    plane,
+                    float(angle)
+                ));
+                return self.distance(node, &turned, material);
+            }
+            Node::Scale { factor, node } => {
+                let scaled = self.point(format!("{} / {}", point, float(*factor)));
+                let result = self.distance(node, &scaled, material);
+                return self.declare(format!(
+                    "vec2({}.x * {}, {}.y)",
+                    result,
+                    float(*factor),
+                    result
+                ));
+            }
+            Node::Shell { thickness, node } => {
+                let result = self.distance(node, point, material);
+                return self.declare(format!(
+                    "vec2(op_onion({}.x, {}), {}.y)",
+                    result,
+                    float(*thickness),
+                    result
+                ));
+            }
+            Node::Repeat { period, node } => {
+                let repeated = self.point(format!("op_repeat({}, {})", point, vec3(*period)));
+                return self.distance(node, &repeated, material);
+            }
+            Node::MirrorX { node } => {
+                let mirrored = self.point(format!("op_mirror_x({})", point));
+                return self.distance(node, &mirrored, material);
+            }
+            // unknown materials are left to validation, they get the default one here
+            Node::Material {
+                material: name,
+                node,
+            } => {
+                let material = self
+                    .materials
+                    .iter()
+                    .position(|material| material == name)
+                    .map_or(0, |index| index + 1);
+                return self.distance(node, point, material);
+            }
+            Node::Union(nodes) => return self.combine(nodes, point, material, "op_union", None),
+            Node::Intersect(nodes) => {
+                return self.combine(nodes, point, material, "op_intersect", None)
+            }
+            Node::Subtract(nodes) => {
+                return self.combine(nodes, point, material, "op_subtract", None)
+            }
+            Node::SmoothUnion { blend, nodes } => {
+                return self.combine(nodes, point, material, "op_smooth_union", Some(*blend))
+            }
+            Node::SmoothIntersect { blend, nodes } => {
+                return self.combine(nodes, point, material, "op_smooth_intersect", Some(*blend))
+            }
+            Node::SmoothSubtract { blend, nodes } => {
+                return self.combine(nodes, point, material, "op_smooth_subtract", Some(*blend))
+            }
+        };
+
+        self.declare(format!("vec2({}, {})", distance, float(material as f32)))
+    }
+
+    fn declare(&mut self, expression: String) -> String {
+        let name = self.name("d");
+        self.line(&format!("vec2 {} = {};", name, expression));
+        name
+    }
+
+    // operator of lib/scene.glsl applied to nodes from the first one on
+    fn combine(
+        &mut self,
+        nodes: &[Node],
+        point: &str,
+        material: usize,
+        operator: &str,
+        blend: Option<f32>,
+    ) -> String {
+        let mut nodes = nodes.iter();
+        let mut result = match nodes.next() {
+            Some(node) => self.distance(node, point, material),
+            None => {
+                return self.declare(format!(
+                    "vec2({}, {})",
+                    float(EMPTY_DISTANCE),
+                    float(material as f32)
+                ))
+            }
+        };
+
+        for node in nodes {
+            let distance = self.distance(node, point, material);
+            let expression = match blend {
+                Some(blend) => format!("{}({}, {}, {})", operator, result, distance, float(blend)),
+                None => format!("{}({}, {})", operator, result, distance),
+            };
+            result = self.declare(expression);
+        }
+
+        result
+    }
+}
+
+// nan is not positive either
+fn positive(name: &str, value: f32) -> Result<(), String> {
+    if value > 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(format!("{} must be positive, not {}", name, value))
+    }
+}
+
+fn at_most(name: &str, value: f32, limit_name: &str, limit: f32) -> Result<(), String> {
+    if value <= limit {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} must be at most {}, {}, not {}",
+            name, limit_name, limit, value
+        ))
+    }
+}
+
+fn finite(name: &str, values: &[f32]) -> Result<(), String> {
+    match values.iter().find(|value| !value.is_finite()) {
+        Some(value) => Err(format!("{} must be a number, not {}", name, value)),
+        None => Ok(()),
+    }
+}
+
+// glsl float literal, debug format always has a point or an exponent
+fn float(value: f32) -> String {
+    format!("{:?}", value)
+}
+
+fn vec3(value: [f32; 3]) -> String {
+    format!(
+        "vec3({}, {}, {})",
+        float(value[0]),
+        float(value[1]),
+        float(value[2])
+    )
+}
+
+// array of at least one element
+fn vec3_array(values: &[[f32; 3]]) -> String {
+    let elements: Vec<String> = if values.is_empty() {
+        vec![vec3([0.0; 3])]
+    } else {
+        values.iter().map(|value| vec3(*value)).collect()
+    };
+
+    format!("vec3[]({})", elements.join(", "))
+}
+
+fn scale(value: [f32; 3], factor: f32) -> [f32; 3] {
+    [value[0] * factor, value[1] * factor, value[2] * factor]
+}
+
+fn normalize(value: [f32; 3]) -> [f32; 3] {
+    let length = (value[0] * value[0] + value[1] * value[1] + value[2] * value[2]).sqrt();
+    if length > 0.0 {
+        [value[0] / length, value[1] / length, value[2] / length]
+    } else {
+        [0.0, 1.0, 0.0]
+    }
+}
+
+// cpu samples of random scenes are checked against
+// the shader generated from them, read back from the gpu
+#[cfg(test)]
+mod tests {
+    use glium::backend::Facade;
+    use glium::index::{NoIndices, PrimitiveType};
+    use glium::texture::{MipmapsOption, UncompressedFloatFormat};
+    use glium::vertex::EmptyVertexAttributes;
+    use glium::{DrawParameters, Program, Rect, Surface, Texture2d};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::constructor::collision::{probe_source, PROBE_POINT};
+    use crate::constructor::headless::create_headless_context;
+    use crate::constructor::preprocessor::preprocess_source;
+    use crate::constructor::sdf::{op_intersect, op_union};
+
+    const SCENES: u64 = 24;
+    const POINTS: usize = 64;
+    const MATERIALS: [&str; 3] = ["brick", "glass", "steel"];
+    // scenes fit in a cube of this half size
+    const EXTENT: f32 = 6.0;
+    const MAX_DEPTH: u32 = 4;
+
+    const DISTANCE_TOLERANCE: f32 = 1e-3;
+    // central differences make rounding of distances 500 times larger
+    const GRADIENT_TOLERANCE: f32 = 0.05;
+    // materials can go either way where surfaces of two are equally near
+    const MATERIAL_TIE: f32 = 1e-3;
+
+    // triangle covering the target, no vertex buffer needed
+    const VERTEX_SHADER: &str = "#version 420
+        out vec2 fragCoord;
+
+        void main() {
+            vec2 corner = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+            fragCoord = corner;
+            gl_Position = vec4(corner * 2.0 - 1.0, 0.0, 1.0);
+        }";
+
+    fn random_size(rng: &mut StdRng) -> f32 {
+        rng.gen_range(0.2..3.0)
+    }
+
+    fn random_vec3(rng: &mut StdRng, range: f32) -> [f32; 3] {
+        [
+            rng.gen_range(-range..range),
+            rng.gen_range(-range..range),
+            rng.gen_range(-range..range),
+        ]
+    }
+
+    fn random_nodes(rng: &mut StdRng, depth: u32) -> Vec<Node> {
+        let count = rng.gen_range(1..=3);
+        (0..count).map(|_| random_node(rng, depth)).collect()
+    }
+
+    fn random_node(rng: &mut StdRng, depth: u32) -> Node {
+        let kinds = if depth == 0 { 10 } else { 22 };
+
+        match rng.gen_range(0..kinds) {
+            0 => Node::sphere(random_size(rng)),
+            1 => Node::cuboid([random_size(rng), random_size(rng), random_size(rng)]),
+            2 => {
+                let radius = random_size(rng);
+                Node::torus(radius, radius * rng.gen_range(0.1..0.5))
+            }
+            3 => Node::capsule(random_size(rng) / 2.0, random_size(rng)),
+            4 => Node::cylinder(random_size(rng) / 2.0, random_size(rng)),
+            5 => Node::plane(random_vec3(rng, 1.0), rng.gen_range(-2.0..2.0)),
+            6 => {
+                let size = [random_size(rng), random_size(rng), random_size(rng)];
+                let radius = Vec3::from(size).min_element() * rng.gen_range(0.1..0.5);
+                Node::round_cuboid(size, radius)
+            }
+            7 => Node::octahedron(random_size(rng)),
+            8 => Node::infinite_cylinder(random_size(rng) / 2.0),
+            // copies that don't reach into the next period, or
+            // the distance would jump where periods meet
+            9 => {
+                let radius = random_size(rng) / 2.0;
+                let period = [
+                    rng.gen_range(2.0..4.0) * radius,
+                    rng.gen_range(2.0..4.0) * radius,
+                    rng.gen_range(2.0..4.0) * radius,
+                ];
+                Node::sphere(radius).repeat(period)
+            }
+            10 => random_node(rng, depth - 1).translate(random_vec3(rng, 3.0)),
+            11 => {
+                let axis = [Axis::X, Axis::Y, Axis::Z][rng.gen_range(0..3)];
+                random_node(rng, depth - 1).rotate(axis, rng.gen_range(-180.0..180.0))
+            }
+            12 => random_node(rng, depth - 1).scale(rng.gen_range(0.5..2.0)),
+            13 => {
+                let material = MATERIALS[rng.gen_range(0..MATERIALS.len())];
+                random_node(rng, depth - 1).material(material)
+            }
+            14 => random_node(rng, depth - 1).shell(rng.gen_range(0.05..0.5)),
+            15 => random_node(rng, depth - 1).mirror_x(),
+            16 => Node::Union(random_nodes(rng, depth - 1)),
+            17 => Node::Intersect(random_nodes(rng, depth - 1)),
+            18 => Node::Subtract(random_nodes(rng, depth - 1)),
+            19 => Node::SmoothUnion {
+                blend: rng.gen_range(0.1..1.0),
+                nodes: random_nodes(rng, depth - 1),
+            },
+            20 => Node::SmoothIntersect {
+                blend: rng.gen_range(0.1..1.0),
+                nodes: random_nodes(rng, depth - 1),
+            },
+            _ => Node::SmoothSubtract {
+                blend: rng.gen_range(0.1..1.0),
+                nodes: random_nodes(rng, depth - 1),
+            },
+        }
+    }
+
+    fn random_scene(seed: u64) -> Scene {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut scene = Scene::new(random_node(&mut rng, MAX_DEPTH));
+        scene.nodes.extend(random_nodes(&mut rng, MAX_DEPTH));
+        for (index, name) in MATERIALS.iter().enumerate() {
+            let material = Material {
+                color: [index as f32 / 3.0; 3],
+            };
+            scene.materials.insert(String::from(*name), material);
+        }
+
+        scene
+    }
+
+    fn random_points(seed: u64) -> Vec<[f32; 3]> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..POINTS).map(|_| random_vec3(&mut rng, EXTENT)).collect()
+    }
+
+    fn program<F: Facade>(facade: &F, source: &str) -> Program {
+        Program::from_source(facade, VERTEX_SHADER, source, None)
+            .unwrap_or_else(|error| panic!("{}\n{}", error, source))
+    }
+
+    // pixel of the program at every point
+    fn read_back<F: Facade>(facade: &F, program: &Program, points: &[[f32; 3]]) -> Vec<[f32; 4]> {
+        let texture = Texture2d::empty_with_format(
+            facade,
+            UncompressedFloatFormat::F32F32F32F32,
+            MipmapsOption::NoMipmap,
+            points.len() as u32,
+            1,
+        )
+        .unwrap();
+        let mut target = texture.as_surface();
+
+        for (index, point) in points.iter().enumerate() {
+            target
+                .draw(
+                    EmptyVertexAttributes { len: 3 },
+                    NoIndices(PrimitiveType::TrianglesList),
+                    program,
+                    &uniform! { probe_point: *point },
+                    &DrawParameters {
+                        scissor: Some(Rect {
+                            left: index as u32,
+                            bottom: 0,
+                            width: 1,
+                            height: 1,
+                        }),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+
+        let pixels: Vec<Vec<(f32, f32, f32, f32)>> = texture
+            .main_level()
+            .first_layer()
+            .into_image(None)
+            .unwrap()
+            .raw_read(&Rect {
+                left: 0,
+                bottom: 0,
+                width: points.len() as u32,
+                height: 1,
+            });
+        pixels[0].iter().map(|&(x, y, z, w)| [x, y, z, w]).collect()
+    }
+
+    fn close(a: f32, b: f32, tolerance: f32) -> bool {
+        (a - b).abs() <= tolerance * a.abs().max(b.abs()).max(1.0)
+    }
+
+    // some material of the points around, where two are equally near
+    fn material_near(scene: &Scene, point: [f32; 3], material: usize) -> bool {
+        let offsets = [
+            [0.0, 0.0, 0.0],
+            [MATERIAL_TIE, 0.0, 0.0],
+            [-MATERIAL_TIE, 0.0, 0.0],
+            [0.0, MATERIAL_TIE, 0.0],
+            [0.0, -MATERIAL_TIE, 0.0],
+            [0.0, 0.0, MATERIAL_TIE],
+            [0.0, 0.0, -MATERIAL_TIE],
+        ];
+
+        offsets.iter().any(|offset| {
+            let near = [
+                point[0] + offset[0],
+                point[1] + offset[1],
+                point[2] + offset[2],
+            ];
+            scene.material(near) == material
+        })
+    }
+
+    #[test]
+    fn samples_match_gpu() {
+        let context = match create_headless_context((1, 1)) {
+            Ok(context) => context,
+            Err(error) => {
+                // nothing can be checked on machines without gl
+                eprintln!("skipping gpu comparison: {}", error);
+                return;
+            }
+        };
+
+        for seed in 0..SCENES {
+            let scene = random_scene(seed);
+            let source = preprocess_source(SCENE_SHADER_NAME, &scene.shader_source(), &[])
+                .unwrap_or_else(|error| panic!("{}", error))
+                .source;
+
+            let probe = program(&context, &probe_source(&source).unwrap());
+            let material_probe = program(
+                &context,
+                &format!(
+                    "{}\nuniform vec3 {point};\n\nvoid main() {{\n    fragColor = vec4(map_material({point}), 0.0, 0.0);\n}}\n",
+                    source.replacen("void main()", "void image_main()", 1),
+                    point = PROBE_POINT,
+                ),
+            );
+
+            let points = random_points(seed);
+            let probed = read_back(&context, &probe, &points);
+            let materials = read_back(&context, &material_probe, &points);
+
+            for ((point, gpu), gpu_material) in points.iter().zip(probed).zip(materials) {
+                let cpu = (scene.distance(*point), scene.gradient(*point));
+                let context = format!(
+                    "scene {} at {:?}: cpu {:?}, gpu {:?}",
+                    seed, point, cpu, gpu
+                );
+
+                assert!(
+                    close(cpu.0, gpu[0], DISTANCE_TOLERANCE),
+                    "distance of {}",
+                    context
+                );
+                for axis in 0..3 {
+                    assert!(
+                        close(cpu.1[axis], gpu[axis + 1], GRADIENT_TOLERANCE),
+                        "gradient of {}",
+                        context
+                    );
+                }
+                let material = (gpu_material[1] + 0.5) as usize;
+                assert!(
+                    material_near(&scene, *point, material),
+                    "material of {}",
+                    context
+                );
+            }
+        }
+    }
+
+    // no distance changes faster than the point moves,
+    // or ray marching would step through surfaces
+    #[test]
+    fn distances_are_bounds() {
+        for seed in 0..SCENES {
+            let scene = random_scene(seed);
+            let points = random_points(seed);
+
+            for pair in points.windows(2) {
+                let a = scene.distance(pair[0]);
+                let b = scene.distance(pair[1]);
+                let moved = (Vec3::from(pair[0]) - Vec3::from(pair[1])).length();
+
+                assert!(
+                    (a - b).abs() <= moved * (1.0 + DISTANCE_TOLERANCE),
+                    "scene {} between {:?} and {:?}: {} and {}",
+                    seed,
+                    pair[0],
+                    pair[1],
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn materials_are_named() {
+        let scene = random_scene(0);
+
+        assert_eq!(scene.material_name(0), None);
+        for (index, name) in MATERIALS.iter().enumerate() {
+            assert_eq!(scene.material_name(index + 1), Some(*name));
+        }
+        assert_eq!(scene.material_name(MATERIALS.len() + 1), None);
+    }
+
+    // glsl expression of p on the cpu, all values of the pixel
+    type CpuFunction = fn(Vec3) -> [f32; 4];
+
+    // functions of lib/sdf.glsl one by one, whether scenes use them or not
+    #[test]
+    fn sdf_functions_match_gpu() {
+        let context = match create_headless_context((1, 1)) {
+            Ok(context) => context,
+            Err(error) => {
+                eprintln!("skipping gpu comparison: {}", error);
+                return;
+            }
+        };
+
+        let functions: [(&str, CpuFunction); 8] = [
+            ("vec4(sd_round_box(p, vec3(1.0, 0.5, 2.0), 0.3))", |p| {
+                [sd_round_box(p, Vec3::new(1.0, 0.5, 2.0), 0.3); 4]
+            }),
+            ("vec4(sd_inf_cylinder(p, 0.8))", |p| {
+                [sd_inf_cylinder(p, 0.8); 4]
+            }),
+            ("vec4(sd_octahedron(p, 1.5))", |p| [sd_octahedron(p, 1.5); 4]),
+            (
+                "vec4(op_union(sd_sphere(p, 1.0), sd_box(p - vec3(1.0, 0.0, 0.0), vec3(1.0, 0.5, 2.0))))",
+                |p| [op_union(sd_sphere(p, 1.0), sd_box(p - Vec3::X, Vec3::new(1.0, 0.5, 2.0))); 4],
+            ),
+            (
+                "vec4(op_intersect(sd_sphere(p, 1.0), sd_box(p - vec3(1.0, 0.0, 0.0), vec3(1.0, 0.5, 2.0))))",
+                |p| [op_intersect(sd_sphere(p, 1.0), sd_box(p - Vec3::X, Vec3::new(1.0, 0.5, 2.0))); 4],
+            ),
+            ("vec4(op_onion(sd_sphere(p, 2.0), 0.25))", |p| {
+                [op_onion(sd_sphere(p, 2.0), 0.25); 4]
+            }),
+            ("vec4(op_repeat(p, vec3(3.0, 2.0, 4.0)), 0.0)", |p| {
+                op_repeat(p, Vec3::new(3.0, 2.0, 4.0)).extend(0.0).into()
+            }),
+            ("vec4(op_mirror_x(p), 0.0)", |p| {
+                op_mirror_x(p).extend(0.0).into()
+            }),
+        ];
+
+        let points = random_points(0);
+        for (expression, function) in functions.iter() {
+            let source = format!(
+                "#version 420\n#include \"lib/sdf.glsl\"\nuniform vec3 {point};\nout vec4 fragColor;\n\nvoid main() {{\n    vec3 p = {point};\n    fragColor = {};\n}}\n",
+                expression,
+                point = PROBE_POINT,
+            );
+            let source = preprocess_source("<sdf>", &source, &[])
+                .unwrap_or_else(|error| panic!("{}", error))
+                .source;
+            let probed = read_back(&context, &program(&context, &source), &points);
+
+            for (point, gpu) in points.iter().zip(probed) {
+                let cpu = function(Vec3::from(*point));
+                assert!(
+                    (0..4).all(|index| close(cpu[index], gpu[index], DISTANCE_TOLERANCE)),
+                    "{} at {:?}: cpu {:?}, gpu {:?}",
+                    expression,
+                    point,
+                    cpu,
+                    gpu
+                );
+            }
+        }
+    }
+
+    fn house() -> Scene {
+        let walls = Node::cuboid([6.0, 3.0, 8.0])
+            .translate([0.0, 1.5, 0.0])
+            .subtract(Node::cylinder(1.0, 4.0).rotate(Axis::X, 90.0))
+            .material("brick");
+        let roof = Node::torus(2.0, 0.5)
+            .scale(2.0)
+            .smooth_union(Node::sphere(1.0), 0.5)
+            .translate([0.0, 3.0, 0.0]);
+
+        let mut scene = Scene::new(walls.union(roof));
+        scene.materials.insert(
+            String::from("brick"),
+            Material {
+                color: [0.7, 0.3, 0.2],
+            },
+        );
+        scene
+    }
+
+    #[test]
+    fn wrong_values_are_found() {
+        assert_eq!(house().validate(), Ok(()));
+
+        let scene = Scene::new(
+            Node::sphere(1.0).union(Node::cuboid([1.0, -1.0, 1.0]).translate([2.0, 0.0, 0.0])),
+        );
+        assert_eq!(
+            scene.validate(),
+            Err(String::from(
+                "node 1: Union > Translate > Box: size must be positive, not -1"
+            ))
+        );
+
+        let wrong = [
+            Node::sphere(f32::NAN),
+            Node::capsule(0.5, 0.0),
+            Node::plane([0.0; 3], 1.0),
+            Node::sphere(1.0).scale(0.0),
+            Node::sphere(1.0).rotate(Axis::Y, f32::INFINITY),
+            Node::sphere(1.0).smooth_union(Node::sphere(1.0), 0.0),
+            Node::round_cuboid([1.0, 2.0, 3.0], 0.6),
+            Node::octahedron(-1.0),
+            Node::sphere(1.0).shell(0.0),
+            Node::sphere(1.0).repeat([2.0, 0.0, 2.0]),
+        ];
+        for node in wrong {
+            assert!(Scene::new(node.clone()).validate().is_err(), "{:?}", node);
+        }
+    }
+
+    #[test]
+    fn chained_operations_are_one_node() {
+        let a = Node::sphere(1.0);
+
+        let union = a.clone().union(a.clone()).union(a.clone());
+        assert_eq!(union, Node::Union(vec![a.clone(), a.clone(), a.clone()]));
+
+        let intersect = a.clone().intersect(a.clone()).intersect(a.clone());
+        assert_eq!(
+            intersect,
+            Node::Intersect(vec![a.clone(), a.clone(), a.clone()])
+        );
+
+        let subtract = a.clone().subtract(a.clone()).subtract(a.clone());
+        assert_eq!(subtract, Node::Subtract(vec![a.clone(), a.clone(), a]));
+    }
+
+    #[test]
+    fn map_is_generated() {
+        let expected = "\
+const vec3 SUN_DIRECTION = vec3(0.5050762, 0.80812204, 0.30304578);
+const vec3 SUN_COLOR = vec3(0.9, 0.85, 0.75);
+#define LIGHT_COUNT 0
+const vec3 LIGHT_POSITIONS[1] = vec3[](vec3(0.0, 0.0, 0.0));
+const vec3 LIGHT_COLORS[1] = vec3[](vec3(0.0, 0.0, 0.0));
+const vec3 MATERIAL_COLORS[2] = vec3[](vec3(0.6, 0.6, 0.6), vec3(0.7, 0.3, 0.2));
+
+vec2 map_material(vec3 p) {
+    vec3 p1 = p - vec3(0.0, 1.5, 0.0);
+    vec2 d2 = vec2(sd_box(p1, vec3(3.0, 1.5, 4.0)), 1.0);
+    vec3 p3 = p;
+    p3.yz *= rotate(-1.5707964);
+    vec2 d4 = vec2(sd_cylinder(p3 + vec3(0.0, 2.0, 0.0), vec3(0.0, 4.0, 0.0), 1.0), 1.0);
+    vec2 d5 = op_subtract(d2, d4);
+    vec3 p6 = p - vec3(0.0, 3.0, 0.0);
+    vec3 p7 = p6 / 2.0;
+    vec2 d8 = vec2(sd_torus(p7, 2.0, 0.5), 0.0);
+    vec2 d9 = vec2(d8.x * 2.0, d8.y);
+    vec2 d10 = vec2(sd_sphere(p6, 1.0), 0.0);
+    vec2 d11 = op_smooth_union(d9, d10, 0.5);
+    vec2 d12 = op_union(d5, d11);
+    return d12;
+}
+
+float map(vec3 p) {
+    return map_material(p).x;
+}
+";
+
+        assert_eq!(house().glsl(), expected);
+    }
+
+    // every node and light the generator knows, in one shader
+    #[test]
+    fn generated_shader_compiles() {
+        let context = match create_headless_context((1, 1)) {
+            Ok(context) => context,
+            Err(error) => {
+                eprintln!("skipping shader compilation: {}", error);
+                return;
+            }
+        };
+
+        let mut scene = house();
+        scene.nodes.push(
+            Node::capsule(0.5, 2.0)
+                .rotate(Axis::Z, 30.0)
+                .intersect(Node::plane([0.0, 1.0, 0.0], 1.0))
+                .smooth_subtract(Node::sphere(0.5).rotate(Axis::Y, 10.0), 0.2)
+                .smooth_intersect(Node::Union(Vec::new()), 0.1),
+        );
+        scene.nodes.push(
+            Node::round_cuboid([1.0, 2.0, 3.0], 0.2)
+                .union(Node::octahedron(1.0).shell(0.1))
+                .subtract(Node::infinite_cylinder(0.3))
+                .repeat([4.0, 4.0, 4.0])
+                .mirror_x(),
+        );
+        scene.lights.push(Light {
+            position: [0.0, 2.0, 0.0],
+            color: [1.0, 0.9, 0.8],
+            intensity: 3.0,
+        });
+
+        let source = preprocess_source(SCENE_SHADER_NAME, &scene.shader_source(), &[])
+            .unwrap_or_else(|error| panic!("{}", error))
+            .source;
+        program(&context, &source);
+    }
+}
@@ -0,0 +1,183 @@
+// distance functions and operators of lib/sdf.glsl on the cpu, written the
+// same way so both give the same distances. scenes are measured with them
+// without a gl context, see Scene::distance
+
+use glam::{Vec2, Vec3};
+
+// p.xz *= rotate(angle) of glsl is rotate(p.xz, angle)
+pub fn rotate(v: Vec2, angle: f32) -> Vec2 {
+    let (s, c) = angle.sin_cos();
+    Vec2::new(v.x * c - v.y * s, v.x * s + v.y * c)
+}
+
+// primitives
+
+pub fn sd_sphere(p: Vec3, radius: f32) -> f32 {
+    p.length() - radius
+}
+
+// b is half of the box size
+pub fn sd_box(p: Vec3, b: Vec3) -> f32 {
+    let d = p.abs() - b;
+    d.max_element().min(0.0) + d.max(Vec3::ZERO).length()
+}
+
+pub fn sd_round_box(p: Vec3, b: Vec3, radius: f32) -> f32 {
+    sd_box(p, b - Vec3::splat(radius)) - radius
+}
+
+// ring in xz plane
+pub fn sd_torus(p: Vec3, radius1: f32, radius2: f32) -> f32 {
+    let x = Vec2::new(p.x, p.z).length() - radius1;
+    Vec2::new(x, p.y).length() - radius2
+}
+
+// segment from the origin to b
+pub fn sd_capsule(p: Vec3, b: Vec3, radius: f32) -> f32 {
+    let d = b.dot(p) / b.dot(b);
+    if d <= 0.0 {
+        return p.length() - radius;
+    }
+    if d >= 1.0 {
+        return (p - b).length() - radius;
+    }
+    (b * d - p).length() - radius
+}
+
+// infinite along y
+pub fn sd_inf_cylinder(p: Vec3, radius: f32) -> f32 {
+    Vec2::new(p.x, p.z).length() - radius
+}
+
+// flat ends, axis from the origin to ab
+pub fn sd_cylinder(ap: Vec3, ab: Vec3, radius: f32) -> f32 {
+    let t = ap.dot(ab) / ab.dot(ab);
+    let d = (ab * t - ap).length() - radius;
+    let y = ((t - 0.5).abs() - 0.5) * ab.length();
+    let e = Vec2::new(d, y).max(Vec2::ZERO).length();
+    let i = d.max(y).min(0.0);
+    e + i
+}
+
+// normal must be normalized, plane is at distance h from the origin
+pub fn sd_plane(p: Vec3, normal: Vec3, h: f32) -> f32 {
+    p.dot(normal) - h
+}
+
+pub fn sd_octahedron(p: Vec3, size: f32) -> f32 {
+    let p = p.abs();
+    (p.x + p.y + p.z - size) * 0.577_350_27
+}
+
+// operators
+
+pub fn op_union(a: f32, b: f32) -> f32 {
+    a.min(b)
+}
+
+// a with b cut out of it
+pub fn op_subtract(a: f32, b: f32) -> f32 {
+    a.max(-b)
+}
+
+pub fn op_intersect(a: f32, b: f32) -> f32 {
+    a.max(b)
+}
+
+// k is the size of the blend
+pub fn op_smooth_union(a: f32, b: f32, k: f32) -> f32 {
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    mix(b, a, h) - k * h * (1.0 - h)
+}
+
+pub fn op_smooth_subtract(a: f32, b: f32, k: f32) -> f32 {
+    let h = (0.5 - 0.5 * (a + b) / k).clamp(0.0, 1.0);
+    mix(a, -b, h) + k * h * (1.0 - h)
+}
+
+pub fn op_smooth_intersect(a: f32, b: f32, k: f32) -> f32 {
+    let h = (0.5 - 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    mix(b, a, h) + k * h * (1.0 - h)
+}
+
+// shell of the given thickness around the surface
+pub fn op_onion(d: f32, thickness: f32) -> f32 {
+    d.abs() - thickness
+}
+
+// domain operators, applied to the point before the distance function
+
+// infinite copies every period units
+pub fn op_repeat(p: Vec3, period: Vec3) -> Vec3 {
+    let p = p + 0.5 * period;
+    // mod of glsl, the result has the sign of the period
+    p - period * (p / period).floor() - 0.5 * period
+}
+
+// mirror copy of negative x side
+pub fn op_mirror_x(p: Vec3) -> Vec3 {
+    Vec3::new(p.x.abs(), p.y, p.z)
+}
+
+// as glsl defines it, so far apart a and b give back one of them exactly
+fn mix(a: f32, b: f32, h: f32) -> f32 {
+    a * (1.0 - h) + b * h
+}
+
+// distance with material, the vec2 of lib/scene.glsl
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Solid {
+    pub distance: f32,
+    pub material: usize,
+}
+
+// operators of lib/scene.glsl, the material of the
+// surface that is left goes with the distance
+impl Solid {
+    pub fn new(distance: f32, material: usize) -> Solid {
+        Solid { distance, material }
+    }
+
+    pub fn union(self, other: Solid) -> Solid {
+        let material = if self.distance < other.distance {
+            self.material
+        } else {
+            other.material
+        };
+        Solid::new(op_union(self.distance, other.distance), material)
+    }
+
+    pub fn subtract(self, other: Solid) -> Solid {
+        Solid::new(op_subtract(self.distance, other.distance), self.material)
+    }
+
+    pub fn intersect(self, other: Solid) -> Solid {
+        let material = if self.distance > other.distance {
+            self.material
+        } else {
+            other.material
+        };
+        Solid::new(op_intersect(self.distance, other.distance), material)
+    }
+
+    pub fn smooth_union(self, other: Solid, k: f32) -> Solid {
+        Solid::new(
+            op_smooth_union(self.distance, other.distance, k),
+            self.union(other).material,
+        )
+    }
+
+    pub fn smooth_subtract(self, other: Solid, k: f32) -> Solid {
+        Solid::new(
+            op_smooth_subtract(self.distance, other.distance, k),
+            self.material,
+        )
+    }
+
+    pub fn smooth_intersect(self, other: Solid, k: f32) -> Solid {
+        Solid::new(
+            op_smooth_intersect(self.distance, other.distance, k),
+            self.intersect(other).material,
+        )
+    }
+}
@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time;
+
+use super::camera_path::{CameraPath, PathEditor};
+use super::collision::{Walk, DEFAULT_EYE_HEIGHT, DEFAULT_STEP_HEIGHT};
+use super::input::InputEvent;
+use super::orbit::Orbit;
+use super::parameters::Parameters;
+use super::presets::{Preset, Presets};
+use super::render::FrameInput;
+use super::sidecar::{self, SidecarError};
+use super::views::{View, Views};
+
+// name of the file content in messages
+const WHAT: &str = "session";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionEvent {
+    // event comes before this frame is drawn
+    pub frame: u32,
+    pub event: InputEvent,
+}
+
+// input of a window session with the state it started from. time goes
+// by fixed step while recording, so replay draws exactly the same frames:
+//   (
+//       shader: "shaders/fragment_shader.frag",
+//       time_step: 0.016666668,
+//       start_time: 1760000000.0,
+//       display_size: (1792, 768),
+//       start: (name: "start", camera_position: (0.0, 1.0, 0.0), ...),
+//       fov: 90.0,
+//       modes: (collision: true, walking: false, eye_height: 1.6, step_height: 0.3, orbit: None, samples: 1),
+//       frames: 240,
+//       events: [
+//           (frame: 12, event: Key(key: W, pressed: true)),
+//           (frame: 60, event: Key(key: W, pressed: false)),
+//       ],
+//       files: (presets: [...], preset: Some(0), views: [...], camera_path: (...)),
+//   )
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub shader: PathBuf,
+    pub time_step: f32,
+    // seconds since unix epoch, iDate of replayed frames follows it
+    pub start_time: f64,
+    pub display_size: (u32, u32),
+    pub start: Preset,
+    pub fov: f32,
+    // sessions recorded before modes were kept replay with the defaults
+    #[serde(default)]
+    pub modes: SessionModes,
+    pub frames: u32,
+    pub events: Vec<SessionEvent>,
+    #[serde(default)]
+    pub files: SessionFiles,
+}
+
+// how the camera moved and frames were drawn when recording started,
+// command line options of the replay don't change them
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionModes {
+    pub collision: bool,
+    pub walking: bool,
+    pub eye_height: f32,
+    pub step_height: f32,
+    // pivot of the orbit camera, None for the fly camera
+    pub orbit: Option<[f32; 3]>,
+    // samples of every frame
+    pub samples: u32,
+}
+
+impl Default for SessionModes {
+    fn default() -> SessionModes {
+        SessionModes {
+            collision: false,
+            walking: false,
+            eye_height: DEFAULT_EYE_HEIGHT,
+            step_height: DEFAULT_STEP_HEIGHT,
+            orbit: None,
+            samples: 1,
+        }
+    }
+}
+
+impl SessionModes {
+    pub fn capture(frame_input: &FrameInput, samples: u32) -> SessionModes {
+        SessionModes {
+            collision: frame_input.collision,
+            walking: frame_input.walking,
+            eye_height: frame_input.walk.eye_height,
+            step_height: frame_input.walk.step_height,
+            orbit: frame_input.orbit.as_ref().map(|orbit| orbit.pivot),
+            samples,
+        }
+    }
+
+    fn apply(&self, frame_input: &mut FrameInput, samples: &mut u32) {
+        frame_input.collision = self.collision;
+        frame_input.walking = self.walking;
+        frame_input.walk = Walk::new(self.eye_height, self.step_height);
+        frame_input.orbit = self.orbit.map(Orbit::around);
+        *samples = self.samples;
+    }
+}
+
+// presets, views and camera path of the shader when recording started.
+// replay goes on from them, so keys that use the files do the same
+// even when the files have changed since
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionFiles {
+    pub presets: Vec<Preset>,
+    // current preset, F6 and F7 go on from it
+    pub preset: Option<usize>,
+    pub views: Vec<View>,
+    pub camera_path: CameraPath,
+}
+
+impl SessionFiles {
+    pub fn snapshot(
+        presets: &Presets,
+        views: &Views,
+        path_editor: &PathEditor,
+    ) -> Result<SessionFiles, SidecarError> {
+        let (presets, preset) = presets.snapshot()?;
+        Ok(SessionFiles {
+            presets,
+            preset,
+            views: views.snapshot()?,
+            camera_path: path_editor.snapshot()?,
+        })
+    }
+
+    pub fn replay(&self, presets: &mut Presets, views: &mut Views, path_editor: &mut PathEditor) {
+        presets.replay(self.presets.clone(), self.preset);
+        views.replay(self.views.clone());
+        path_editor.replay(self.camera_path.clone());
+    }
+}
+
+impl Session {
+    pub fn load(path: &Path) -> Result<Session, SidecarError> {
+        let session: Session = sidecar::read(WHAT, path)?;
+
+        if session.time_step <= 0.0 {
+            return Err(SidecarError::Parse {
+                what: WHAT,
+                path: path.to_path_buf(),
+                message: format!("time step {} must be greater than 0", session.time_step),
+            });
+        }
+
+        Ok(session)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), SidecarError> {
+        sidecar::write(WHAT, path, self)
+    }
+
+    // puts frame input, parameters and samples of every frame
+    // into the state recording started from
+    pub fn apply_start(
+        &self,
+        frame_input: &mut FrameInput,
+        parameters: &mut Parameters,
+        samples: &mut u32,
+    ) {
+        self.start.apply(frame_input, parameters);
+        self.modes.apply(frame_input, samples);
+        frame_input.fov = self.fov;
+        frame_input.time_step = self.time_step;
+        frame_input.offline = true;
+        frame_input.time = time::UNIX_EPOCH + time::Duration::from_secs_f64(self.start_time);
+        frame_input.display_width = self.display_size.0;
+        frame_input.display_height = self.display_size.1;
+    }
+}
+
+// collects events of the window and writes them when the window closes
+pub struct Recorder {
+    path: PathBuf,
+    session: Session,
+}
+
+impl Recorder {
+    // file is written right away, so a bad path is reported before the session
+    pub fn new(
+        path: PathBuf,
+        shader: &Path,
+        frame_input: &FrameInput,
+        parameters: &Parameters,
+        samples: u32,
+        files: SessionFiles,
+    ) -> Result<Recorder, SidecarError> {
+        let start_time = frame_input
+            .time
+            .duration_since(time::UNIX_EPOCH)
+            .map_or(0.0, |duration| duration.as_secs_f64());
+
+        let session = Session {
+            shader: shader.to_path_buf(),
+            time_step: frame_input.time_step,
+            start_time,
+            display_size: (frame_input.display_width, frame_input.display_height),
+            start: Preset::capture(String::from("start"), frame_input, parameters),
+            fov: frame_input.fov,
+            modes: SessionModes::capture(frame_input, samples),
+            frames: 0,
+            events: Vec::new(),
+            files,
+        };
+        session.save(&path)?;
+
+        Ok(Recorder { path, session })
+    }
+
+    pub fn record(&mut self, frame: u32, event: &InputEvent) {
+        self.session.events.push(SessionEvent {
+            frame,
+            event: event.clone(),
+        });
+    }
+
+    pub fn finish(&mut self, frames: u32) -> Result<String, SidecarError> {
+        self.session.frames = frames;
+        self.session.save(&self.path)?;
+
+        Ok(format!(
+            "recorded {} frames, {} events to {}",
+            frames,
+            self.session.events.len(),
+            self.path.display()
+        ))
+    }
+}
+
+// feeds events of a session back frame by frame
+pub struct Replay {
+    session: Session,
+    next: usize,
+}
+
+impl Replay {
+    pub fn new(session: Session) -> Replay {
+        Replay { session, next: 0 }
+    }
+
+    // events recorded before the frame that were not taken yet
+    pub fn take_events(&mut self, frame: u32) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+
+        while let Some(event) = self.session.events.get(self.next) {
+            if event.frame > frame {
+                break;
+            }
+            events.push(event.event.clone());
+            self.next += 1;
+        }
+
+        events
+    }
+
+    pub fn finished(&self, frame: u32) -> bool {
+        frame >= self.session.frames
+    }
+}
@@ -0,0 +1,218 @@
+use glium::program::ProgramCreationError;
+use glium::program::ShaderType;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use super::preprocessor::SourceMap;
+
+pub const VERTEX_SHADER_NAME: &str = "<built-in vertex shader>";
+
+// one message of a driver log, mapped to the file it came from
+pub struct ShaderMessage {
+    pub file: String,
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+pub enum ShaderError {
+    Io { path: PathBuf, error: io::Error },
+    Compile { messages: Vec<ShaderMessage> },
+    Link { messages: Vec<ShaderMessage> },
+    Unsupported(String),
+    Project { path: PathBuf, message: String },
+    Scene { path: PathBuf, message: String },
+    Texture { path: PathBuf, message: String },
+    // program was built but the driver refused to run it
+    Draw(String),
+}
+
+impl ShaderError {
+    // lines of fragment shader messages are mapped to files it was expanded from
+    pub fn from_program_error(error: ProgramCreationError, source_map: &SourceMap) -> ShaderError {
+        match error {
+            ProgramCreationError::CompilationError(log, ShaderType::Fragment) => {
+                ShaderError::Compile {
+                    messages: parse_fragment_log(&log, source_map),
+                }
+            }
+            ProgramCreationError::CompilationError(log, _) => ShaderError::Compile {
+                messages: parse_log(&log, VERTEX_SHADER_NAME),
+            },
+            ProgramCreationError::LinkingError(log) => ShaderError::Link {
+                messages: parse_fragment_log(&log, source_map),
+            },
+            other => ShaderError::Unsupported(other.to_string()),
+        }
+    }
+
+    // human readable lines, used both for terminal and in-window overlay
+    pub fn lines(&self) -> Vec<String> {
+        self.to_string().lines().map(String::from).collect()
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Io { path, error } => {
+                write!(f, "can not read shader {}: {}", path.display(), error)
+            }
+            ShaderError::Compile { messages } => {
+                writeln!(f, "shader compilation failed:")?;
+                write_messages(f, messages)
+            }
+            ShaderError::Link { messages } => {
+                writeln!(f, "shader linking failed:")?;
+                write_messages(f, messages)
+            }
+            ShaderError::Unsupported(message) => write!(f, "{}", message),
+            ShaderError::Project { path, message } => {
+                write!(f, "can not load project {}: {}", path.display(), message)
+            }
+            ShaderError::Scene { path, message } => {
+                write!(f, "can not load scene {}: {}", path.display(), message)
+            }
+            ShaderError::Texture { path, message } => {
+                write!(f, "can not load texture {}: {}", path.display(), message)
+            }
+            ShaderError::Draw(message) => write!(f, "can not draw shader: {}", message),
+        }
+    }
+}
+
+fn write_messages(f: &mut fmt::Formatter<'_>, messages: &[ShaderMessage]) -> fmt::Result {
+    for message in messages {
+        match message.line {
+            Some(line) => writeln!(f, "  {}:{}: {}", message.file, line, message.message)?,
+            None => writeln!(f, "  {}: {}", message.file, message.message)?,
+        }
+    }
+    Ok(())
+}
+
+fn parse_log(log: &str, file: &str) -> Vec<ShaderMessage> {
+    log.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (line_number, message) = match parse_log_line(line) {
+                Some((line_number, message)) => (Some(line_number), message),
+                None => (None, String::from(line)),
+            };
+            ShaderMessage {
+                file: String::from(file),
+                line: line_number,
+                message,
+            }
+        })
+        .collect()
+}
+
+// compiler sees expanded source, messages point to the included files.
+// lines without a source, like declarations added around shadertoy code, stay
+fn parse_fragment_log(log: &str, source_map: &SourceMap) -> Vec<ShaderMessage> {
+    let mut messages = parse_log(log, source_map.name());
+
+    for message in messages.iter_mut() {
+        if let Some((file, line)) = message.line.and_then(|line| source_map.locate(line)) {
+            message.file = String::from(file);
+            message.line = Some(line);
+        }
+    }
+
+    messages
+}
+
+// drivers report errors in different formats:
+//   mesa:         0:12(5): error: syntax error, unexpected ...
+//   nvidia:       0(12) : error C0000: syntax error, unexpected ...
+//   amd, apple:   ERROR: 0:12: 'foo' : undeclared identifier
+fn parse_log_line(line: &str) -> Option<(u32, String)> {
+    let mut severity = None;
+    let mut rest = line;
+
+    for prefix in ["ERROR:", "WARNING:"].iter() {
+        if let Some(stripped) = line.strip_prefix(prefix) {
+            severity = Some(prefix.trim_end_matches(':').to_lowercase());
+            rest = stripped.trim_start();
+        }
+    }
+
+    let (_source, rest) = split_number(rest)?;
+
+    let (line_number, rest) = if let Some(rest) = rest.strip_prefix(':') {
+        // mesa or amd: "0:12"
+        let (line_number, rest) = split_number(rest)?;
+        let rest = match rest.strip_prefix('(') {
+            Some(column) => split_number(column)?.1.strip_prefix(')')?,
+            None => rest,
+        };
+        (line_number, rest)
+    } else {
+        // nvidia: "0(12)"
+        let (line_number, rest) = split_number(rest.strip_prefix('(')?)?;
+        (line_number, rest.strip_prefix(')')?)
+    };
+
+    let message = rest.trim_start().strip_prefix(':')?.trim();
+
+    let message = match severity {
+        Some(severity) => format!("{}: {}", severity, message),
+        None => String::from(message),
+    };
+
+    Some((line_number, message))
+}
+
+fn split_number(s: &str) -> Option<(u32, &str)> {
+    let digits = s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let number = s[..digits].parse().ok()?;
+    Some((number, &s[digits..]))
+}
+
+// lines as the drivers write them
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn driver_lines_are_parsed() {
+        assert_eq!(
+            parse_log_line(
+                "0:12(5): error: syntax error, unexpected IDENTIFIER, expecting ',' or ';'"
+            ),
+            Some((
+                12,
+                String::from("error: syntax error, unexpected IDENTIFIER, expecting ',' or ';'")
+            ))
+        );
+        assert_eq!(
+            parse_log_line(
+                "0(12) : error C0000: syntax error, unexpected identifier, expecting \"::\" at token \"foo\""
+            ),
+            Some((
+                12,
+                String::from(
+                    "error C0000: syntax error, unexpected identifier, expecting \"::\" at token \"foo\""
+                )
+            ))
+        );
+        assert_eq!(
+            parse_log_line("ERROR: 0:12: 'foo' : undeclared identifier"),
+            Some((12, String::from("error: 'foo' : undeclared identifier")))
+        );
+    }
+
+    #[test]
+    fn other_lines_are_not_messages() {
+        assert_eq!(
+            parse_log_line("ERROR: 1 compilation errors.  No code generated."),
+            None
+        );
+        assert_eq!(
+            parse_log_line("Fragment shader failed to compile with the following errors:"),
+            None
+        );
+    }
+}
@@ -0,0 +1,458 @@
+use chrono::{DateTime, Datelike, Local, Timelike};
+use glium::uniforms::{UniformType, UniformValue, Uniforms};
+use std::time::SystemTime;
+
+pub const CHANNEL_COUNT: usize = 4;
+
+// sampler type of iChannelN, depends on what is bound to the channel
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChannelType {
+    Texture2d,
+    Cubemap,
+    Texture3d,
+}
+
+impl ChannelType {
+    pub fn glsl_type(self) -> &'static str {
+        match self {
+            ChannelType::Texture2d => "sampler2D",
+            ChannelType::Cubemap => "samplerCube",
+            ChannelType::Texture3d => "sampler3D",
+        }
+    }
+
+    // what the program reports for a uniform of glsl_type
+    pub fn uniform_type(self) -> UniformType {
+        match self {
+            ChannelType::Texture2d => UniformType::Sampler2d,
+            ChannelType::Cubemap => UniformType::SamplerCube,
+            ChannelType::Texture3d => UniformType::Sampler3d,
+        }
+    }
+}
+
+// shadertoy reports this even for shaders without sound
+const SAMPLE_RATE: f32 = 44100.0;
+
+// standard shadertoy inputs, names and meaning are the same as on shadertoy.com
+#[derive(Clone)]
+pub struct ShaderToyInput {
+    pub i_resolution: [f32; 3],
+    pub i_time: f32,
+    pub i_time_delta: f32,
+    pub i_frame: i32,
+    pub i_frame_rate: f32,
+    pub i_mouse: [f32; 4],
+    pub i_date: [f32; 4],
+    pub i_sample_rate: f32,
+    pub i_channel_time: [f32; CHANNEL_COUNT],
+    pub i_channel_resolution: [[f32; 3]; CHANNEL_COUNT],
+}
+
+impl ShaderToyInput {
+    pub fn new(resolution: (u32, u32)) -> ShaderToyInput {
+        ShaderToyInput {
+            i_resolution: [resolution.0 as f32, resolution.1 as f32, 1.0],
+            i_time: 0.0,
+            i_time_delta: 0.0,
+            i_frame: 0,
+            i_frame_rate: 0.0,
+            i_mouse: [0.0; 4],
+            i_date: [0.0; 4],
+            i_sample_rate: SAMPLE_RATE,
+            // channels without input are zero, like empty channels on shadertoy
+            i_channel_time: [0.0; CHANNEL_COUNT],
+            i_channel_resolution: [[0.0; 3]; CHANNEL_COUNT],
+        }
+    }
+
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, output: &mut F) {
+        output("iResolution", UniformValue::Vec3(self.i_resolution));
+        output("iTime", UniformValue::Float(self.i_time));
+        output("iTimeDelta", UniformValue::Float(self.i_time_delta));
+        output("iFrame", UniformValue::SignedInt(self.i_frame));
+        output("iFrameRate", UniformValue::Float(self.i_frame_rate));
+        output("iMouse", UniformValue::Vec4(self.i_mouse));
+        output("iDate", UniformValue::Vec4(self.i_date));
+        output("iSampleRate", UniformValue::Float(self.i_sample_rate));
+
+        // arrays declared by the shader are known to glium as "name[i]", arrays
+        // added by wrap_main_image are separate uniforms. glium skips names the
+        // program doesn't have, so both are set
+        for channel in 0..CHANNEL_COUNT {
+            let time = UniformValue::Float(self.i_channel_time[channel]);
+            let resolution = UniformValue::Vec3(self.i_channel_resolution[channel]);
+
+            output(&format!("iChannelTime[{}]", channel), time);
+            output(&channel_element("iChannelTime", channel), time);
+            output(&format!("iChannelResolution[{}]", channel), resolution);
+            output(&channel_element("iChannelResolution", channel), resolution);
+        }
+    }
+}
+
+// adds shadertoy inputs and textures of the pass, iChannelN and named
+// samplers, to uniforms made with uniform! macro
+pub struct WithShaderToyInput<'b, U> {
+    pub uniforms: &'b U,
+    pub shader_toy_input: &'b ShaderToyInput,
+    pub textures: Vec<(&'b str, UniformValue<'b>)>,
+}
+
+impl<'b, U: Uniforms> Uniforms for WithShaderToyInput<'b, U> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        self.uniforms.visit_values(&mut output);
+        self.shader_toy_input.visit_values(&mut output);
+
+        for (name, value) in self.textures.iter() {
+            output(name, *value);
+        }
+    }
+}
+
+// year, month from 0, day of month from 1 and seconds since midnight
+pub fn date(time: SystemTime) -> [f32; 4] {
+    let time = DateTime::<Local>::from(time);
+
+    let seconds = time.num_seconds_from_midnight() as f32 + time.nanosecond() as f32 * 1e-9;
+
+    [
+        time.year() as f32,
+        time.month0() as f32,
+        time.day() as f32,
+        seconds,
+    ]
+}
+
+// mouse as shadertoy sees it:
+//   xy - position while left button is down, stays at last drag position after release
+//   zw - position of the last click, z is negative when button is up,
+//        w is positive only in the frame of the click
+// coordinates are in window pixels from top left until converted by to_image
+#[derive(Default)]
+pub struct ShaderToyMouse {
+    cursor: [f32; 2],
+    position: [f32; 2],
+    click: Option<[f32; 2]>,
+    pressed: bool,
+    clicked: bool,
+}
+
+impl ShaderToyMouse {
+    pub fn move_to(&mut self, x: f32, y: f32) {
+        self.cursor = [x, y];
+
+        if self.pressed {
+            self.position = self.cursor;
+        }
+    }
+
+    pub fn press(&mut self) {
+        self.pressed = true;
+        self.clicked = true;
+        self.position = self.cursor;
+        self.click = Some(self.cursor);
+    }
+
+    pub fn release(&mut self) {
+        self.pressed = false;
+    }
+
+    // image is shown in the window scaled by `scale` and shifted by `offset`,
+    // result is in image pixels from bottom left
+    pub fn uniform(&mut self, image_height: u32, scale: f32, offset: [f32; 2]) -> [f32; 4] {
+        // all zeros until the first click
+        let click = match self.click {
+            Some(click) => click,
+            None => return [0.0; 4],
+        };
+
+        let to_image = |point: [f32; 2]| {
+            [
+                (point[0] - offset[0]) / scale,
+                image_height as f32 - (point[1] - offset[1]) / scale,
+            ]
+        };
+
+        let position = to_image(self.position);
+        let click = to_image(click);
+
+        let z = if self.pressed { click[0] } else { -click[0] };
+        let w = if self.clicked { click[1] } else { -click[1] };
+
+        self.clicked = false;
+
+        [position[0], position[1], z, w]
+    }
+}
+
+// shadertoy shaders are glsl es 3.00, closest desktop version
+const SHADERTOY_VERSION: u32 = 330;
+
+// declarations shadertoy provides to every shader
+const SHADERTOY_UNIFORMS: &[(&str, &str)] = &[
+    ("iResolution", "uniform vec3 iResolution;"),
+    ("iTime", "uniform float iTime;"),
+    ("iTimeDelta", "uniform float iTimeDelta;"),
+    ("iFrame", "uniform int iFrame;"),
+    ("iFrameRate", "uniform float iFrameRate;"),
+    ("iMouse", "uniform vec4 iMouse;"),
+    ("iDate", "uniform vec4 iDate;"),
+    ("iSampleRate", "uniform float iSampleRate;"),
+];
+
+// per channel arrays shadertoy provides, name and element type. when only the
+// first element is used drivers report an array of one element, which glium
+// fails on, so the elements are separate uniforms copied to the array in main()
+const SHADERTOY_ARRAYS: &[(&str, &str)] =
+    &[("iChannelTime", "float"), ("iChannelResolution", "vec3")];
+
+// uniform holding one element of an array from SHADERTOY_ARRAYS
+fn channel_element(name: &str, channel: usize) -> String {
+    format!("shadertoy_{}{}", name, channel)
+}
+
+// `void name (` at the start of a line
+pub fn has_function(source: &str, name: &str) -> bool {
+    source.lines().any(|line| {
+        let line = line.trim_start();
+        line.starts_with("void")
+            && line["void".len()..].trim_start().starts_with(name)
+            && line["void".len()..].trim_start()[name.len()..]
+                .trim_start()
+                .starts_with('(')
+    })
+}
+
+// names of global variables declared with qualifier, like "uniform" or "out"
+fn declared_names<'a>(source: &'a str, qualifier: &str) -> Vec<&'a str> {
+    source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix(qualifier))
+        .filter(|declaration| declaration.starts_with(char::is_whitespace))
+        .filter_map(|declaration| declaration.split(';').next()?.split_whitespace().last())
+        .map(|name| name.split('[').next().unwrap_or(name))
+        .collect()
+}
+
+// shader written for shadertoy has mainImage and no main
+pub fn is_shadertoy_shader(source: &str) -> bool {
+    has_function(source, "mainImage") && !has_function(source, "main")
+}
+
+// makes a complete fragment shader from shadertoy code: adds #version,
+// declarations the code doesn't have yet and main() calling mainImage.
+// line numbers in compile errors still point to the original file
+pub fn wrap_main_image(source: &str, channels: &[ChannelType; CHANNEL_COUNT]) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+
+    let version_line = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with("#version"));
+
+    let version = version_line
+        .and_then(|index| lines[index].split_whitespace().nth(1))
+        .and_then(|version| version.parse::<u32>().ok())
+        .unwrap_or(SHADERTOY_VERSION);
+
+    let mut wrapped = String::new();
+
+    let code_start = match version_line {
+        Some(index) => {
+            for line in lines[..=index].iter() {
+                wrapped.push_str(line);
+                wrapped.push('\n');
+            }
+            index + 1
+        }
+        None => {
+            wrapped.push_str(&format!("#version {}\n", version));
+            0
+        }
+    };
+
+    let uniforms = declared_names(source, "uniform");
+    for (name, declaration) in SHADERTOY_UNIFORMS.iter() {
+        if !uniforms.contains(name) {
+            wrapped.push_str(declaration);
+            wrapped.push('\n');
+        }
+    }
+
+    let mut copies = String::new();
+    for (name, element_type) in SHADERTOY_ARRAYS.iter() {
+        if uniforms.contains(name) {
+            continue;
+        }
+
+        let elements: Vec<String> = (0..CHANNEL_COUNT)
+            .map(|channel| channel_element(name, channel))
+            .collect();
+        for element in elements.iter() {
+            wrapped.push_str(&format!("uniform {} {};\n", element_type, element));
+        }
+        wrapped.push_str(&format!("{} {}[{}];\n", element_type, name, CHANNEL_COUNT));
+
+        copies.push_str(&format!(
+            "    {} = {}[{}]({});\n",
+            name,
+            element_type,
+            CHANNEL_COUNT,
+            elements.join(", ")
+        ));
+    }
+
+    for (channel, channel_type) in channels.iter().enumerate() {
+        let name = format!("iChannel{}", channel);
+        if !uniforms.contains(&name.as_str()) {
+            wrapped.push_str(&format!("uniform {} {};\n", channel_type.glsl_type(), name));
+        }
+    }
+
+    // fragCoord comes from the vertex shader
+    if !declared_names(source, "in").contains(&"fragCoord") {
+        wrapped.push_str("in vec2 fragCoord;\n");
+    }
+
+    let output = match declared_names(source, "out").first() {
+        Some(name) => name.to_string(),
+        None => {
+            wrapped.push_str("out vec4 shadertoy_fragColor;\n");
+            String::from("shadertoy_fragColor")
+        }
+    };
+
+    // before glsl 3.30 #line sets number of the directive line itself
+    let line_number = if version < 330 { code_start } else { code_start + 1 };
+    wrapped.push_str(&format!("#line {}\n", line_number));
+
+    for line in lines[code_start..].iter() {
+        wrapped.push_str(line);
+        wrapped.push('\n');
+    }
+
+    wrapped.push_str(&format!(
+        "\nvoid main() {{\n{}    mainImage({}, fragCoord);\n}}\n",
+        copies, output
+    ));
+
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANNELS: [ChannelType; CHANNEL_COUNT] = [ChannelType::Texture2d; CHANNEL_COUNT];
+
+    const MAIN_IMAGE: &str = "void mainImage(out vec4 fragColor, in vec2 fragCoord) {\n\
+                              \x20   fragColor = vec4(iChannelTime[0]);\n\
+                              }";
+
+    // line following the #line directive and the number it sets
+    fn numbered_line(wrapped: &str) -> (u32, &str) {
+        let mut lines = wrapped
+            .lines()
+            .skip_while(|line| !line.starts_with("#line"));
+        let directive = lines.next().unwrap();
+        let number = directive["#line".len()..].trim().parse().unwrap();
+        (number, lines.next().unwrap())
+    }
+
+    #[test]
+    fn functions_are_found() {
+        assert!(has_function(MAIN_IMAGE, "mainImage"));
+        assert!(has_function("  void  main ( ) {}", "main"));
+        assert!(!has_function(MAIN_IMAGE, "main"));
+        assert!(!has_function("int main() {}", "main"));
+        assert!(!has_function("// void main() {}", "main"));
+        assert!(is_shadertoy_shader(MAIN_IMAGE));
+        assert!(!is_shadertoy_shader(&format!(
+            "{}\nvoid main() {{}}",
+            MAIN_IMAGE
+        )));
+    }
+
+    #[test]
+    fn line_numbers_point_to_source() {
+        let wrapped = wrap_main_image(MAIN_IMAGE, &CHANNELS);
+        assert!(wrapped.starts_with("#version 330\n"));
+        assert_eq!(
+            numbered_line(&wrapped),
+            (1, "void mainImage(out vec4 fragColor, in vec2 fragCoord) {")
+        );
+
+        // from 3.30 #line sets number of the next line
+        let source = format!("// comment\n#version 330\n{}", MAIN_IMAGE);
+        let wrapped = wrap_main_image(&source, &CHANNELS);
+        assert!(wrapped.starts_with("// comment\n#version 330\n"));
+        assert_eq!(
+            numbered_line(&wrapped),
+            (3, "void mainImage(out vec4 fragColor, in vec2 fragCoord) {")
+        );
+
+        // before 3.30 number of the directive line itself
+        for version in ["130", "150"] {
+            let source = format!("// comment\n#version {}\n{}", version, MAIN_IMAGE);
+            let wrapped = wrap_main_image(&source, &CHANNELS);
+            assert_eq!(
+                numbered_line(&wrapped),
+                (2, "void mainImage(out vec4 fragColor, in vec2 fragCoord) {")
+            );
+        }
+    }
+
+    #[test]
+    fn channel_arrays_are_copied_from_elements() {
+        let wrapped = wrap_main_image(MAIN_IMAGE, &CHANNELS);
+        assert!(wrapped.contains("uniform float shadertoy_iChannelTime3;\n"));
+        assert!(wrapped.contains("\nfloat iChannelTime[4];\n"));
+        assert!(wrapped.contains(
+            "    iChannelTime = float[4](shadertoy_iChannelTime0, shadertoy_iChannelTime1, \
+             shadertoy_iChannelTime2, shadertoy_iChannelTime3);\n"
+        ));
+        assert!(!wrapped.contains("uniform float iChannelTime"));
+
+        // arrays declared by the shader are left alone
+        let source = format!("uniform vec3 iChannelResolution[4];\n{}", MAIN_IMAGE);
+        let wrapped = wrap_main_image(&source, &CHANNELS);
+        assert!(!wrapped.contains("shadertoy_iChannelResolution"));
+        assert!(!wrapped.contains("\nvec3 iChannelResolution[4];"));
+        assert!(wrapped.contains("shadertoy_iChannelTime0"));
+    }
+
+    #[test]
+    fn mouse_follows_shadertoy_conventions() {
+        let mut mouse = ShaderToyMouse::default();
+        mouse.move_to(5.0, 5.0);
+        assert_eq!(mouse.uniform(100, 1.0, [0.0; 2]), [0.0; 4]);
+
+        // w is positive only in the frame of the click
+        mouse.move_to(10.0, 20.0);
+        mouse.press();
+        assert_eq!(mouse.uniform(100, 1.0, [0.0; 2]), [10.0, 80.0, 10.0, 80.0]);
+        assert_eq!(mouse.uniform(100, 1.0, [0.0; 2]), [10.0, 80.0, 10.0, -80.0]);
+
+        mouse.move_to(30.0, 40.0);
+        assert_eq!(mouse.uniform(100, 1.0, [0.0; 2]), [30.0, 60.0, 10.0, -80.0]);
+
+        // z turns negative on release, xy stay at the last drag position
+        mouse.release();
+        mouse.move_to(50.0, 50.0);
+        assert_eq!(
+            mouse.uniform(100, 1.0, [0.0; 2]),
+            [30.0, 60.0, -10.0, -80.0]
+        );
+
+        mouse.press();
+        assert_eq!(mouse.uniform(100, 1.0, [0.0; 2]), [50.0, 50.0, 50.0, 50.0]);
+    }
+
+    #[test]
+    fn mouse_is_in_image_pixels() {
+        let mut mouse = ShaderToyMouse::default();
+        mouse.move_to(24.0, 26.0);
+        mouse.press();
+        assert_eq!(mouse.uniform(50, 2.0, [4.0, 6.0]), [10.0, 40.0, 10.0, 40.0]);
+    }
+}
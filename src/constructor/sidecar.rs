@@ -0,0 +1,108 @@
+use ron::ser::PrettyConfig;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// files the app keeps next to a shader or project: presets, views and
+// camera paths, and recorded sessions. all are ron that can be edited by hand
+
+// file next to the shader or project, "<shader file>.<extension>"
+pub fn sidecar_path(shader_path: &Path, extension: &str) -> PathBuf {
+    let mut file_name = shader_path
+        .file_name()
+        .map(OsString::from)
+        .unwrap_or_default();
+    file_name.push(".");
+    file_name.push(extension);
+    shader_path.with_file_name(file_name)
+}
+
+// what names the content of the file in messages, like "presets"
+pub enum SidecarError {
+    Read {
+        what: &'static str,
+        path: PathBuf,
+        error: io::Error,
+    },
+    // also content that is read but makes no sense
+    Parse {
+        what: &'static str,
+        path: PathBuf,
+        message: String,
+    },
+    Write {
+        what: &'static str,
+        path: PathBuf,
+        error: io::Error,
+    },
+}
+
+impl fmt::Display for SidecarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SidecarError::Read { what, path, error } => {
+                write!(f, "can not read {} {}: {}", what, path.display(), error)
+            }
+            SidecarError::Parse {
+                what,
+                path,
+                message,
+            } => write!(f, "can not read {} {}: {}", what, path.display(), message),
+            SidecarError::Write { what, path, error } => {
+                write!(f, "can not save {} {}: {}", what, path.display(), error)
+            }
+        }
+    }
+}
+
+pub fn read<T: DeserializeOwned>(what: &'static str, path: &Path) -> Result<T, SidecarError> {
+    let text = fs::read_to_string(path).map_err(|error| SidecarError::Read {
+        what,
+        path: path.to_path_buf(),
+        error,
+    })?;
+
+    ron::from_str(&text).map_err(|error| SidecarError::Parse {
+        what,
+        path: path.to_path_buf(),
+        message: error.to_string(),
+    })
+}
+
+// missing file is empty, nothing was saved yet
+pub fn read_or_default<T: DeserializeOwned + Default>(
+    what: &'static str,
+    path: &Path,
+) -> Result<T, SidecarError> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    read(what, path)
+}
+
+pub fn write<T: Serialize + ?Sized>(
+    what: &'static str,
+    path: &Path,
+    value: &T,
+) -> Result<(), SidecarError> {
+    let text = ron::ser::to_string_pretty(value, PrettyConfig::new())
+        .expect("sidecar files are always serializable");
+
+    fs::write(path, text).map_err(|error| SidecarError::Write {
+        what,
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+pub fn remove(what: &'static str, path: &Path) -> Result<(), SidecarError> {
+    fs::remove_file(path).map_err(|error| SidecarError::Write {
+        what,
+        path: path.to_path_buf(),
+        error,
+    })
+}
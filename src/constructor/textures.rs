@@ -0,0 +1,342 @@
+use glium::backend::Facade;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{
+    ClientFormat, CubeLayer, Cubemap, MipmapsOption, RawImage2d, RawImage3d, SrgbCubemap,
+    SrgbTexture2d, SrgbTexture3d, Texture2d, Texture3d,
+};
+use glium::uniforms::{
+    MagnifySamplerFilter, MinifySamplerFilter, SamplerBehavior, SamplerWrapFunction, UniformValue,
+};
+use glium::Surface;
+use image::{imageops, RgbaImage};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use super::project::{Filter, Input, SamplerOptions, Wrap};
+use super::shader_error::ShaderError;
+
+// order of Input::Cubemap faces
+const CUBE_LAYERS: [CubeLayer; 6] = [
+    CubeLayer::PositiveX,
+    CubeLayer::NegativeX,
+    CubeLayer::PositiveY,
+    CubeLayer::NegativeY,
+    CubeLayer::PositiveZ,
+    CubeLayer::NegativeZ,
+];
+
+// texture loaded from disk, srgb textures are decoded to linear by the gpu
+pub enum Texture {
+    Texture2d(Texture2d),
+    SrgbTexture2d(SrgbTexture2d),
+    Texture3d(Texture3d),
+    SrgbTexture3d(SrgbTexture3d),
+    Cubemap(Cubemap),
+    SrgbCubemap(SrgbCubemap),
+}
+
+impl Texture {
+    pub fn uniform_value(&self, sampler: SamplerBehavior) -> UniformValue<'_> {
+        match self {
+            Texture::Texture2d(texture) => UniformValue::Texture2d(texture, Some(sampler)),
+            Texture::SrgbTexture2d(texture) => UniformValue::SrgbTexture2d(texture, Some(sampler)),
+            Texture::Texture3d(texture) => UniformValue::Texture3d(texture, Some(sampler)),
+            Texture::SrgbTexture3d(texture) => UniformValue::SrgbTexture3d(texture, Some(sampler)),
+            Texture::Cubemap(texture) => UniformValue::Cubemap(texture, Some(sampler)),
+            Texture::SrgbCubemap(texture) => UniformValue::SrgbCubemap(texture, Some(sampler)),
+        }
+    }
+
+    // value of iChannelResolution
+    pub fn resolution(&self) -> [f32; 3] {
+        let (width, height, depth) = match self {
+            Texture::Texture2d(texture) => (texture.width(), texture.height(), 1),
+            Texture::SrgbTexture2d(texture) => (texture.width(), texture.height(), 1),
+            Texture::Texture3d(texture) => (texture.width(), texture.height(), texture.depth()),
+            Texture::SrgbTexture3d(texture) => (texture.width(), texture.height(), texture.depth()),
+            Texture::Cubemap(texture) => (texture.width(), texture.height(), 1),
+            Texture::SrgbCubemap(texture) => (texture.width(), texture.height(), 1),
+        };
+
+        [width as f32, height as f32, depth as f32]
+    }
+}
+
+pub fn sampler_behavior(options: &SamplerOptions) -> SamplerBehavior {
+    let wrap = match options.wrap {
+        Wrap::Clamp => SamplerWrapFunction::Clamp,
+        Wrap::Repeat => SamplerWrapFunction::Repeat,
+        Wrap::Mirror => SamplerWrapFunction::Mirror,
+    };
+
+    let (minify_filter, magnify_filter) = match (options.filter, options.mipmaps) {
+        (Filter::Nearest, false) => (MinifySamplerFilter::Nearest, MagnifySamplerFilter::Nearest),
+        (Filter::Nearest, true) => (
+            MinifySamplerFilter::NearestMipmapNearest,
+            MagnifySamplerFilter::Nearest,
+        ),
+        (Filter::Linear, false) => (MinifySamplerFilter::Linear, MagnifySamplerFilter::Linear),
+        (Filter::Linear, true) => (
+            MinifySamplerFilter::LinearMipmapLinear,
+            MagnifySamplerFilter::Linear,
+        ),
+    };
+
+    SamplerBehavior {
+        wrap_function: (wrap, wrap, wrap),
+        minify_filter,
+        magnify_filter,
+        ..Default::default()
+    }
+}
+
+pub fn load_texture<F: Facade>(facade: &F, input: &Input) -> Result<Texture, ShaderError> {
+    match input {
+        Input::Buffer(_) => unreachable!("buffers are rendered, not loaded"),
+        Input::Texture { path, sampler } => load_texture_2d(facade, path, sampler),
+        Input::Cubemap { faces, sampler } => {
+            let vflip = sampler.vflip.unwrap_or(false);
+            let images = faces
+                .iter()
+                .map(|path| open_image(path, vflip).map(|image| (path.clone(), image)))
+                .collect::<Result<Vec<_>, _>>()?;
+            load_cubemap(facade, &faces[0], images, sampler)
+        }
+        Input::CubemapCross { path, sampler } => {
+            let image = open_image(path, false)?;
+            let faces = split_cross(path, &image, sampler.vflip.unwrap_or(false))?;
+            load_cubemap(facade, path, faces, sampler)
+        }
+        Input::Volume {
+            path,
+            depth,
+            sampler,
+        } => load_volume(facade, path, *depth, sampler),
+    }
+}
+
+fn texture_error(path: &Path, message: String) -> ShaderError {
+    ShaderError::Texture {
+        path: path.to_path_buf(),
+        message,
+    }
+}
+
+fn mipmaps_option(options: &SamplerOptions) -> MipmapsOption {
+    if options.mipmaps {
+        MipmapsOption::AutoGeneratedMipmaps
+    } else {
+        MipmapsOption::NoMipmap
+    }
+}
+
+// rows go to gpu in file order, first row is at the bottom of the texture.
+// vflip turns image over so it is upright in texture coordinates
+fn open_image(path: &Path, vflip: bool) -> Result<RgbaImage, ShaderError> {
+    let mut image = image::open(path)
+        .map_err(|error| texture_error(path, error.to_string()))?
+        .into_rgba8();
+
+    if vflip {
+        imageops::flip_vertical_in_place(&mut image);
+    }
+
+    Ok(image)
+}
+
+fn load_texture_2d<F: Facade>(
+    facade: &F,
+    path: &Path,
+    options: &SamplerOptions,
+) -> Result<Texture, ShaderError> {
+    let image = open_image(path, options.vflip.unwrap_or(true))?;
+    let dimensions = image.dimensions();
+    let raw = RawImage2d::from_raw_rgba(image.into_raw(), dimensions);
+    let mipmaps = mipmaps_option(options);
+
+    let texture = if options.srgb {
+        SrgbTexture2d::with_mipmaps(facade, raw, mipmaps).map(Texture::SrgbTexture2d)
+    } else {
+        Texture2d::with_mipmaps(facade, raw, mipmaps).map(Texture::Texture2d)
+    };
+
+    texture.map_err(|error| texture_error(path, error.to_string()))
+}
+
+fn load_volume<F: Facade>(
+    facade: &F,
+    path: &Path,
+    depth: Option<u32>,
+    options: &SamplerOptions,
+) -> Result<Texture, ShaderError> {
+    let image = open_image(path, false)?;
+    let (width, height) = image.dimensions();
+    let depth = depth.unwrap_or(width);
+
+    if depth == 0 || height % depth != 0 {
+        return Err(texture_error(
+            path,
+            format!(
+                "image height {} is not a multiple of volume depth {}",
+                height, depth
+            ),
+        ));
+    }
+
+    let slice_height = height / depth;
+    let vflip = options.vflip.unwrap_or(true);
+
+    // slices are flipped one by one, so the first slice stays at z = 0
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+    for slice in 0..depth {
+        let mut slice =
+            imageops::crop_imm(&image, 0, slice * slice_height, width, slice_height).to_image();
+        if vflip {
+            imageops::flip_vertical_in_place(&mut slice);
+        }
+        data.extend_from_slice(slice.as_raw());
+    }
+
+    let raw = RawImage3d {
+        data: Cow::Owned(data),
+        width,
+        height: slice_height,
+        depth,
+        format: ClientFormat::U8U8U8U8,
+    };
+    let mipmaps = mipmaps_option(options);
+
+    let texture = if options.srgb {
+        SrgbTexture3d::with_mipmaps(facade, raw, mipmaps).map(Texture::SrgbTexture3d)
+    } else {
+        Texture3d::with_mipmaps(facade, raw, mipmaps).map(Texture::Texture3d)
+    };
+
+    texture.map_err(|error| texture_error(path, error.to_string()))
+}
+
+// horizontal cross:     vertical cross:
+//       +Y                    +Y
+//   -X  +Z  +X  -Z        -X  +Z  +X
+//       -Y                    -Y
+//                             -Z (upside down)
+fn split_cross(
+    path: &Path,
+    image: &RgbaImage,
+    vflip: bool,
+) -> Result<Vec<(PathBuf, RgbaImage)>, ShaderError> {
+    let (width, height) = image.dimensions();
+
+    let (size, cells, vertical) = if width * 3 == height * 4 {
+        (
+            width / 4,
+            [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)],
+            false,
+        )
+    } else if width * 4 == height * 3 {
+        (
+            width / 3,
+            [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)],
+            true,
+        )
+    } else {
+        return Err(texture_error(
+            path,
+            format!(
+                "{}x{} is not a cube map cross, expected 4:3 or 3:4 image",
+                width, height
+            ),
+        ));
+    };
+
+    let faces = cells
+        .iter()
+        .enumerate()
+        .map(|(index, (column, row))| {
+            let mut face =
+                imageops::crop_imm(image, column * size, row * size, size, size).to_image();
+
+            if vertical && CUBE_LAYERS[index] == CubeLayer::NegativeZ {
+                face = imageops::rotate180(&face);
+            }
+            if vflip {
+                imageops::flip_vertical_in_place(&mut face);
+            }
+
+            (path.to_path_buf(), face)
+        })
+        .collect();
+
+    Ok(faces)
+}
+
+// glium can't upload pixels to cube maps, so every face is uploaded to
+// a 2d texture first and copied to its cube map layer
+fn load_cubemap<F: Facade>(
+    facade: &F,
+    path: &Path,
+    faces: Vec<(PathBuf, RgbaImage)>,
+    options: &SamplerOptions,
+) -> Result<Texture, ShaderError> {
+    let size = faces[0].1.width();
+
+    for (face_path, face) in faces.iter() {
+        if face.dimensions() != (size, size) {
+            return Err(texture_error(
+                face_path,
+                format!(
+                    "cube map faces must be square and of the same size, expected {}x{}, got {}x{}",
+                    size,
+                    size,
+                    face.width(),
+                    face.height()
+                ),
+            ));
+        }
+    }
+
+    let error = |error: String| texture_error(path, error);
+    let mipmaps = mipmaps_option(options);
+
+    let texture = if options.srgb {
+        let cubemap = SrgbCubemap::empty_with_mipmaps(facade, mipmaps, size)
+            .map_err(|e| error(e.to_string()))?;
+
+        for (layer, (_, face)) in CUBE_LAYERS.iter().zip(faces) {
+            let raw = RawImage2d::from_raw_rgba(face.into_raw(), (size, size));
+            let source = SrgbTexture2d::new(facade, raw).map_err(|e| error(e.to_string()))?;
+            let source =
+                SimpleFrameBuffer::new(facade, &source).map_err(|e| error(e.to_string()))?;
+            let target = SimpleFrameBuffer::new(facade, cubemap.main_level().image(*layer))
+                .map_err(|e| error(e.to_string()))?;
+            source.fill(&target, MagnifySamplerFilter::Nearest);
+        }
+
+        if options.mipmaps {
+            unsafe { cubemap.generate_mipmaps() };
+        }
+
+        Texture::SrgbCubemap(cubemap)
+    } else {
+        let cubemap =
+            Cubemap::empty_with_mipmaps(facade, mipmaps, size).map_err(|e| error(e.to_string()))?;
+
+        for (layer, (_, face)) in CUBE_LAYERS.iter().zip(faces) {
+            let raw = RawImage2d::from_raw_rgba(face.into_raw(), (size, size));
+            let source = Texture2d::new(facade, raw).map_err(|e| error(e.to_string()))?;
+            let target = SimpleFrameBuffer::new(facade, cubemap.main_level().image(*layer))
+                .map_err(|e| error(e.to_string()))?;
+            source
+                .as_surface()
+                .fill(&target, MagnifySamplerFilter::Nearest);
+        }
+
+        if options.mipmaps {
+            unsafe { cubemap.generate_mipmaps() };
+        }
+
+        Texture::Cubemap(cubemap)
+    };
+
+    Ok(texture)
+}
@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::render::FrameInput;
+use super::sidecar::{self, sidecar_path, SidecarError};
+
+// views are kept next to the shader in "<shader file>.views"
+const VIEWS_EXTENSION: &str = "views";
+
+// length of the flight to a recalled view, in seconds of shader time
+const TRANSITION_TIME: f32 = 0.8;
+
+// saved camera, recalled with its number key:
+//   [
+//       (key: 1, name: "entrance", camera_position: (0.0, 1.0, -8.0), yaw: 0.0, pitch: 0.0, speed: 200.0),
+//   ]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct View {
+    pub key: u32,
+    pub name: String,
+    pub camera_position: [f32; 3],
+    // degrees, like --yaw and --pitch
+    pub yaw: f32,
+    pub pitch: f32,
+    pub speed: f32,
+}
+
+// camera flight from where it was to a recalled view
+pub struct CameraTransition {
+    from_position: [f32; 3],
+    from_angles: [f32; 2],
+    to_position: [f32; 3],
+    to_angles: [f32; 2],
+    progress: f32,
+}
+
+impl CameraTransition {
+    // yaw turns the short way round
+    fn new(frame_input: &FrameInput, view: &View) -> CameraTransition {
+        let from_yaw = frame_input.last_angle_x;
+        let turn = (view.yaw.to_radians() - from_yaw + PI).rem_euclid(2.0 * PI) - PI;
+
+        CameraTransition {
+            from_position: frame_input.camera_position,
+            from_angles: [from_yaw, frame_input.last_angle_y],
+            to_position: view.camera_position,
+            to_angles: [from_yaw + turn, view.pitch.to_radians()],
+            progress: 0.0,
+        }
+    }
+
+    // moves transition forward by time step, returns false when it is over
+    pub fn step(&mut self, time_step: f32) -> bool {
+        self.progress = (self.progress + time_step / TRANSITION_TIME).min(1.0);
+        self.progress < 1.0
+    }
+
+    // smoothstep, camera speeds up and slows down
+    pub fn position(&self) -> [f32; 3] {
+        let t = self.eased();
+        let mut position = [0.0; 3];
+        for (axis, value) in position.iter_mut().enumerate() {
+            *value =
+                self.from_position[axis] + (self.to_position[axis] - self.from_position[axis]) * t;
+        }
+        position
+    }
+
+    pub fn angles(&self) -> [f32; 2] {
+        let t = self.eased();
+        [
+            self.from_angles[0] + (self.to_angles[0] - self.from_angles[0]) * t,
+            self.from_angles[1] + (self.to_angles[1] - self.from_angles[1]) * t,
+        ]
+    }
+
+    fn eased(&self) -> f32 {
+        self.progress * self.progress * (3.0 - 2.0 * self.progress)
+    }
+}
+
+pub enum ViewError {
+    File(SidecarError),
+    NotFound { path: PathBuf, key: u32 },
+}
+
+impl fmt::Display for ViewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViewError::File(error) => write!(f, "{}", error),
+            ViewError::NotFound { path, key } => write!(
+                f,
+                "no view {} in {}, Ctrl+{} saves one",
+                key,
+                path.display(),
+                key
+            ),
+        }
+    }
+}
+
+impl From<SidecarError> for ViewError {
+    fn from(error: SidecarError) -> ViewError {
+        ViewError::File(error)
+    }
+}
+
+// camera bookmarks of one shader or project. like presets, the file
+// is read again on every use so hand edits are picked up
+pub struct Views {
+    path: PathBuf,
+    // views of a replayed session, they are used instead of the file
+    replayed: Option<Vec<View>>,
+}
+
+impl Views {
+    pub fn new(shader_path: &Path) -> Views {
+        Views {
+            path: sidecar_path(shader_path, VIEWS_EXTENSION),
+            replayed: None,
+        }
+    }
+
+    // views as they are now, kept in a recorded session
+    pub fn snapshot(&self) -> Result<Vec<View>, SidecarError> {
+        self.read()
+    }
+
+    // from now on views are the recorded ones, saved ones go to them
+    pub fn replay(&mut self, views: Vec<View>) {
+        self.replayed = Some(views);
+    }
+
+    // replaces the view with this key, its name is kept
+    pub fn save(&mut self, key: u32, frame_input: &FrameInput) -> Result<String, ViewError> {
+        let mut views = self.read()?;
+
+        let name = views
+            .iter()
+            .find(|view| view.key == key)
+            .map_or_else(|| format!("view {}", key), |view| view.name.clone());
+
+        views.retain(|view| view.key != key);
+        views.push(View {
+            key,
+            name: name.clone(),
+            camera_position: frame_input.camera_position,
+            yaw: frame_input.last_angle_x.to_degrees(),
+            pitch: frame_input.last_angle_y.to_degrees(),
+            speed: frame_input.camera_speed,
+        });
+        views.sort_by_key(|view| view.key);
+
+        self.write(views)?;
+
+        Ok(format!("saved {} as {}", name, key))
+    }
+
+    // camera flies to the view over a short time
+    pub fn recall(&self, key: u32, frame_input: &mut FrameInput) -> Result<String, ViewError> {
+        let view = self
+            .read()?
+            .into_iter()
+            .find(|view| view.key == key)
+            .ok_or_else(|| ViewError::NotFound {
+                path: self.path.clone(),
+                key,
+            })?;
+
+        frame_input.camera_speed = view.speed;
+        frame_input.transition = Some(CameraTransition::new(frame_input, &view));
+
+        Ok(format!("{} {}", key, view.name))
+    }
+
+    fn read(&self) -> Result<Vec<View>, SidecarError> {
+        match &self.replayed {
+            Some(views) => Ok(views.clone()),
+            None => sidecar::read_or_default("views", &self.path),
+        }
+    }
+
+    fn write(&mut self, views: Vec<View>) -> Result<(), SidecarError> {
+        match &mut self.replayed {
+            Some(replayed) => {
+                *replayed = views;
+                Ok(())
+            }
+            None => sidecar::write("views", &self.path, &views),
+        }
+    }
+}
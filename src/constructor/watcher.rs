@@ -0,0 +1,53 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time;
+
+// polls modification time of a set of files.
+// polling is used instead of os notifications because editors replace files
+// in many different ways (truncate, rename, write to temp file) and stat-ing
+// a handful of files once per frame is cheap.
+pub struct FileWatcher {
+    files: Vec<(PathBuf, Option<time::SystemTime>)>,
+}
+
+fn modified_time(path: &Path) -> Option<time::SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+impl FileWatcher {
+    pub fn new(paths: Vec<PathBuf>) -> FileWatcher {
+        let mut watcher = FileWatcher { files: Vec::new() };
+        watcher.set_paths(paths);
+        watcher
+    }
+
+    // replaces watched files and remembers their current state,
+    // so set_paths itself never triggers a change
+    pub fn set_paths(&mut self, paths: Vec<PathBuf>) {
+        self.files = paths
+            .into_iter()
+            .map(|path| {
+                let modified = modified_time(&path);
+                (path, modified)
+            })
+            .collect();
+    }
+
+    // returns true once for every change of any watched file
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+
+        for (path, last_modified) in self.files.iter_mut() {
+            let modified = modified_time(path);
+
+            // file can be missing for a moment while editor saves it,
+            // wait until it appears again
+            if modified.is_some() && modified != *last_modified {
+                *last_modified = modified;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+}
@@ -0,0 +1,3 @@
+#[macro_use]
+extern crate glium;
+pub mod constructor;
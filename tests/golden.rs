@@ -0,0 +1,336 @@
+// renders every shader and project from shaders/ headlessly and compares
+// the frame with reference image in tests/golden/. shaders next to a
+// project file are rendered only as part of the project. sessions from
+// tests/sessions/ are replayed and their last frame is compared too.
+//
+//   cargo test --test golden                     compare
+//   UPDATE_GOLDEN=1 cargo test --test golden     rewrite reference images
+//
+// when a shader differs, image with differing pixels marked red is written
+// to target/tmp/golden/<name>.diff.png next to the rendered frame
+
+use image::{Rgba, RgbaImage};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SHADERS_DIR: &str = "shaders";
+const SESSIONS_DIR: &str = "tests/sessions";
+const GOLDEN_DIR: &str = "tests/golden";
+
+// small frames keep software rendering fast
+const RESOLUTION: &str = "160x90";
+const CAMERA: &str = "0,1,0";
+// frame 1 at 1 fps, iTime is 1.0. frame 0 is rendered too,
+// so buffers of multipass projects have a previous frame
+const FPS: &str = "1";
+const FRAMES: &str = "0..2";
+const FRAME_FILE: &str = "frame_1.png";
+
+// gpus and drivers round differently, so channels may differ a little
+const TOLERANCE: u8 = 12;
+// raymarching can hit or miss on silhouettes, allow a few such pixels
+const MAX_BAD_PIXELS: f64 = 0.005;
+
+// shaders that can't be rendered yet
+const SKIP: &[&str] = &[
+    // needs iChannel0 texture, rendered by projects/tunnel
+    "test_tunnel.frag",
+];
+
+const NO_HEADLESS: &str = "can not create headless gl context";
+
+// the part of a session file the test needs
+#[derive(Deserialize)]
+struct SessionLength {
+    frames: u32,
+}
+
+enum Render {
+    Image(RgbaImage),
+    NoHeadless(String),
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().is_some_and(|ext| ext == extension)
+}
+
+fn is_scene_file(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    name.ends_with(".scene.ron") || name.ends_with(".scene.json")
+}
+
+fn shader_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let paths: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+
+    let is_project_dir = paths
+        .iter()
+        .any(|path| has_extension(path, "ron") && !is_scene_file(path));
+
+    for path in paths {
+        if path.is_dir() {
+            shader_files(&path, files);
+        } else if has_extension(&path, "ron")
+            || is_scene_file(&path)
+            || (!is_project_dir && has_extension(&path, "frag"))
+        {
+            files.push(path);
+        }
+    }
+}
+
+// "shaders/templates/star.frag" -> "templates_star",
+// "shaders/scenes/house.scene.ron" -> "scenes_house"
+fn golden_name(shader: &Path) -> String {
+    let mut name = shader.strip_prefix(SHADERS_DIR).unwrap().with_extension("");
+    if is_scene_file(shader) {
+        name = name.with_extension("");
+    }
+
+    name.iter()
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+fn render_command(output: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_constructor"));
+    command
+        .arg("render")
+        .arg("--headless")
+        .args(["--resolution", RESOLUTION])
+        .args(["--pattern", "frame_#"])
+        .args(["--format", "png"])
+        .arg("--output")
+        .arg(output);
+    command
+}
+
+// scene files are seen from their own camera
+fn render(shader: &Path, output: &Path) -> Result<Render, String> {
+    let mut command = render_command(output);
+    if !is_scene_file(shader) {
+        command.args(["--camera", CAMERA]);
+    }
+    command
+        .args(["--fps", FPS])
+        .args(["--frames", FRAMES])
+        .arg(shader);
+
+    run(command, &output.join(FRAME_FILE))
+}
+
+// file name of the last frame of a replayed session
+fn last_frame_file(session: &Path) -> Result<String, String> {
+    let text = fs::read_to_string(session).map_err(|error| error.to_string())?;
+    let length: SessionLength = ron::from_str(&text).map_err(|error| error.to_string())?;
+
+    Ok(format!("frame_{}.png", length.frames.max(1) - 1))
+}
+
+// session is replayed from the start, frames and time step are its own
+fn replay(session: &Path, output: &Path, frame: &Path) -> Result<Render, String> {
+    let mut command = render_command(output);
+    command.arg("--replay").arg(session);
+
+    run(command, frame)
+}
+
+fn run(mut command: Command, frame: &Path) -> Result<Render, String> {
+    let result = command.output().map_err(|error| error.to_string())?;
+
+    let stderr = String::from_utf8_lossy(&result.stderr).into_owned();
+
+    if stderr.contains(NO_HEADLESS) {
+        return Ok(Render::NoHeadless(stderr));
+    }
+
+    if !result.status.success() {
+        return Err(stderr);
+    }
+
+    image::open(frame)
+        .map(|image| Render::Image(image.into_rgba8()))
+        .map_err(|error| error.to_string())
+}
+
+// returns number of pixels out of tolerance and image where they are red
+fn compare(actual: &RgbaImage, expected: &RgbaImage) -> (usize, RgbaImage) {
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    let mut bad_pixels = 0;
+
+    for (x, y, pixel) in actual.enumerate_pixels() {
+        let reference = expected.get_pixel(x, y);
+
+        let out_of_tolerance = pixel
+            .0
+            .iter()
+            .zip(reference.0.iter())
+            .any(|(a, b)| a.abs_diff(*b) > TOLERANCE);
+
+        let marked = if out_of_tolerance {
+            bad_pixels += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            // dimmed frame so the red pixels can be located
+            let [r, g, b, _] = pixel.0;
+            Rgba([r / 4, g / 4, b / 4, 255])
+        };
+
+        diff.put_pixel(x, y, marked);
+    }
+
+    (bad_pixels, diff)
+}
+
+// compares rendered frame with its reference image, or rewrites the
+// reference when updating. without gl nothing is checked, which must
+// not pass as if the images matched
+fn check(
+    name: &str,
+    rendered: Result<Render, String>,
+    frame: &Path,
+    out_dir: &Path,
+    failures: &mut Vec<String>,
+) {
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let golden = Path::new(GOLDEN_DIR).join(name).with_extension("png");
+
+    let actual = match rendered {
+        Ok(Render::Image(image)) => image,
+        Ok(Render::NoHeadless(message)) => {
+            panic!("golden images need headless gl: {}", message.trim())
+        }
+        Err(error) => {
+            failures.push(format!("{}: render failed:\n{}", name, error.trim()));
+            return;
+        }
+    };
+
+    if update {
+        fs::create_dir_all(GOLDEN_DIR).unwrap();
+        actual.save(&golden).unwrap();
+        return;
+    }
+
+    let expected = match image::open(&golden) {
+        Ok(image) => image.into_rgba8(),
+        Err(error) => {
+            failures.push(format!(
+                "{}: no reference image {} ({}), run with UPDATE_GOLDEN=1 to create it",
+                name,
+                golden.display(),
+                error
+            ));
+            return;
+        }
+    };
+
+    if actual.dimensions() != expected.dimensions() {
+        failures.push(format!(
+            "{}: size {:?} differs from reference {:?}",
+            name,
+            actual.dimensions(),
+            expected.dimensions()
+        ));
+        return;
+    }
+
+    let (bad_pixels, diff) = compare(&actual, &expected);
+    let pixel_count = (actual.width() * actual.height()) as usize;
+
+    if bad_pixels as f64 > pixel_count as f64 * MAX_BAD_PIXELS {
+        let diff_path = out_dir.join(format!("{}.diff.png", name));
+        diff.save(&diff_path).unwrap();
+
+        failures.push(format!(
+            "{}: {} of {} pixels differ, see {} and {}",
+            name,
+            bad_pixels,
+            pixel_count,
+            frame.display(),
+            diff_path.display()
+        ));
+    }
+}
+
+#[test]
+fn shaders_match_golden_images() {
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+
+    let mut shaders = Vec::new();
+    shader_files(Path::new(SHADERS_DIR), &mut shaders);
+    shaders.sort();
+
+    let mut failures = Vec::new();
+
+    for shader in shaders.iter() {
+        if SKIP.iter().any(|skip| shader.ends_with(skip)) {
+            continue;
+        }
+
+        let name = golden_name(shader);
+        let output = out_dir.join(&name);
+
+        let _ = fs::remove_dir_all(&output);
+
+        let rendered = render(shader, &output);
+        check(
+            &name,
+            rendered,
+            &output.join(FRAME_FILE),
+            &out_dir,
+            &mut failures,
+        );
+    }
+
+    assert!(
+        failures.is_empty(),
+        "golden images don't match:\n{}",
+        failures.join("\n")
+    );
+}
+
+// "tests/sessions/walk.session" has reference image "session_walk.png"
+#[test]
+fn sessions_match_golden_images() {
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+
+    let mut sessions: Vec<PathBuf> = fs::read_dir(SESSIONS_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| has_extension(path, "session"))
+        .collect();
+    sessions.sort();
+
+    let mut failures = Vec::new();
+
+    for session in sessions.iter() {
+        let name = format!("session_{}", session.file_stem().unwrap().to_string_lossy());
+        let output = out_dir.join(&name);
+
+        let _ = fs::remove_dir_all(&output);
+
+        let frame = match last_frame_file(session) {
+            Ok(file) => output.join(file),
+            Err(error) => {
+                failures.push(format!("{}: can not read session: {}", name, error));
+                continue;
+            }
+        };
+
+        let rendered = replay(session, &output, &frame);
+        check(&name, rendered, &frame, &out_dir, &mut failures);
+    }
+
+    assert!(
+        failures.is_empty(),
+        "replayed sessions don't match golden images:\n{}",
+        failures.join("\n")
+    );
+}